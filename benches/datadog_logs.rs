@@ -0,0 +1,172 @@
+use std::net::SocketAddr;
+
+use criterion::{
+    criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, SamplingMode, Throughput,
+};
+use futures::TryFutureExt;
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Response, Server, StatusCode,
+};
+use indoc::formatdoc;
+use tokio::runtime::Runtime;
+use vector::{
+    config,
+    test_util::{next_addr, random_lines, runtime, send_lines, start_topology, wait_for_tcp},
+    Error,
+};
+
+fn benchmark_encode_concurrency(c: &mut Criterion) {
+    let num_lines: usize = 20_000;
+    let line_size: usize = 1_000;
+
+    let in_addr = next_addr();
+    let out_addr = next_addr();
+
+    let _srv = serve(out_addr);
+
+    let mut group = c.benchmark_group("datadog_logs");
+    group.throughput(Throughput::Bytes((num_lines * line_size) as u64));
+    group.sampling_mode(SamplingMode::Flat);
+
+    for concurrency in [1, 2, 4, 8] {
+        group.bench_with_input(
+            BenchmarkId::new("encode_concurrency", concurrency),
+            &concurrency,
+            |b, concurrency| {
+                b.iter_batched(
+                    || {
+                        // Small batches compressed at the best level, so that encoding rather
+                        // than sending bounds the throughput.
+                        let config = formatdoc! {r#"
+                            [sources.in]
+                              type = "socket"
+                              mode = "tcp"
+                              address = "{in_addr}"
+
+                            [sinks.out]
+                              type = "datadog_logs"
+                              inputs = ["in"]
+                              default_api_key = "atoken"
+                              endpoint = "http://{out_addr}"
+                              compression = {{ algorithm = "gzip", level = 9 }}
+                              encode_concurrency = {concurrency}
+                              batch.max_events = 100
+                              healthcheck.enabled = false
+                        "#, in_addr = in_addr, out_addr = out_addr, concurrency = concurrency};
+                        let config = config::load_from_str(&config, config::Format::Toml)
+                            .expect("invalid TOML configuration");
+
+                        let rt = runtime();
+                        let topology = rt.block_on(async move {
+                            let (topology, _crash) = start_topology(config, false).await;
+                            wait_for_tcp(in_addr).await;
+                            topology
+                        });
+                        (rt, topology)
+                    },
+                    |(rt, topology)| {
+                        rt.block_on(async move {
+                            let lines = random_lines(line_size).take(num_lines);
+                            send_lines(in_addr, lines).await.unwrap();
+                            topology.stop().await;
+                        })
+                    },
+                    BatchSize::PerIteration,
+                )
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn benchmark_request_rate(c: &mut Criterion) {
+    let num_lines: usize = 20_000;
+    let line_size: usize = 100;
+
+    let in_addr = next_addr();
+    let out_addr = next_addr();
+
+    let _srv = serve(out_addr);
+
+    let mut group = c.benchmark_group("datadog_logs");
+    group.throughput(Throughput::Bytes((num_lines * line_size) as u64));
+    group.sampling_mode(SamplingMode::Flat);
+
+    for compression in ["none", "gzip"] {
+        group.bench_with_input(
+            BenchmarkId::new("request_rate", compression),
+            &compression,
+            |b, compression| {
+                b.iter_batched(
+                    || {
+                        // Many small requests, so that the per-request cost of building bodies
+                        // outweighs the cost of encoding their events.
+                        let config = formatdoc! {r#"
+                            [sources.in]
+                              type = "socket"
+                              mode = "tcp"
+                              address = "{in_addr}"
+
+                            [sinks.out]
+                              type = "datadog_logs"
+                              inputs = ["in"]
+                              default_api_key = "atoken"
+                              endpoint = "http://{out_addr}"
+                              compression = "{compression}"
+                              batch.max_events = 10
+                              healthcheck.enabled = false
+                        "#, in_addr = in_addr, out_addr = out_addr, compression = compression};
+                        let config = config::load_from_str(&config, config::Format::Toml)
+                            .expect("invalid TOML configuration");
+
+                        let rt = runtime();
+                        let topology = rt.block_on(async move {
+                            let (topology, _crash) = start_topology(config, false).await;
+                            wait_for_tcp(in_addr).await;
+                            topology
+                        });
+                        (rt, topology)
+                    },
+                    |(rt, topology)| {
+                        rt.block_on(async move {
+                            let lines = random_lines(line_size).take(num_lines);
+                            send_lines(in_addr, lines).await.unwrap();
+                            topology.stop().await;
+                        })
+                    },
+                    BatchSize::PerIteration,
+                )
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn serve(addr: SocketAddr) -> Runtime {
+    let rt = runtime();
+    rt.spawn(async move {
+        let make_service = make_service_fn(|_| async {
+            Ok::<_, Error>(service_fn(|_req| async {
+                let mut response = Response::new(Body::empty());
+                *response.status_mut() = StatusCode::ACCEPTED;
+                Ok::<_, Error>(response)
+            }))
+        });
+
+        Server::bind(&addr)
+            .serve(make_service)
+            .map_err(|e| panic!("{}", e))
+            .await
+    });
+    rt
+}
+
+criterion_group!(
+    name = benches;
+    config = Criterion::default().noise_threshold(0.05);
+    targets = benchmark_encode_concurrency, benchmark_request_rate
+);
+criterion_main!(benches);
@@ -23,6 +23,13 @@ pub struct EventMetadata {
     #[getset(get = "pub", set = "pub")]
     #[serde(default, skip)]
     splunk_hec_token: Option<Arc<str>>,
+    /// Used to store the correlation ID of the event, as set by tracing, from sources to sinks
+    #[getset(get = "pub", set = "pub")]
+    #[serde(default, skip)]
+    correlation_id: Option<Arc<str>>,
+    /// The number of events read from the source this event stands for, if other than one
+    #[serde(default, skip)]
+    source_events: Option<usize>,
     #[serde(default, skip)]
     finalizers: EventFinalizers,
 }
@@ -59,6 +66,7 @@ impl EventMetadata {
     /// Merge the other `EventMetadata` into this.
     /// If a Datadog API key is not set in `self`, the one from `other` will be used.
     /// If a Splunk HEC token is not set in `self`, the one from `other` will be used.
+    /// If a correlation ID is not set in `self`, the one from `other` will be used.
     pub fn merge(&mut self, other: Self) {
         self.finalizers.merge(other.finalizers);
         if self.datadog_api_key.is_none() {
@@ -67,6 +75,20 @@ impl EventMetadata {
         if self.splunk_hec_token.is_none() {
             self.splunk_hec_token = other.splunk_hec_token;
         }
+        if self.correlation_id.is_none() {
+            self.correlation_id = other.correlation_id;
+        }
+    }
+
+    /// Get the number of events read from the source this event stands for, such as the lines
+    /// coalesced into it. This is one unless set otherwise.
+    pub fn source_events(&self) -> usize {
+        self.source_events.unwrap_or(1)
+    }
+
+    /// Set the number of events read from the source this event stands for.
+    pub fn set_source_events(&mut self, source_events: usize) {
+        self.source_events = Some(source_events);
     }
 
     /// Update the finalizer(s) status.
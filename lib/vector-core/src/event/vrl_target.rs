@@ -266,6 +266,10 @@ impl vrl_core::Target for VrlTarget {
                 .splunk_hec_token()
                 .as_ref()
                 .map(|token| vrl_core::Value::from(token.to_string()))),
+            "correlation_id" => Ok(metadata
+                .correlation_id()
+                .as_ref()
+                .map(|id| vrl_core::Value::from(id.to_string()))),
             _ => Err(format!("key {} not available", key)),
         }
     }
@@ -285,6 +289,10 @@ impl vrl_core::Target for VrlTarget {
                 metadata.set_splunk_hec_token(Some(Arc::from(value.as_str())));
                 Ok(())
             }
+            "correlation_id" => {
+                metadata.set_correlation_id(Some(Arc::from(value.as_str())));
+                Ok(())
+            }
             _ => Err(format!("key {} not available", key)),
         }
     }
@@ -304,6 +312,10 @@ impl vrl_core::Target for VrlTarget {
                 metadata.set_splunk_hec_token(None);
                 Ok(())
             }
+            "correlation_id" => {
+                metadata.set_correlation_id(None);
+                Ok(())
+            }
             _ => Err(format!("key {} not available", key)),
         }
     }
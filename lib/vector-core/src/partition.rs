@@ -14,4 +14,18 @@ pub trait Partitioner {
     /// in such a way that if two distinct `Item` instances partition to the
     /// same key they are mergeable if put into the same collection by this key.
     fn partition(&self, item: &Self::Item) -> Self::Key;
+
+    /// Whether an item partitioned to `item_key` ends the partition `key`, such
+    /// that no further item will be partitioned to `key`
+    ///
+    /// The batch of an ended partition is flushed as soon as it ends, rather
+    /// than once it fills up or expires. No partition ends by default.
+    fn ends(&self, _item_key: &Self::Key, _key: &Self::Key) -> bool {
+        false
+    }
+
+    /// Notifies the partitioner that the batch of `key` was flushed without a
+    /// further batch of it being opened, so that any state it keeps for the
+    /// partition can be dropped
+    fn flushed(&self, _key: &Self::Key) {}
 }
@@ -89,6 +89,12 @@ where
         }
     }
 
+    fn remove(&mut self, item_key: &K) {
+        if let Some(expiration_key) = self.expiration_map.remove(item_key) {
+            self.expirations.remove(&expiration_key);
+        }
+    }
+
     fn poll_expired(&mut self, cx: &mut Context) -> Poll<Option<K>> {
         match ready!(self.expirations.poll_expired(cx)) {
             // No expirations yet.
@@ -394,21 +400,25 @@ where
             if !this.closed_batches.is_empty() {
                 return Poll::Ready(this.closed_batches.pop());
             }
+            // Expired batches are checked for before every item, rather than
+            // only once the underlying stream is pending, as otherwise a busy
+            // partition that keeps the stream ready would hold back the
+            // timeouts of every other partition.
+            //
+            // Unlike normal streams, `DelayQueue` can return `None` here but
+            // still be usable later if more entries are added.
+            if let Poll::Ready(Some(item_key)) = this.timer.poll_expired(cx) {
+                let batch = this
+                    .batches
+                    .remove(&item_key)
+                    .expect("batch should exist if it is set to expire");
+                this.partitioner.flushed(&item_key);
+                this.closed_batches.push((item_key, batch.into_inner()));
+
+                continue;
+            }
             match this.stream.as_mut().poll_next(cx) {
-                Poll::Pending => match this.timer.poll_expired(cx) {
-                    // Unlike normal streams, `DelayQueue` can return `None`
-                    // here but still be usable later if more entries are added.
-                    Poll::Pending | Poll::Ready(None) => return Poll::Pending,
-                    Poll::Ready(Some(item_key)) => {
-                        let batch = this
-                            .batches
-                            .remove(&item_key)
-                            .expect("batch should exist if it is set to expire");
-                        this.closed_batches.push((item_key, batch.into_inner()));
-
-                        continue;
-                    }
-                },
+                Poll::Pending => return Poll::Pending,
                 Poll::Ready(None) => {
                     // Now that the underlying stream is closed, we need to
                     // clear out our batches, including all expiration
@@ -448,6 +458,24 @@ where
                             this.closed_batches.push((item_key, batch.into_inner()));
                         }
                     } else {
+                        // A new partition may end others, which won't receive
+                        // any further items, so their batches are flushed now
+                        // rather than once they expire.
+                        let partitioner = &*this.partitioner;
+                        let ended: Vec<_> = this
+                            .batches
+                            .keys()
+                            .filter(|key| partitioner.ends(&item_key, key))
+                            .cloned()
+                            .collect();
+                        for key in ended {
+                            let batch =
+                                this.batches.remove(&key).expect("ended batch should exist");
+                            this.timer.remove(&key);
+                            this.partitioner.flushed(&key);
+                            this.closed_batches.push((key, batch.into_inner()));
+                        }
+
                         // We have no batch yet for this partition key, so
                         // create one and create the expiration entries as well.
                         // This allows the batch to expire before filling up,
@@ -472,7 +500,7 @@ mod test {
         time::Duration,
     };
 
-    use futures::{stream, Stream};
+    use futures::{stream, Stream, StreamExt};
     use pin_project::pin_project;
     use proptest::prelude::*;
     use tokio::{pin, time::advance};
@@ -513,12 +541,18 @@ mod test {
             self.valid_keys.insert(item_key);
         }
 
+        fn remove(&mut self, item_key: &u8) {
+            self.valid_keys.remove(item_key);
+        }
+
         fn poll_expired(&mut self, _cx: &mut Context) -> Poll<Option<u8>> {
             match self.responses.pop() {
                 Some(Poll::Pending) => unreachable!(),
                 None | Some(Poll::Ready(None)) => Poll::Ready(None),
                 Some(Poll::Ready(Some(k))) => {
-                    if self.valid_keys.contains(&k) {
+                    // Like `ExpirationQueue`, a key only expires once per
+                    // insertion.
+                    if self.valid_keys.remove(&k) {
                         Poll::Ready(Some(k))
                     } else {
                         Poll::Ready(None)
@@ -750,6 +784,76 @@ mod test {
         }
     }
 
+    #[test]
+    fn batch_expires_while_stream_ready() {
+        // Asserts that a batch is flushed once it expires even though the
+        // underlying stream never returns pending, so that a busy partition
+        // doesn't hold back the timeouts of the others.
+        let noop_waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&noop_waker);
+
+        let mut stream = stream::iter(vec![0, 1, 1, 1]);
+        let partitioner = TestPartitioner {
+            key_space: NonZeroU8::new(2).unwrap(),
+        };
+        // The batch of key 0 expires as soon as it exists.
+        let timer = TestTimer::new(vec![Poll::Ready(Some(0)); 8]);
+        let item_limit = NonZeroUsize::new(16).unwrap();
+        let mut batcher =
+            PartitionedBatcher::with_timer(&mut stream, partitioner, timer, item_limit, None);
+        let mut batcher = Pin::new(&mut batcher);
+
+        assert_eq!(
+            batcher.as_mut().poll_next(&mut cx),
+            Poll::Ready(Some((0, vec![0])))
+        );
+        assert_eq!(
+            batcher.as_mut().poll_next(&mut cx),
+            Poll::Ready(Some((1, vec![1, 1, 1])))
+        );
+        assert_eq!(batcher.as_mut().poll_next(&mut cx), Poll::Ready(None));
+    }
+
+    /// A partitioner of runs of ten items, where each run ends the previous
+    /// ones
+    struct RunPartitioner;
+
+    impl Partitioner for RunPartitioner {
+        type Item = u64;
+        type Key = u64;
+
+        fn partition(&self, item: &Self::Item) -> Self::Key {
+            *item / 10
+        }
+
+        fn ends(&self, item_key: &Self::Key, key: &Self::Key) -> bool {
+            key < item_key
+        }
+    }
+
+    #[test]
+    fn ended_partition_flushes_immediately() {
+        // Asserts that the batch of an ended partition is flushed as soon as
+        // the partition ending it starts, although it has neither filled up
+        // nor expired, while the batch of the new partition stays open.
+        let noop_waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&noop_waker);
+
+        // The stream stays open, so nothing is flushed for it ending.
+        let mut stream = stream::iter(vec![1, 2, 11, 12]).chain(stream::pending());
+        let timer = TestTimer::new(Vec::new());
+        let item_limit = NonZeroUsize::new(16).unwrap();
+        let mut batcher =
+            PartitionedBatcher::with_timer(&mut stream, RunPartitioner, timer, item_limit, None);
+        let mut batcher = Pin::new(&mut batcher);
+
+        assert_eq!(
+            batcher.as_mut().poll_next(&mut cx),
+            Poll::Ready(Some((0, vec![1, 2])))
+        );
+        assert_eq!(batcher.as_mut().poll_next(&mut cx), Poll::Pending);
+    }
+
     #[tokio::test(start_paused = true)]
     #[allow(clippy::semicolon_if_nothing_returned)] // https://github.com/rust-lang/rust-clippy/issues/7438
     async fn expiration_queue_impl_keyed_timer() {
@@ -30,7 +30,11 @@ impl Function for RemoveMetadataField {
         _ctx: &FunctionCompileContext,
         mut arguments: ArgumentList,
     ) -> Compiled {
-        let keys = vec![value!("datadog_api_key"), value!("splunk_hec_token")];
+        let keys = vec![
+            value!("datadog_api_key"),
+            value!("splunk_hec_token"),
+            value!("correlation_id"),
+        ];
         let key = arguments
             .required_enum("key", &keys)?
             .try_bytes_utf8_lossy()
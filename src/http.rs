@@ -1,5 +1,6 @@
 use std::{
     fmt,
+    sync::Arc,
     task::{Context, Poll},
 };
 
@@ -15,6 +16,7 @@ use hyper_openssl::HttpsConnector;
 use hyper_proxy::ProxyConnector;
 use serde::{Deserialize, Serialize};
 use snafu::{ResultExt, Snafu};
+use tokio::sync::Semaphore;
 use tower::Service;
 use tracing_futures::Instrument;
 
@@ -42,7 +44,7 @@ pub enum HttpError {
 pub type HttpClientFuture = <HttpClient as Service<http::Request<Body>>>::Future;
 
 pub struct HttpClient<B = Body> {
-    client: Client<ProxyConnector<HttpsConnector<HttpConnector>>, B>,
+    client: Client<HandshakeLimiter<ProxyConnector<HttpsConnector<HttpConnector>>>, B>,
     user_agent: HeaderValue,
 }
 
@@ -59,10 +61,34 @@ where
         HttpClient::new_with_custom_client(tls_settings, proxy_config, &mut Client::builder())
     }
 
+    /// Builds a client which establishes at most `max_concurrent_handshakes` connections at the
+    /// same time, to spread out the cost of TLS handshakes when many requests start at once.
+    pub fn new_with_handshake_limit(
+        tls_settings: impl Into<MaybeTlsSettings>,
+        proxy_config: &ProxyConfig,
+        max_concurrent_handshakes: Option<usize>,
+    ) -> Result<HttpClient<B>, HttpError> {
+        HttpClient::build(
+            tls_settings,
+            proxy_config,
+            &mut Client::builder(),
+            max_concurrent_handshakes,
+        )
+    }
+
     pub fn new_with_custom_client(
         tls_settings: impl Into<MaybeTlsSettings>,
         proxy_config: &ProxyConfig,
         client_builder: &mut client::Builder,
+    ) -> Result<HttpClient<B>, HttpError> {
+        HttpClient::build(tls_settings, proxy_config, client_builder, None)
+    }
+
+    fn build(
+        tls_settings: impl Into<MaybeTlsSettings>,
+        proxy_config: &ProxyConfig,
+        client_builder: &mut client::Builder,
+        max_concurrent_handshakes: Option<usize>,
     ) -> Result<HttpClient<B>, HttpError> {
         let mut http = HttpConnector::new();
         http.enforce_http(false);
@@ -84,7 +110,7 @@ where
         proxy_config
             .configure(&mut proxy)
             .context(MakeProxyConnector)?;
-        let client = client_builder.build(proxy);
+        let client = client_builder.build(HandshakeLimiter::new(proxy, max_concurrent_handshakes));
 
         let version = crate::get_version();
         let user_agent = HeaderValue::from_str(&format!("Vector/{}", version))
@@ -143,6 +169,57 @@ where
     }
 }
 
+/// Limits the number of connections a connector establishes at the same time.
+///
+/// Establishing a connection includes the TLS handshake, which is CPU intensive on both ends, so
+/// starting many connections at once causes CPU spikes and handshake timeouts. The limit is shared
+/// by all clones of the connector.
+#[derive(Clone, Debug)]
+pub struct HandshakeLimiter<C> {
+    inner: C,
+    semaphore: Option<Arc<Semaphore>>,
+}
+
+impl<C> HandshakeLimiter<C> {
+    pub fn new(inner: C, max_concurrent_handshakes: Option<usize>) -> Self {
+        Self {
+            inner,
+            semaphore: max_concurrent_handshakes.map(|limit| Arc::new(Semaphore::new(limit))),
+        }
+    }
+}
+
+impl<C, R> Service<R> for HandshakeLimiter<C>
+where
+    C: Service<R>,
+    C::Future: Send + 'static,
+{
+    type Response = C::Response;
+    type Error = C::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        let semaphore = self.semaphore.clone();
+        let connecting = self.inner.call(request);
+        Box::pin(async move {
+            let _permit = match semaphore {
+                Some(semaphore) => Some(
+                    semaphore
+                        .acquire_owned()
+                        .await
+                        .expect("handshake semaphore is never closed"),
+                ),
+                None => None,
+            };
+            connecting.await
+        })
+    }
+}
+
 fn default_request_headers<B>(request: &mut Request<B>, user_agent: &HeaderValue) {
     if !request.headers().contains_key("User-Agent") {
         request
@@ -245,8 +322,44 @@ impl Auth {
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use futures::future::join_all;
+    use tower::ServiceExt;
+
     use super::*;
 
+    #[tokio::test]
+    async fn handshake_limiter_bounds_concurrent_connections() {
+        let connecting = Arc::new(AtomicUsize::new(0));
+        let max_connecting = Arc::new(AtomicUsize::new(0));
+        let connector = {
+            let connecting = Arc::clone(&connecting);
+            let max_connecting = Arc::clone(&max_connecting);
+            tower::service_fn(move |_: ()| {
+                let connecting = Arc::clone(&connecting);
+                let max_connecting = Arc::clone(&max_connecting);
+                async move {
+                    let current = connecting.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_connecting.fetch_max(current, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    connecting.fetch_sub(1, Ordering::SeqCst);
+                    Ok::<_, ()>(())
+                }
+            })
+        };
+
+        let limiter = HandshakeLimiter::new(connector, Some(2));
+        let connections = (0..10).map(|_| limiter.clone().oneshot(()));
+        let results = join_all(connections).await;
+
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(max_connecting.load(Ordering::SeqCst), 2);
+    }
+
     #[test]
     fn test_default_request_headers_defaults() {
         let user_agent = HeaderValue::from_static("vector");
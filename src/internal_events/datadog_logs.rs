@@ -1,5 +1,7 @@
-use metrics::counter;
-use vector_core::internal_event::InternalEvent;
+use std::{collections::BTreeMap, path::Path};
+
+use metrics::{counter, gauge, histogram};
+use vector_core::{event::BatchStatus, internal_event::InternalEvent};
 
 #[derive(Debug)]
 pub struct DatadogLogEventProcessed {
@@ -12,3 +14,479 @@ impl InternalEvent for DatadogLogEventProcessed {
         counter!("processed_bytes_total", self.byte_size as u64);
     }
 }
+
+#[derive(Debug)]
+pub struct DatadogLogsImplausibleTimestamp {
+    pub timestamp_millis: i64,
+    pub now_millis: i64,
+    pub suspected_precision: &'static str,
+}
+
+impl InternalEvent for DatadogLogsImplausibleTimestamp {
+    fn emit_logs(&self) {
+        warn!(
+            message = "Timestamp is implausibly far from the current time; it may have the wrong precision.",
+            timestamp = %self.timestamp_millis,
+            now = %self.now_millis,
+            suspected_precision = %self.suspected_precision,
+            internal_log_rate_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("implausible_timestamps_total", 1);
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogsInvalidTimestamp<'a> {
+    pub kind: &'a str,
+    pub policy: &'static str,
+    pub dropped: bool,
+}
+
+impl<'a> InternalEvent for DatadogLogsInvalidTimestamp<'a> {
+    fn emit_logs(&self) {
+        warn!(
+            message = "Timestamp is not a time value; applying the `invalid_timestamp` policy.",
+            kind = %self.kind,
+            policy = %self.policy,
+            internal_log_rate_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("invalid_timestamps_total", 1);
+        if self.dropped {
+            counter!("events_discarded_total", 1);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogAttributesTruncated {
+    pub max_attributes: usize,
+    pub dropped_attributes: usize,
+}
+
+impl InternalEvent for DatadogLogAttributesTruncated {
+    fn emit_logs(&self) {
+        warn!(
+            message = "Event has too many attributes; dropping the excess.",
+            max_attributes = %self.max_attributes,
+            dropped_attributes = %self.dropped_attributes,
+            internal_log_rate_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("truncated_attributes_total", self.dropped_attributes as u64);
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogsUnknownSite<'a> {
+    pub site: &'a str,
+}
+
+impl<'a> InternalEvent for DatadogLogsUnknownSite<'a> {
+    fn emit_logs(&self) {
+        warn!(
+            message = "Configured site is not a known Datadog site; check it for typos unless it is a custom relay.",
+            site = %self.site,
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogsEndpointNotOnSite<'a> {
+    pub endpoint: &'a str,
+    pub option: &'static str,
+    pub site: &'a str,
+}
+
+impl<'a> InternalEvent for DatadogLogsEndpointNotOnSite<'a> {
+    fn emit_logs(&self) {
+        warn!(
+            message = "Configured endpoint is not on the configured site, which it overrides; check it unless it is a relay or proxy.",
+            endpoint = %self.endpoint,
+            option = %self.option,
+            site = %self.site,
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogsDeprecatedRegion;
+
+impl InternalEvent for DatadogLogsDeprecatedRegion {
+    fn emit_logs(&self) {
+        warn!(message = "The `region` option is deprecated; use `site` instead.");
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogsApiKeyLooksLikeAppKey<'a> {
+    pub option: &'a str,
+}
+
+impl<'a> InternalEvent for DatadogLogsApiKeyLooksLikeAppKey<'a> {
+    fn emit_logs(&self) {
+        warn!(
+            message = "Configured API key looks like a Datadog application key, which can't be used to send logs; use an API key instead.",
+            option = %self.option,
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogPartitionStalled<'a> {
+    pub partition: &'a str,
+    pub stalled_secs: u64,
+}
+
+impl<'a> InternalEvent for DatadogLogPartitionStalled<'a> {
+    fn emit_logs(&self) {
+        warn!(
+            message = "Partition has not been delivered successfully for a while, while others have.",
+            partition = %self.partition,
+            stalled_secs = %self.stalled_secs,
+            internal_log_rate_secs = 30
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("partition_stalls_total", 1);
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogDottedKeyRejected<'a> {
+    pub key: &'a str,
+}
+
+impl<'a> InternalEvent for DatadogLogDottedKeyRejected<'a> {
+    fn emit_logs(&self) {
+        warn!(
+            message = "Attribute key contains dots; dropping the attribute.",
+            key = %self.key,
+            internal_log_rate_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("rejected_dotted_keys_total", 1);
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogsBufferDepth {
+    pub events: usize,
+    pub byte_size: usize,
+}
+
+impl InternalEvent for DatadogLogsBufferDepth {
+    fn emit_metrics(&self) {
+        gauge!("sink_buffered_events", self.events as f64);
+        gauge!("sink_buffered_bytes", self.byte_size as f64);
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogsBatchFlushed {
+    /// The number of events relative to `batch.max_events`.
+    pub events_ratio: f64,
+    /// The byte size of events relative to `batch.max_bytes`.
+    pub bytes_ratio: f64,
+    /// Whether the batch was flushed by its `size` or its `timeout`.
+    pub trigger: &'static str,
+}
+
+impl InternalEvent for DatadogLogsBatchFlushed {
+    fn emit_metrics(&self) {
+        histogram!(
+            "batch_fill_ratio", self.events_ratio,
+            "limit" => "events",
+            "trigger" => self.trigger,
+        );
+        histogram!(
+            "batch_fill_ratio", self.bytes_ratio,
+            "limit" => "bytes",
+            "trigger" => self.trigger,
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogsRequestCompressed {
+    /// The name of the compression algorithm of the request.
+    pub compression: &'static str,
+    pub uncompressed_bytes: usize,
+    pub compressed_bytes: usize,
+}
+
+impl InternalEvent for DatadogLogsRequestCompressed {
+    fn emit_metrics(&self) {
+        // Uncompressed requests, and empty ones, are reported as not shrinking at all.
+        let ratio = if self.compression == "none" || self.compressed_bytes == 0 {
+            1.0
+        } else {
+            self.uncompressed_bytes as f64 / self.compressed_bytes as f64
+        };
+        histogram!(
+            "request_uncompressed_bytes", self.uncompressed_bytes as f64,
+            "compression" => self.compression,
+        );
+        histogram!(
+            "request_compressed_bytes", self.compressed_bytes as f64,
+            "compression" => self.compression,
+        );
+        histogram!(
+            "request_compression_ratio", ratio,
+            "compression" => self.compression,
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogEventTooLarge {
+    pub byte_size: usize,
+    pub max_byte_size: usize,
+    pub dropped: bool,
+}
+
+impl InternalEvent for DatadogLogEventTooLarge {
+    fn emit_logs(&self) {
+        let message = if self.dropped {
+            "Log is above the size limit of the Datadog Logs API; dropping the log."
+        } else {
+            "Log is above the size limit of the Datadog Logs API; truncating its message."
+        };
+        warn!(
+            message,
+            byte_size = %self.byte_size,
+            max_byte_size = %self.max_byte_size,
+            internal_log_rate_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("oversized_events_total", 1);
+        if self.dropped {
+            counter!("events_discarded_total", 1);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogAttributeValueTruncated<'a> {
+    pub key: &'a str,
+    pub byte_size: usize,
+    pub max_byte_size: usize,
+}
+
+impl<'a> InternalEvent for DatadogLogAttributeValueTruncated<'a> {
+    fn emit_logs(&self) {
+        warn!(
+            message = "Attribute value is too large; truncating it.",
+            key = %self.key,
+            byte_size = %self.byte_size,
+            max_byte_size = %self.max_byte_size,
+            internal_log_rate_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("truncated_attribute_values_total", 1);
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogsMirrorWriteFailed<'a> {
+    pub error: std::io::Error,
+    pub path: &'a Path,
+}
+
+impl<'a> InternalEvent for DatadogLogsMirrorWriteFailed<'a> {
+    fn emit_logs(&self) {
+        error!(
+            message = "Failed to write request body to mirror file.",
+            path = ?self.path,
+            error = %self.error,
+            internal_log_rate_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("mirror_write_errors_total", 1);
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogEventDropped {
+    pub reason: &'static str,
+}
+
+impl InternalEvent for DatadogLogEventDropped {
+    fn emit_logs(&self) {
+        warn!(
+            message = "Event dropped.",
+            reason = %self.reason,
+            internal_log_rate_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("events_discarded_total", 1, "reason" => self.reason);
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogDropSummary {
+    pub counts: BTreeMap<&'static str, usize>,
+}
+
+impl InternalEvent for DatadogLogDropSummary {
+    fn emit_logs(&self) {
+        let reasons = self
+            .counts
+            .iter()
+            .map(|(reason, count)| format!("{}={}", reason, count))
+            .collect::<Vec<_>>()
+            .join(", ");
+        warn!(
+            message = "Events were dropped.",
+            %reasons,
+            count = %self.counts.values().sum::<usize>(),
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("drop_summaries_total", 1);
+        for (reason, count) in &self.counts {
+            counter!("summarized_dropped_events_total", *count as u64, "reason" => *reason);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogsCanaryCompleted {
+    pub status: BatchStatus,
+}
+
+impl InternalEvent for DatadogLogsCanaryCompleted {
+    fn emit_logs(&self) {
+        match self.status {
+            BatchStatus::Delivered => info!(message = "Canary was delivered."),
+            status => error!(message = "Canary was not delivered.", ?status),
+        }
+    }
+
+    fn emit_metrics(&self) {
+        let status = match self.status {
+            BatchStatus::Delivered => "delivered",
+            BatchStatus::Errored => "errored",
+            BatchStatus::Rejected => "rejected",
+        };
+        counter!("canaries_total", 1, "status" => status);
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogEncodingError<'a> {
+    pub error: &'a dyn std::error::Error,
+    pub error_type: &'static str,
+    pub dropped_events: usize,
+}
+
+impl<'a> InternalEvent for DatadogLogEncodingError<'a> {
+    fn emit_logs(&self) {
+        error!(
+            message = "Failed to build Datadog Logs request.",
+            error = %self.error,
+            error_type = self.error_type,
+            dropped_events = self.dropped_events,
+            internal_log_rate_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("encoding_errors_total", 1, "error_type" => self.error_type);
+        if self.dropped_events > 0 {
+            counter!(
+                "events_discarded_total", self.dropped_events as u64,
+                "reason" => self.error_type,
+            );
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogsRequestDeadlineExceeded {
+    pub deadline: std::time::Duration,
+    pub dropped_events: usize,
+}
+
+impl InternalEvent for DatadogLogsRequestDeadlineExceeded {
+    fn emit_logs(&self) {
+        error!(
+            message = "Request exceeded its deadline; giving up on its events.",
+            deadline_secs = self.deadline.as_secs_f64(),
+            dropped_events = self.dropped_events,
+            internal_log_rate_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("request_deadlines_exceeded_total", 1);
+        counter!(
+            "events_discarded_total", self.dropped_events as u64,
+            "reason" => "deadline_exceeded",
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogsEndpointFailover<'a> {
+    pub from: &'a http::Uri,
+    pub to: &'a http::Uri,
+    pub error: &'a crate::http::HttpError,
+}
+
+impl<'a> InternalEvent for DatadogLogsEndpointFailover<'a> {
+    fn emit_logs(&self) {
+        warn!(
+            message = "Endpoint can't be reached, failing over to the next one.",
+            from = %self.from,
+            to = %self.to,
+            error = %self.error,
+            internal_log_rate_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("endpoint_failovers_total", 1);
+    }
+}
+
+#[derive(Debug)]
+pub struct DatadogLogsDryRunDiscarded {
+    pub count: usize,
+}
+
+impl InternalEvent for DatadogLogsDryRunDiscarded {
+    fn emit_logs(&self) {
+        debug!(
+            message = "Discarded events in dry run mode.",
+            count = self.count,
+            internal_log_rate_secs = 10
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!(
+            "events_discarded_total", self.count as u64,
+            "reason" => "dry_run",
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+};
+
+use futures::{future::BoxFuture, FutureExt};
+use tower::Service;
+use vector_core::{event::EventStatus, stream::DriverResponse};
+
+use super::service::LogApiRequest;
+
+/// Checks that events are only acknowledged as delivered once a `2xx` response confirmed the
+/// delivery of their request, for durability tests.
+///
+/// Events are tracked through their request, as all the events of a request are acknowledged
+/// together. This is only available in debug builds, where any mismatch panics.
+#[derive(Debug, Default)]
+pub struct AckVerifier {
+    next_id: AtomicU64,
+    /// The requests which haven't been acknowledged yet, with whether their delivery was confirmed.
+    pending: Mutex<HashMap<u64, bool>>,
+}
+
+impl AckVerifier {
+    /// Starts tracking a request, returning its ID.
+    pub fn enqueue(&self) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.lock().insert(id, false);
+        id
+    }
+
+    /// Records that the request was answered with a `2xx` response.
+    pub fn confirm(&self, id: u64) {
+        if let Some(confirmed) = self.lock().get_mut(&id) {
+            *confirmed = true;
+        }
+    }
+
+    /// Stops tracking the request, panicking if its events are acknowledged as delivered without
+    /// a confirmed delivery.
+    pub fn complete(&self, id: u64, status: EventStatus) {
+        let confirmed = self.lock().remove(&id);
+        if status == EventStatus::Delivered && confirmed != Some(true) {
+            panic!(
+                "Request {} was acknowledged as delivered without a confirmed delivery.",
+                id
+            );
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, bool>> {
+        self.pending.lock().expect("ack verifier mutex poisoned")
+    }
+}
+
+/// Reports the status each request is acknowledged with to the verifier, once the request has
+/// completed.
+#[derive(Clone)]
+pub struct AckVerificationService<S> {
+    inner: S,
+    verifier: Option<Arc<AckVerifier>>,
+}
+
+impl<S> AckVerificationService<S> {
+    pub const fn new(inner: S, verifier: Option<Arc<AckVerifier>>) -> Self {
+        Self { inner, verifier }
+    }
+}
+
+impl<S> Service<LogApiRequest> for AckVerificationService<S>
+where
+    S: Service<LogApiRequest>,
+    S::Future: Send + 'static,
+    S::Response: DriverResponse,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: LogApiRequest) -> Self::Future {
+        let tracked = self.verifier.clone().zip(request.ack_id);
+        let response = self.inner.call(request);
+        async move {
+            let result = response.await;
+            if let Some((verifier, id)) = tracked {
+                let status = result
+                    .as_ref()
+                    .map_or(EventStatus::Errored, DriverResponse::event_status);
+                verifier.complete(id, status);
+            }
+            result
+        }
+        .boxed()
+    }
+}
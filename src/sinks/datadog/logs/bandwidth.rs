@@ -0,0 +1,98 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::time::{sleep, Instant};
+
+use super::config::ApiKey;
+
+/// Limits the rate at which request bodies leave the sink.
+///
+/// This is a token bucket holding up to one second's worth of bytes. Sending a body larger than
+/// the available budget is allowed, but puts the bucket into debt, and the caller is delayed until
+/// that debt has been paid back. As requests are acquired sequentially, this applies backpressure
+/// to the rest of the sink whenever the configured bandwidth is exceeded.
+#[derive(Clone, Debug)]
+pub struct BandwidthLimiter {
+    bytes_per_sec: f64,
+    state: Arc<Mutex<BucketState>>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    available: f64,
+    last_refill: Instant,
+}
+
+impl BandwidthLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        let bytes_per_sec = bytes_per_sec as f64;
+        Self {
+            bytes_per_sec,
+            state: Arc::new(Mutex::new(BucketState {
+                available: bytes_per_sec,
+                last_refill: Instant::now(),
+            })),
+        }
+    }
+
+    /// Waits until `bytes` may be sent without exceeding the configured bandwidth.
+    pub async fn acquire(&self, bytes: usize) {
+        let delay = {
+            let mut state = self.state.lock().expect("bandwidth limiter mutex poisoned");
+            let now = Instant::now();
+            let refill = now.duration_since(state.last_refill).as_secs_f64() * self.bytes_per_sec;
+            state.available = (state.available + refill).min(self.bytes_per_sec);
+            state.last_refill = now;
+            state.available -= bytes as f64;
+
+            (state.available < 0.0)
+                .then(|| Duration::from_secs_f64(-state.available / self.bytes_per_sec))
+        };
+
+        if let Some(delay) = delay {
+            sleep(delay).await;
+        }
+    }
+}
+
+/// Limits the request rate of each partition that has a configured limit, independently of the
+/// other partitions and of the sink's overall rate limit.
+///
+/// Each partition gets its own token bucket, which holds requests rather than bytes.
+#[derive(Clone)]
+pub struct PartitionRateLimits {
+    limiters: Arc<HashMap<String, BandwidthLimiter>>,
+}
+
+impl fmt::Debug for PartitionRateLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The partitions are API keys, so only their number is shown.
+        f.debug_struct("PartitionRateLimits")
+            .field("partitions", &self.limiters.len())
+            .finish()
+    }
+}
+
+impl PartitionRateLimits {
+    pub fn new(requests_per_sec: &HashMap<ApiKey, u64>) -> Self {
+        let limiters = requests_per_sec
+            .iter()
+            .map(|(api_key, limit)| (api_key.expose().to_owned(), BandwidthLimiter::new(*limit)))
+            .collect();
+        Self {
+            limiters: Arc::new(limiters),
+        }
+    }
+
+    /// Waits until a request of the partition of `api_key` may be sent without exceeding its
+    /// limit, if it has one.
+    pub async fn acquire(&self, api_key: &str) {
+        if let Some(limiter) = self.limiters.get(api_key) {
+            limiter.acquire(1).await;
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use std::sync::Mutex;
+
+use bytes::{Bytes, BytesMut};
+
+use super::config::MAX_PAYLOAD_BYTES;
+
+/// The capacity reserved for each body, which is enough for a small compressed payload.
+const INITIAL_CAPACITY: usize = 64 * 1024;
+
+/// Holds the allocation request bodies are compressed into, so that it is reused across requests
+/// rather than allocated and grown from scratch for each of them.
+///
+/// Each body is split off the buffer and frozen into its request without being copied, so the
+/// bodies share the allocation of the buffer. Once all requests sharing it are done with their
+/// bodies, the allocation is reclaimed for the next body. While any are still in flight, the next
+/// body gets a new allocation of the same size instead.
+#[derive(Debug, Default)]
+pub struct BodyBuffer {
+    buffer: Mutex<BytesMut>,
+}
+
+impl BodyBuffer {
+    /// Takes the buffer to compress a body into, with room for at least a small payload.
+    pub fn take(&self) -> BytesMut {
+        let mut buffer = std::mem::take(&mut *self.buffer.lock().expect("body buffer poisoned"));
+        buffer.reserve(INITIAL_CAPACITY);
+        buffer
+    }
+
+    /// Splits the body written to `buffer` off it, and keeps the rest of `buffer` for the next body
+    /// unless it grew past the largest payload.
+    pub fn freeze(&self, mut buffer: BytesMut) -> Bytes {
+        let body = buffer.split().freeze();
+        if body.len() + buffer.capacity() <= MAX_PAYLOAD_BYTES {
+            *self.buffer.lock().expect("body buffer poisoned") = buffer;
+        }
+        body
+    }
+}
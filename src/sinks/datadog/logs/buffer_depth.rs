@@ -0,0 +1,99 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{future::BoxFuture, FutureExt};
+use tower::Service;
+
+use super::service::LogApiRequest;
+use crate::internal_events::DatadogLogsBufferDepth;
+
+/// Counts the events held by the sink, from when they are received until their request completes,
+/// across all partitions.
+#[derive(Debug, Default)]
+pub struct BufferDepth {
+    events: AtomicUsize,
+    byte_size: AtomicUsize,
+}
+
+impl BufferDepth {
+    /// Creates a counter whose totals are emitted every `interval`, for as long as it is in use.
+    pub fn spawn(interval: Duration) -> Arc<Self> {
+        let depth = Arc::new(Self::default());
+        tokio::spawn(report(Arc::downgrade(&depth), interval));
+        depth
+    }
+
+    pub fn add(&self, events: usize, byte_size: usize) {
+        self.events.fetch_add(events, Ordering::Relaxed);
+        self.byte_size.fetch_add(byte_size, Ordering::Relaxed);
+    }
+
+    pub fn remove(&self, events: usize, byte_size: usize) {
+        self.events.fetch_sub(events, Ordering::Relaxed);
+        self.byte_size.fetch_sub(byte_size, Ordering::Relaxed);
+    }
+}
+
+async fn report(depth: Weak<BufferDepth>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        let depth = match depth.upgrade() {
+            Some(depth) => depth,
+            None => break,
+        };
+        emit!(&DatadogLogsBufferDepth {
+            events: depth.events.load(Ordering::Relaxed),
+            byte_size: depth.byte_size.load(Ordering::Relaxed),
+        });
+    }
+}
+
+/// Removes the events of each request from the buffer depth once the request has completed,
+/// whatever its outcome.
+#[derive(Clone)]
+pub struct BufferDepthService<S> {
+    inner: S,
+    depth: Option<Arc<BufferDepth>>,
+}
+
+impl<S> BufferDepthService<S> {
+    pub const fn new(inner: S, depth: Option<Arc<BufferDepth>>) -> Self {
+        Self { inner, depth }
+    }
+}
+
+impl<S> Service<LogApiRequest> for BufferDepthService<S>
+where
+    S: Service<LogApiRequest>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: LogApiRequest) -> Self::Future {
+        let events = request.batch_size;
+        let byte_size = request.events_byte_size;
+        let depth = self.depth.clone();
+        let response = self.inner.call(request);
+        async move {
+            let result = response.await;
+            if let Some(depth) = depth {
+                depth.remove(events, byte_size);
+            }
+            result
+        }
+        .boxed()
+    }
+}
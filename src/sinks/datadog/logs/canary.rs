@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use async_stream::stream;
+use futures::{Stream, StreamExt};
+use tokio::sync::Notify;
+use vector_core::event::{BatchNotifier, Event};
+
+use crate::internal_events::DatadogLogsCanaryCompleted;
+
+/// Builds the well-known log sent to verify the delivery path.
+pub fn canary_event(message: &str) -> Event {
+    let mut canary = Event::from(message);
+    let log = canary.as_mut_log();
+    log.insert("ddsource", "vector");
+    log.insert("service", "vector-canary");
+    log.insert("canary", true);
+    canary
+}
+
+/// Sends a canary log through the sink each time it is triggered, to verify on demand that the
+/// delivery path is live.
+///
+/// Unlike the startup canary, the canary is batched and sent along with the other events, and its
+/// outcome is only reported rather than failing the sink.
+#[derive(Clone, Debug, Default)]
+pub struct CanaryTrigger {
+    notify: Arc<Notify>,
+}
+
+impl CanaryTrigger {
+    /// Sends a canary, or the next time the sink reads its input if it isn't running yet.
+    pub fn trigger(&self) {
+        self.notify.notify_one();
+    }
+
+    /// Also triggers a canary on each `SIGUSR1`, for as long as the trigger is in use. `SIGHUP`
+    /// isn't used, as it reloads the configuration.
+    #[cfg(unix)]
+    pub fn on_sigusr1(self) -> std::io::Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut signals = signal(SignalKind::user_defined1())?;
+        let notify = Arc::downgrade(&self.notify);
+        tokio::spawn(async move {
+            while signals.recv().await.is_some() {
+                match notify.upgrade() {
+                    Some(notify) => notify.notify_one(),
+                    None => break,
+                }
+            }
+        });
+        Ok(self)
+    }
+
+    /// Adds a canary to `input` each time the trigger fires, until `input` ends.
+    pub fn inject<'a>(
+        self,
+        mut input: impl Stream<Item = Event> + Send + Unpin + 'a,
+    ) -> impl Stream<Item = Event> + Send + 'a {
+        stream! {
+            loop {
+                let event = tokio::select! {
+                    event = input.next() => event,
+                    _ = self.notify.notified() => Some(canary()),
+                };
+                match event {
+                    Some(event) => yield event,
+                    None => break,
+                }
+            }
+        }
+    }
+}
+
+fn canary() -> Event {
+    let (batch, receiver) = BatchNotifier::new_with_receiver();
+    let mut canary = canary_event("Canary sent by Vector.").with_batch_notifier(&batch);
+    // The canary wasn't read from the source, so its request mustn't acknowledge it.
+    canary.metadata_mut().set_source_events(0);
+    tokio::spawn(async move {
+        let status = receiver.await;
+        emit!(&DatadogLogsCanaryCompleted { status });
+    });
+    canary
+}
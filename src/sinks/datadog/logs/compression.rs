@@ -0,0 +1,251 @@
+use std::{io, io::Read};
+
+use flate2::read::GzDecoder;
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::sinks::util::{Compression, Compressor};
+
+/// The highest brotli quality, which is the default.
+pub const BROTLI_MAX: u32 = 11;
+/// The base-2 logarithm of the brotli window size, as recommended by the brotli reference.
+const BROTLI_WINDOW: u32 = 22;
+
+pub const ZSTD_DEFAULT: i32 = 3;
+const ZSTD_MAX: i32 = 22;
+
+/// The compression applied to the body of a request.
+///
+/// This extends the algorithms common to all sinks with brotli and zstd, which the Datadog Logs API
+/// accepts but which the other sinks can't apply.
+#[derive(Debug, Derivative, Copy, Clone, Eq, PartialEq)]
+#[derivative(Default)]
+pub enum RequestCompression {
+    #[derivative(Default)]
+    None,
+    Gzip(flate2::Compression),
+    /// Brotli at the given quality, from 0 to 11.
+    Brotli(u32),
+    /// Zstandard at the given level, from 1 to 22.
+    Zstd(i32),
+}
+
+impl RequestCompression {
+    pub const fn is_compressed(&self) -> bool {
+        !matches!(self, RequestCompression::None)
+    }
+
+    pub const fn content_encoding(self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Gzip(_) => Some("gzip"),
+            Self::Brotli(_) => Some("br"),
+            Self::Zstd(_) => Some("zstd"),
+        }
+    }
+
+    /// The name of the algorithm, as stamped by `add_codec_tag`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Gzip(_) => "gzip",
+            Self::Brotli(_) => "brotli",
+            Self::Zstd(_) => "zstd",
+        }
+    }
+
+    /// Decompresses a `body` compressed with this algorithm.
+    pub fn decompress(self, body: &[u8]) -> io::Result<Vec<u8>> {
+        let mut decompressed = Vec::new();
+        match self {
+            Self::None => decompressed.extend_from_slice(body),
+            Self::Gzip(_) => {
+                GzDecoder::new(body).read_to_end(&mut decompressed)?;
+            }
+            Self::Brotli(_) => {
+                brotli::Decompressor::new(body, 4096).read_to_end(&mut decompressed)?;
+            }
+            Self::Zstd(_) => decompressed = zstd::stream::decode_all(body)?,
+        }
+        Ok(decompressed)
+    }
+}
+
+impl From<Compression> for RequestCompression {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::None => Self::None,
+            Compression::Gzip(level) => Self::Gzip(level),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for RequestCompression {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // `Compression` borrows its strings, so it is deserialized from a buffered value.
+        let value = Value::deserialize(deserializer)?;
+        let standard = || {
+            Compression::deserialize(&value)
+                .map(Self::from)
+                .map_err(de::Error::custom)
+        };
+        match &value {
+            Value::String(s) if s == "brotli" => Ok(Self::Brotli(BROTLI_MAX)),
+            Value::String(s) if s == "zstd" => Ok(Self::Zstd(ZSTD_DEFAULT)),
+            // The level of zstd may also be given inline, as in `zstd(19)`.
+            Value::String(s) => match s
+                .strip_prefix("zstd(")
+                .and_then(|level| level.strip_suffix(')'))
+            {
+                Some(level) => zstd_level(Value::String(level.to_owned())).map(Self::Zstd),
+                None => standard(),
+            },
+            Value::Object(map) => match map.get("algorithm").and_then(Value::as_str) {
+                Some(algorithm @ ("brotli" | "zstd")) => {
+                    if let Some(key) = map
+                        .keys()
+                        .find(|key| *key != "algorithm" && *key != "level")
+                    {
+                        return Err(de::Error::unknown_field(key, &["algorithm", "level"]));
+                    }
+                    let level = map.get("level").cloned();
+                    if algorithm == "brotli" {
+                        let level = level.map(brotli_level).transpose()?;
+                        Ok(Self::Brotli(level.unwrap_or(BROTLI_MAX)))
+                    } else {
+                        let level = level.map(zstd_level).transpose()?;
+                        Ok(Self::Zstd(level.unwrap_or(ZSTD_DEFAULT)))
+                    }
+                }
+                _ => standard(),
+            },
+            _ => standard(),
+        }
+    }
+}
+
+fn brotli_level<E: de::Error>(level: Value) -> Result<u32, E> {
+    match level {
+        Value::Number(level) => match level.as_u64() {
+            Some(value) if value <= u64::from(BROTLI_MAX) => Ok(value as u32),
+            Some(_) | None => Err(de::Error::invalid_value(
+                de::Unexpected::Other(&level.to_string()),
+                &"an integer from 0 to 11",
+            )),
+        },
+        value => Err(de::Error::invalid_type(
+            de::Unexpected::Other(&value.to_string()),
+            &"integer",
+        )),
+    }
+}
+
+fn zstd_level<E: de::Error>(level: Value) -> Result<i32, E> {
+    let parsed = match &level {
+        Value::Number(level) => level.as_i64(),
+        // Inline levels are parsed from the string form.
+        Value::String(level) => level.parse::<i64>().ok(),
+        value => {
+            return Err(de::Error::invalid_type(
+                de::Unexpected::Other(&value.to_string()),
+                &"integer",
+            ))
+        }
+    };
+    match parsed {
+        Some(value) if (1..=i64::from(ZSTD_MAX)).contains(&value) => Ok(value as i32),
+        _ => Err(de::Error::invalid_value(
+            de::Unexpected::Other(&level.to_string()),
+            &"an integer from 1 to 22",
+        )),
+    }
+}
+
+impl Serialize for RequestCompression {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use ser::SerializeMap;
+
+        let (algorithm, level) = match *self {
+            Self::None => return Compression::None.serialize(serializer),
+            Self::Gzip(level) => return Compression::Gzip(level).serialize(serializer),
+            Self::Brotli(level) => ("brotli", (level != BROTLI_MAX).then(|| i64::from(level))),
+            Self::Zstd(level) => ("zstd", (level != ZSTD_DEFAULT).then(|| i64::from(level))),
+        };
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("algorithm", algorithm)?;
+        if let Some(level) = level {
+            map.serialize_entry("level", &level)?;
+        }
+        map.end()
+    }
+}
+
+/// Compresses a request body into a `W` with a [`RequestCompression`].
+///
+/// None and gzip go through the common [`Compressor`], while brotli and zstd are only applied here.
+pub enum RequestCompressor<W: io::Write> {
+    Standard(Compressor<W>),
+    Brotli(Box<brotli::CompressorWriter<W>>),
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+}
+
+impl<W: io::Write> RequestCompressor<W> {
+    /// Creates a compressor appending the body to `buffer`.
+    pub fn with_buffer(compression: RequestCompression, buffer: W) -> Self {
+        match compression {
+            RequestCompression::None => {
+                Self::Standard(Compressor::with_buffer(Compression::None, buffer))
+            }
+            RequestCompression::Gzip(level) => {
+                Self::Standard(Compressor::with_buffer(Compression::Gzip(level), buffer))
+            }
+            RequestCompression::Brotli(level) => Self::Brotli(Box::new(
+                brotli::CompressorWriter::new(buffer, 4096, level, BROTLI_WINDOW),
+            )),
+            RequestCompression::Zstd(level) => Self::Zstd(
+                zstd::stream::write::Encoder::new(buffer, level)
+                    .expect("zstd encoder should not fail to initialize"),
+            ),
+        }
+    }
+
+    /// Consumes the compressor, returning the buffer holding the compressed body.
+    ///
+    /// # Panics
+    ///
+    /// Panics if finalizing the compressor encounters an I/O error, as for
+    /// [`Compressor::into_inner`].
+    pub fn into_inner(self) -> W {
+        match self {
+            Self::Standard(compressor) => compressor.into_inner(),
+            Self::Brotli(writer) => writer.into_inner(),
+            Self::Zstd(writer) => writer
+                .finish()
+                .expect("zstd writer should not fail to finish"),
+        }
+    }
+}
+
+impl<W: io::Write> io::Write for RequestCompressor<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Standard(compressor) => compressor.write(buf),
+            Self::Brotli(writer) => writer.write(buf),
+            Self::Zstd(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Standard(compressor) => compressor.flush(),
+            Self::Brotli(writer) => writer.flush(),
+            Self::Zstd(writer) => writer.flush(),
+        }
+    }
+}
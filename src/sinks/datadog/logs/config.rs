@@ -1,27 +1,70 @@
-use std::{convert::TryFrom, num::NonZeroU64, sync::Arc};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fmt::{self, Debug},
+    net::SocketAddr,
+    num::{NonZeroU64, NonZeroUsize},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
 
-use futures::FutureExt;
+use futures::{future, Future, FutureExt};
+use http::{
+    header::{HeaderName, HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE},
+    HeaderMap, Request, StatusCode, Uri,
+};
+use hyper::Body;
 use indoc::indoc;
-use serde::{Deserialize, Serialize};
-use tower::ServiceBuilder;
-use vector_core::config::proxy::ProxyConfig;
+use regex::bytes::Regex;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use tokio::time::sleep;
+use tower::{Service, ServiceBuilder};
+use vector_core::{
+    config::proxy::ProxyConfig,
+    stream::{BatcherSettings, DriverResponse},
+};
 
 use super::{
-    service::LogApiRetry,
-    sink::{DatadogLogsJsonEncoding, LogSinkBuilder},
+    ack_verification::AckVerifier,
+    bandwidth::PartitionRateLimits,
+    canary::CanaryTrigger,
+    compression::RequestCompression,
+    deadline::DeadlineService,
+    dry_run::DryRunSink,
+    dual_ship::SecondarySite,
+    mirror::FileMirror,
+    multiline::Multiline,
+    routing::FieldRoutes,
+    self_metrics::SelfMetrics,
+    service::{CompressionFallback, DatadogLogsError, LogApiRequest, LogApiRetry},
+    sink::{ControlChars, DatadogLogsJsonEncoding, DefaultAttributes, LogSinkBuilder, PreSendHook},
+    stall::StallTracker,
+    time_bucket::TimeBucketRouter,
 };
 use crate::{
-    config::{DataType, GenerateConfig, SinkConfig, SinkContext},
+    config::{DataType, GenerateConfig, Resource, SinkConfig, SinkContext},
+    event::Event,
     http::HttpClient,
+    internal_events::{
+        DatadogLogsApiKeyLooksLikeAppKey, DatadogLogsDeprecatedRegion,
+        DatadogLogsEndpointNotOnSite, DatadogLogsUnknownSite,
+    },
     sinks::{
-        datadog::{get_api_validate_endpoint, healthcheck, logs::service::LogApiService, Region},
+        datadog::{
+            get_api_validate_endpoint, get_base_domain, logs::service::LogApiService, Region,
+        },
         util::{
-            encoding::EncodingConfigFixed, service::ServiceBuilderExt, BatchConfig, Compression,
-            SinkBatchSettings, TowerRequestConfig,
+            encoding::{EncodingConfigFixed, EncodingConfigWithDefault},
+            http::RequestConfig,
+            service::ServiceBuilderExt,
+            BatchConfig, Compression, SinkBatchSettings, TowerRequestConfig, TowerRequestSettings,
         },
         Healthcheck, VectorSink,
     },
+    template::Template,
     tls::{MaybeTlsSettings, TlsConfig},
+    transforms::metric_to_log::{MetricToLog, MetricToLogConfig},
 };
 
 // The Datadog API has a hard limit of 5MB for uncompressed payloads. Above this
@@ -32,10 +75,22 @@ use crate::{
 // of escaped double-quotes -- but we believe this should be very rare in
 // practice.
 pub const MAX_PAYLOAD_BYTES: usize = 5_000_000;
+// The Datadog API rejects the whole payload if any single log is above 256KB once encoded.
+pub const MAX_LOG_BYTES: usize = 256_000;
 pub const BATCH_GOAL_BYTES: usize = 4_250_000;
 pub const BATCH_MAX_EVENTS: usize = 1_000;
 pub const BATCH_DEFAULT_TIMEOUT_SECS: u64 = 5;
 
+/// The Datadog sites, which a configured `site` is checked against to catch typos.
+const KNOWN_SITES: &[&str] = &[
+    "datadoghq.com",
+    "us3.datadoghq.com",
+    "us5.datadoghq.com",
+    "datadoghq.eu",
+    "ddog-gov.com",
+    "ap1.datadoghq.com",
+];
+
 #[derive(Clone, Copy, Debug, Default)]
 pub struct DatadogLogsDefaultBatchSettings;
 
@@ -49,28 +104,869 @@ impl SinkBatchSettings for DatadogLogsDefaultBatchSettings {
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct DatadogLogsConfig {
-    pub(crate) endpoint: Option<String>,
+    pub(crate) endpoint: Option<Endpoints>,
     // Deprecated, replaced by the site option
+    #[serde(default, deserialize_with = "deserialize_deprecated_region")]
     region: Option<Region>,
     site: Option<String>,
     // Deprecated name
     #[serde(alias = "api_key")]
-    default_api_key: String,
+    default_api_key: ApiKey,
     #[serde(
         skip_serializing_if = "crate::serde::skip_serializing_if_default",
         default
     )]
-    encoding: EncodingConfigFixed<DatadogLogsJsonEncoding>,
+    encoding: EncodingConfigWithDefault<DatadogLogsCodec>,
     tls: Option<TlsConfig>,
 
     #[serde(default)]
-    compression: Option<Compression>,
+    compression: Option<CompressionByCodec>,
 
     #[serde(default)]
     batch: BatchConfig<DatadogLogsDefaultBatchSettings>,
 
     #[serde(default)]
-    request: TowerRequestConfig,
+    request: DatadogLogsRequestConfig,
+
+    #[serde(default)]
+    deterministic_id: Option<DeterministicIdConfig>,
+
+    #[serde(default)]
+    max_bandwidth_bytes_per_sec: Option<u64>,
+
+    // Events without a correlation ID in their metadata get a generated one
+    #[serde(default)]
+    correlation_id_from_metadata: bool,
+
+    #[serde(default)]
+    on_compression_rejected: Option<CompressionRejectedConfig>,
+
+    #[serde(default)]
+    strip_control_chars: bool,
+
+    // Only applies when `strip_control_chars` is set
+    #[serde(default = "crate::serde::default_true")]
+    strip_control_chars_keep_whitespace: bool,
+
+    #[serde(default)]
+    dual_ship: Option<DualShipConfig>,
+
+    #[serde(default)]
+    implausible_timestamp_threshold_secs: Option<u64>,
+
+    #[serde(default)]
+    max_attributes_per_event: Option<usize>,
+
+    #[serde(default)]
+    time_bucketing: Option<TimeBucketConfig>,
+
+    #[serde(default)]
+    precompressed_field: Option<String>,
+
+    #[serde(default)]
+    drain_order: DrainOrder,
+
+    #[serde(default)]
+    dotted_key_policy: DottedKeyPolicy,
+
+    #[serde(default)]
+    size_class_partitioning: Option<Vec<usize>>,
+
+    #[serde(default)]
+    healthcheck_use_real_body: bool,
+
+    #[serde(default)]
+    healthcheck_boot_retries: usize,
+
+    // The delay before the first retry, which doubles after each further failure
+    #[serde(default = "default_healthcheck_boot_backoff_secs")]
+    healthcheck_boot_backoff_secs: u64,
+
+    // Statuses which pass the healthcheck in addition to `2xx`
+    #[serde(default)]
+    healthcheck_healthy_statuses: Vec<u16>,
+
+    #[serde(default)]
+    partition_stall_threshold_secs: Option<u64>,
+
+    #[serde(default)]
+    metrics: Option<MetricToLogConfig>,
+
+    #[serde(default)]
+    startup_canary: bool,
+
+    #[serde(default)]
+    max_concurrent_handshakes: Option<usize>,
+
+    // Requests per second, by API key
+    #[serde(default)]
+    per_partition_rate_limit: HashMap<ApiKey, u64>,
+
+    #[serde(default)]
+    flush_on_change_field: Option<String>,
+
+    #[serde(default)]
+    buffer_metrics_interval_secs: Option<u64>,
+
+    #[serde(default)]
+    host_template: Option<Template>,
+
+    #[serde(default)]
+    omit_content_length: bool,
+
+    #[serde(default)]
+    add_codec_tag: bool,
+
+    #[serde(default)]
+    on_oversized: OversizedLogPolicy,
+
+    #[serde(default)]
+    max_attribute_value_bytes: Option<usize>,
+
+    #[serde(default)]
+    reserved_attributes: ReservedAttributesConfig,
+
+    #[serde(default)]
+    add_send_timestamp: bool,
+
+    #[serde(default)]
+    mirror_to_file: Option<FileMirrorConfig>,
+
+    #[serde(default)]
+    default_ddsource: Option<String>,
+
+    #[serde(default)]
+    default_ddtags: Option<Vec<String>>,
+
+    #[serde(default)]
+    default_hostname: Option<String>,
+
+    #[serde(default)]
+    default_service: Option<String>,
+
+    #[serde(default)]
+    invalid_timestamp: InvalidTimestampPolicy,
+
+    #[serde(default)]
+    partition_key_fields: Vec<String>,
+
+    #[serde(default)]
+    drop_summary_interval_secs: Option<u64>,
+
+    // Only available in debug builds
+    #[serde(default)]
+    strict_ack_verification: bool,
+
+    #[serde(default)]
+    multiline: Option<MultilineConfig>,
+
+    // Only available on Unix
+    #[serde(default)]
+    canary_on_sigusr1: bool,
+
+    // The API key of events without one, required when `default_api_key` is a template
+    #[serde(default)]
+    fallback_api_key: Option<ApiKey>,
+
+    // Events older than this are dropped, as Datadog would drop them at ingest
+    #[serde(default)]
+    target_retention_days: Option<u32>,
+
+    #[serde(default)]
+    encode_concurrency: Option<usize>,
+
+    // Only used by the `cef` codec
+    #[serde(default)]
+    cef: CefConfig,
+
+    #[serde(default)]
+    idempotency: bool,
+
+    #[serde(default = "default_idempotency_header")]
+    idempotency_header: String,
+
+    // Ends batches where this integer field doesn't follow on from the previous event
+    #[serde(default)]
+    flush_on_sequence_gap: Option<String>,
+
+    #[serde(default)]
+    self_metrics: Option<SelfMetricsConfig>,
+
+    // Sends the whole event as the message of events without one
+    #[serde(default)]
+    fill_missing_message: bool,
+
+    // Stamps events without a timestamp with the time they are encoded
+    #[serde(default)]
+    fill_missing_timestamp: bool,
+
+    // Only validates the configuration, and discards events instead of sending them
+    #[serde(default)]
+    dry_run: bool,
+
+    // Moves the contents of this field into the tags of each event
+    #[serde(default)]
+    ddtags_field: Option<String>,
+
+    #[serde(default)]
+    invalid_utf8: InvalidUtf8Policy,
+
+    // Sends events to the intake matching the value of a field
+    #[serde(default)]
+    route_by_field: Option<RouteByFieldConfig>,
+
+    // Flattens nested attributes into dotted top-level keys
+    #[serde(default)]
+    flatten_attributes: bool,
+
+    // Only set when building the configuration programmatically
+    #[serde(skip)]
+    pre_send_hook: Option<PreSendHook>,
+}
+
+fn default_idempotency_header() -> String {
+    "Idempotency-Key".to_string()
+}
+
+// Warns about `region` as it's parsed, as the config is normalized and validated again on every
+// build and reload.
+fn deserialize_deprecated_region<'de, D>(deserializer: D) -> Result<Option<Region>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let region = Option::<Region>::deserialize(deserializer)?;
+    if region.is_some() {
+        emit!(&DatadogLogsDeprecatedRegion);
+    }
+    Ok(region)
+}
+
+/// The compression of request bodies, which extends the algorithms with `smart`.
+#[derive(Debug, Clone, Copy)]
+pub enum DatadogLogsCompression {
+    Standard(RequestCompression),
+    /// Compresses with gzip, except for partitions whose payloads recently barely compressed.
+    Smart,
+}
+
+impl<'de> Deserialize<'de> for DatadogLogsCompression {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        match value.as_str() {
+            Some("smart") => Ok(DatadogLogsCompression::Smart),
+            _ => RequestCompression::deserialize(&value)
+                .map(DatadogLogsCompression::Standard)
+                .map_err(de::Error::custom),
+        }
+    }
+}
+
+impl Serialize for DatadogLogsCompression {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            DatadogLogsCompression::Standard(compression) => compression.serialize(serializer),
+            DatadogLogsCompression::Smart => serializer.serialize_str("smart"),
+        }
+    }
+}
+
+/// The compression of request bodies, either for every codec or by codec, where codecs without
+/// one aren't compressed.
+#[derive(Debug, Clone)]
+pub enum CompressionByCodec {
+    All(DatadogLogsCompression),
+    ByCodec(HashMap<DatadogLogsCodec, DatadogLogsCompression>),
+}
+
+impl CompressionByCodec {
+    fn get(&self, codec: DatadogLogsCodec) -> Option<DatadogLogsCompression> {
+        match self {
+            CompressionByCodec::All(compression) => Some(*compression),
+            CompressionByCodec::ByCodec(by_codec) => by_codec.get(&codec).copied(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for CompressionByCodec {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        // A table is the compression of every codec if it names an algorithm, as in
+        // `{ algorithm = "gzip", level = 9 }`, and otherwise gives the compression of each codec.
+        match value.as_object() {
+            Some(table) if !table.contains_key("algorithm") => {
+                HashMap::deserialize(value).map(CompressionByCodec::ByCodec)
+            }
+            _ => DatadogLogsCompression::deserialize(value).map(CompressionByCodec::All),
+        }
+        .map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for CompressionByCodec {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            CompressionByCodec::All(compression) => compression.serialize(serializer),
+            CompressionByCodec::ByCodec(by_codec) => by_codec.serialize(serializer),
+        }
+    }
+}
+
+/// The endpoints overriding the site, either a single one or a list tried in order of preference.
+///
+/// Requests fail over to the next endpoint of a list when the current one can't be connected to.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Endpoints {
+    Single(String),
+    Failover(Vec<String>),
+}
+
+impl Endpoints {
+    fn iter(&self) -> impl Iterator<Item = &String> {
+        match self {
+            Endpoints::Single(endpoint) => std::slice::from_ref(endpoint).iter(),
+            Endpoints::Failover(endpoints) => endpoints.iter(),
+        }
+    }
+}
+
+impl From<String> for Endpoints {
+    fn from(endpoint: String) -> Self {
+        Endpoints::Single(endpoint)
+    }
+}
+
+/// An API key, which is redacted when formatted so that it can't leak into logs or errors.
+#[derive(Deserialize, Serialize, Clone, Eq, Hash, PartialEq)]
+#[serde(transparent)]
+pub struct ApiKey(String);
+
+impl ApiKey {
+    /// The key itself, only to be sent to Datadog.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Debug for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("***")
+    }
+}
+
+impl fmt::Display for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("***")
+    }
+}
+
+/// The order in which batches that are ready at the same time, such as while draining a backlog,
+/// are sent.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DrainOrder {
+    /// Oldest batch first.
+    Fifo,
+    /// Most recently batched first.
+    Lifo,
+    /// The batch with the newest event timestamps first.
+    NewestFirst,
+}
+
+impl Default for DrainOrder {
+    fn default() -> Self {
+        DrainOrder::Fifo
+    }
+}
+
+/// The handling of attribute keys containing dots, which Datadog reads as paths into nested
+/// attributes.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DottedKeyPolicy {
+    /// Sends the key as is, leaving Datadog to nest it.
+    Nest,
+    /// Replaces the dots with underscores.
+    Sanitize,
+    /// Drops the attribute.
+    Reject,
+}
+
+impl Default for DottedKeyPolicy {
+    fn default() -> Self {
+        DottedKeyPolicy::Nest
+    }
+}
+
+/// The handling of logs above the size limit of the Datadog Logs API, which would otherwise get
+/// their whole batch rejected.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OversizedLogPolicy {
+    /// Drops the log.
+    Drop,
+    /// Shortens the message of the log to fit, dropping the log if that isn't enough.
+    Truncate,
+}
+
+impl Default for OversizedLogPolicy {
+    fn default() -> Self {
+        OversizedLogPolicy::Drop
+    }
+}
+
+/// The handling of events whose timestamp isn't a time value, which Datadog can't parse.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InvalidTimestampPolicy {
+    /// Removes the timestamp, leaving Datadog to use the time it receives the log.
+    Remove,
+    /// Replaces the timestamp with the current time.
+    Now,
+    /// Drops the log.
+    Drop,
+    /// Sends the timestamp as is.
+    Leave,
+}
+
+impl Default for InvalidTimestampPolicy {
+    fn default() -> Self {
+        InvalidTimestampPolicy::Remove
+    }
+}
+
+impl InvalidTimestampPolicy {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            InvalidTimestampPolicy::Remove => "remove",
+            InvalidTimestampPolicy::Now => "now",
+            InvalidTimestampPolicy::Drop => "drop",
+            InvalidTimestampPolicy::Leave => "leave",
+        }
+    }
+}
+
+/// The handling of events whose message isn't valid UTF-8, which Datadog may reject or store
+/// garbled.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InvalidUtf8Policy {
+    /// Sends the message as is, leaving it to the codec.
+    Pass,
+    /// Replaces the invalid sequences with the Unicode replacement character.
+    Replace,
+    /// Drops the log.
+    Drop,
+}
+
+impl Default for InvalidUtf8Policy {
+    fn default() -> Self {
+        InvalidUtf8Policy::Pass
+    }
+}
+
+/// The format of request bodies.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DatadogLogsCodec {
+    /// A JSON array of logs, as accepted by the Datadog Logs API.
+    Json,
+    /// One JSON log per line, which is easier to stream and to inspect in captured payloads.
+    Ndjson,
+    /// Each log in Vector's protobuf encoding, prefixed by its length as a big-endian `u32`, for
+    /// relays which ingest that framing rather than JSON.
+    Binary,
+    /// One log per line in the Common Event Format, for SIEM relays which don't accept JSON.
+    Cef,
+}
+
+impl Default for DatadogLogsCodec {
+    fn default() -> Self {
+        DatadogLogsCodec::Json
+    }
+}
+
+impl DatadogLogsCodec {
+    pub const fn content_type(self) -> &'static str {
+        match self {
+            DatadogLogsCodec::Json | DatadogLogsCodec::Ndjson => "application/json",
+            DatadogLogsCodec::Binary => "application/octet-stream",
+            DatadogLogsCodec::Cef => "text/plain",
+        }
+    }
+}
+
+/// The header fields identifying the sender of logs encoded with the `cef` codec.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CefConfig {
+    #[serde(default = "default_cef_device")]
+    pub device_vendor: String,
+    #[serde(default = "default_cef_device")]
+    pub device_product: String,
+    #[serde(default = "default_cef_device_version")]
+    pub device_version: String,
+}
+
+impl Default for CefConfig {
+    fn default() -> Self {
+        Self {
+            device_vendor: default_cef_device(),
+            device_product: default_cef_device(),
+            device_version: default_cef_device_version(),
+        }
+    }
+}
+
+fn default_cef_device() -> String {
+    "Vector".to_string()
+}
+
+fn default_cef_device_version() -> String {
+    crate::get_version()
+}
+
+/// Configuration for stamping each event with an identifier derived from its content, so that
+/// Datadog can deduplicate events which are reprocessed.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DeterministicIdConfig {
+    /// The fields whose values are hashed to produce the identifier.
+    pub fields: Vec<String>,
+    /// The attribute the identifier is written to.
+    #[serde(default = "default_deterministic_id_target")]
+    pub target: String,
+}
+
+fn default_deterministic_id_target() -> String {
+    "event_id".to_string()
+}
+
+/// Configuration for the attributes that the message, host and timestamp of events are sent as,
+/// which Datadog reads specially.
+///
+/// An empty name leaves the field under its name in the log schema, and the timestamp in its
+/// original format.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReservedAttributesConfig {
+    pub message: String,
+    pub host: String,
+    /// The timestamp is also converted to milliseconds since the epoch.
+    pub timestamp: String,
+}
+
+impl Default for ReservedAttributesConfig {
+    fn default() -> Self {
+        Self {
+            message: "message".to_string(),
+            host: "host".to_string(),
+            timestamp: "timestamp".to_string(),
+        }
+    }
+}
+
+/// Configuration for falling back to uncompressed requests when compressed ones are rejected, as
+/// some relays are misconfigured to reject compressed bodies.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct CompressionRejectedConfig {
+    /// The number of consecutive compressed requests rejected with `400 Bad Request` as their body
+    /// couldn't be decoded, after which compression is disabled.
+    #[serde(default = "default_consecutive_rejections")]
+    pub consecutive_rejections: usize,
+}
+
+const fn default_consecutive_rejections() -> usize {
+    3
+}
+
+/// Configuration for shipping a copy of every event to a second Datadog site, e.g. while migrating
+/// between organizations.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DualShipConfig {
+    pub(crate) endpoint: Option<String>,
+    site: Option<String>,
+    /// The API key used for all events sent to the second site.
+    api_key: ApiKey,
+}
+
+/// Configuration for sending events to a different Datadog intake depending on the value of one of
+/// their fields, e.g. to keep EU-resident logs on the EU site.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RouteByFieldConfig {
+    /// The field whose value selects the intake.
+    pub field: String,
+    /// The intake of each value of the field.
+    pub routes: HashMap<String, RouteTarget>,
+    /// The intake of events whose value matches no route, which defaults to the sink's own
+    /// `endpoint` or `site`.
+    #[serde(default)]
+    pub default: Option<RouteTarget>,
+}
+
+/// A Datadog intake, given by its site, or by an endpoint overriding the site.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RouteTarget {
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    #[serde(default)]
+    pub site: Option<String>,
+}
+
+/// Configuration for appending the uncompressed body of every request to a local file, to retain a
+/// copy of everything sent to Datadog.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FileMirrorConfig {
+    /// The file the bodies are appended to, one per line. Rotated files are suffixed with the time
+    /// of their rotation.
+    pub path: PathBuf,
+    /// The size above which the file is rotated.
+    #[serde(default = "default_mirror_max_bytes")]
+    pub max_bytes: u64,
+    /// The age after which the file is rotated, if set.
+    #[serde(default)]
+    pub rotate_secs: Option<u64>,
+}
+
+const fn default_mirror_max_bytes() -> u64 {
+    100 * 1024 * 1024
+}
+
+/// Configuration for coalescing the consecutive lines of one message, such as a stack trace, into a
+/// single log.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MultilineConfig {
+    /// The pattern matching the first line of a message. The following lines which don't match it
+    /// are appended to that message.
+    pub start_pattern: String,
+    /// The time after the last line of a message at which it is sent, even if the next message
+    /// hasn't started yet.
+    #[serde(default = "default_multiline_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+const fn default_multiline_timeout_ms() -> u64 {
+    1000
+}
+
+/// The request options of the sink, which extend the common options with a deadline and a limit
+/// on the bytes in flight.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct DatadogLogsRequestConfig {
+    #[serde(flatten)]
+    pub common: RequestConfig,
+    /// The total time spent on a request across its retries, after which its events are
+    /// finalized as errored, if set.
+    #[serde(default)]
+    pub deadline_secs: Option<u64>,
+    /// The total size of the request bodies in flight, past which new requests are held back, if
+    /// set.
+    #[serde(default)]
+    pub max_in_flight_bytes: Option<u64>,
+}
+
+/// Configuration for serving counts of the sink's requests in the Prometheus text format, for
+/// environments which don't consume Vector's internal metrics.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SelfMetricsConfig {
+    /// The address the counts are served on, at any path.
+    pub address: SocketAddr,
+}
+
+/// Configuration for batching recent events separately from backfilled ones, so that backfill
+/// traffic goes through its own lower-priority path.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TimeBucketConfig {
+    /// Events whose timestamp is within this many seconds of the current time are hot, and all
+    /// older ones are backfill.
+    #[serde(default = "default_hot_window_secs")]
+    pub hot_window_secs: u64,
+    /// The request settings of backfill events, while hot events use the sink's `request`.
+    #[serde(default)]
+    pub backfill_request: TowerRequestConfig,
+}
+
+const fn default_hot_window_secs() -> u64 {
+    60
+}
+
+const fn default_healthcheck_boot_backoff_secs() -> u64 {
+    1
+}
+
+/// Builds a [`DatadogLogsConfig`] programmatically, such as when embedding Vector as a library.
+///
+/// Options which aren't set get the same defaults as when the configuration is parsed.
+#[derive(Debug, Default)]
+pub struct DatadogLogsConfigBuilder {
+    api_key: Option<ApiKey>,
+    endpoint: Option<String>,
+    region: Option<Region>,
+    site: Option<String>,
+    encoding: Option<DatadogLogsCodec>,
+    compression: Option<Compression>,
+    batch: Option<BatchConfig<DatadogLogsDefaultBatchSettings>>,
+    request: Option<DatadogLogsRequestConfig>,
+    pre_send_hook: Option<PreSendHook>,
+}
+
+impl DatadogLogsConfigBuilder {
+    /// Sets the API key of events without one of their own, which is required.
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(ApiKey(api_key.into()));
+        self
+    }
+
+    /// Sends events to `endpoint` rather than to the endpoint of the site.
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Sends events to the Datadog region `region`.
+    ///
+    /// This is deprecated in favor of `site`.
+    pub const fn region(mut self, region: Region) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    pub fn site(mut self, site: impl Into<String>) -> Self {
+        self.site = Some(site.into());
+        self
+    }
+
+    pub const fn encoding(mut self, codec: DatadogLogsCodec) -> Self {
+        self.encoding = Some(codec);
+        self
+    }
+
+    /// Compresses request bodies with `compression`, whatever their codec.
+    pub const fn compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    pub const fn batch(mut self, batch: BatchConfig<DatadogLogsDefaultBatchSettings>) -> Self {
+        self.batch = Some(batch);
+        self
+    }
+
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn request(mut self, request: DatadogLogsRequestConfig) -> Self {
+        self.request = Some(request);
+        self
+    }
+
+    /// Runs `hook` on each event just before it is encoded, for last-mile mutations when embedding
+    /// Vector as a library.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn with_pre_send_hook(mut self, hook: Box<dyn Fn(&mut Event) + Send + Sync>) -> Self {
+        self.pre_send_hook = Some(PreSendHook::new(hook));
+        self
+    }
+
+    /// Builds the configuration, checking it as the sink would before being built.
+    pub fn build(self) -> crate::Result<DatadogLogsConfig> {
+        let api_key = self.api_key.ok_or("`default_api_key` must be set.")?;
+        // The other options get the same defaults as when they are missing from a parsed
+        // configuration, which `builder_matches_parsed_config` checks.
+        let config = DatadogLogsConfig {
+            endpoint: self.endpoint.map(Endpoints::from),
+            region: self.region,
+            site: self.site,
+            default_api_key: api_key,
+            encoding: self.encoding.map(Into::into).unwrap_or_default(),
+            tls: None,
+            compression: self.compression.map(|compression| {
+                CompressionByCodec::All(DatadogLogsCompression::Standard(compression.into()))
+            }),
+            batch: self.batch.unwrap_or_default(),
+            request: self.request.unwrap_or_default(),
+            deterministic_id: None,
+            max_bandwidth_bytes_per_sec: None,
+            correlation_id_from_metadata: false,
+            on_compression_rejected: None,
+            strip_control_chars: false,
+            strip_control_chars_keep_whitespace: true,
+            dual_ship: None,
+            implausible_timestamp_threshold_secs: None,
+            max_attributes_per_event: None,
+            time_bucketing: None,
+            precompressed_field: None,
+            drain_order: Default::default(),
+            dotted_key_policy: Default::default(),
+            size_class_partitioning: None,
+            healthcheck_use_real_body: false,
+            healthcheck_boot_retries: Default::default(),
+            healthcheck_boot_backoff_secs: default_healthcheck_boot_backoff_secs(),
+            healthcheck_healthy_statuses: Vec::new(),
+            partition_stall_threshold_secs: None,
+            metrics: None,
+            startup_canary: false,
+            max_concurrent_handshakes: None,
+            per_partition_rate_limit: HashMap::new(),
+            flush_on_change_field: None,
+            buffer_metrics_interval_secs: None,
+            host_template: None,
+            omit_content_length: false,
+            add_codec_tag: false,
+            on_oversized: Default::default(),
+            max_attribute_value_bytes: None,
+            reserved_attributes: Default::default(),
+            add_send_timestamp: false,
+            mirror_to_file: None,
+            default_ddsource: None,
+            default_ddtags: None,
+            default_hostname: None,
+            default_service: None,
+            invalid_timestamp: Default::default(),
+            partition_key_fields: Vec::new(),
+            drop_summary_interval_secs: None,
+            strict_ack_verification: false,
+            multiline: None,
+            canary_on_sigusr1: false,
+            fallback_api_key: None,
+            target_retention_days: None,
+            encode_concurrency: None,
+            cef: Default::default(),
+            idempotency: false,
+            idempotency_header: default_idempotency_header(),
+            flush_on_sequence_gap: None,
+            self_metrics: None,
+            fill_missing_message: false,
+            fill_missing_timestamp: false,
+            dry_run: false,
+            ddtags_field: None,
+            invalid_utf8: Default::default(),
+            route_by_field: None,
+            flatten_attributes: false,
+            pre_send_hook: self.pre_send_hook,
+        };
+        // `region` is only checked against `site` and `endpoint` as it's normalized.
+        config.normalized()?.validate()?;
+        Ok(config)
+    }
 }
 
 impl GenerateConfig for DatadogLogsConfig {
@@ -82,73 +978,837 @@ impl GenerateConfig for DatadogLogsConfig {
     }
 }
 
+/// Warns if `site` is not a known Datadog site, unless it is overridden by `endpoint`.
+///
+/// This is only a warning, as `site` may legitimately point to a custom relay.
+fn check_site(endpoint: Option<&String>, site: Option<&String>) {
+    if let (None, Some(site)) = (endpoint, site) {
+        if !KNOWN_SITES.contains(&site.as_str()) {
+            emit!(&DatadogLogsUnknownSite { site });
+        }
+    }
+}
+
+/// Checks that `site` and the deprecated `region` agree, and that `endpoint`, which overrides
+/// both, is on their domain, as one of them would otherwise be silently ignored.
+///
+/// Only an endpoint on a different Datadog site is an error. An endpoint elsewhere, such as a relay,
+/// a proxy or a local server, is only warned about.
+fn check_site_conflicts<'a>(
+    endpoints: impl Iterator<Item = Option<&'a String>>,
+    site: Option<&String>,
+    region: Option<Region>,
+) -> crate::Result<()> {
+    let region_domain = region.map(|region| get_base_domain(None, Some(region)));
+    if let (Some(site), Some(region_domain)) = (site, region_domain) {
+        if site != region_domain {
+            return Err(format!(
+                "`site` ({}) and `region` ({}) point to different Datadog sites.",
+                site, region_domain
+            )
+            .into());
+        }
+    }
+    for endpoint in endpoints.flatten() {
+        let host = match endpoint
+            .parse::<Uri>()
+            .ok()
+            .and_then(|uri| uri.host().map(str::to_owned))
+        {
+            Some(host) => host,
+            None => continue,
+        };
+        let endpoint_site = datadog_site_of(&host);
+        for (option, described, domain) in [
+            ("site", "`site`", site.map(String::as_str)),
+            ("region", "the site of `region`", region_domain),
+        ] {
+            let domain = match domain {
+                Some(domain) => domain,
+                None => continue,
+            };
+            match endpoint_site {
+                Some(endpoint_site) if KNOWN_SITES.contains(&domain) => {
+                    if endpoint_site != domain {
+                        return Err(format!(
+                            "`endpoint` ({}) is not on {} ({}), which it overrides.",
+                            endpoint, described, domain
+                        )
+                        .into());
+                    }
+                }
+                _ if host == domain || host.ends_with(&format!(".{}", domain)) => {}
+                _ => emit!(&DatadogLogsEndpointNotOnSite {
+                    endpoint,
+                    option,
+                    site: domain,
+                }),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Gets the known Datadog site `host` is on, if any.
+fn datadog_site_of(host: &str) -> Option<&'static str> {
+    KNOWN_SITES
+        .iter()
+        .copied()
+        .filter(|site| host == *site || host.ends_with(&format!(".{}", site)))
+        // `us3.datadoghq.com` is on `datadoghq.com` too, but is a site of its own.
+        .max_by_key(|site| site.len())
+}
+
+/// Warns if `api_key` is shaped like an application key rather than an API key.
+///
+/// API keys are 32 hexadecimal characters long, while application keys are 40, and pasting the
+/// latter by mistake only surfaces as confusing authentication failures.
+fn check_api_key(option: &str, api_key: &str) {
+    if api_key.len() == 40 && api_key.chars().all(|c| c.is_ascii_hexdigit()) {
+        emit!(&DatadogLogsApiKeyLooksLikeAppKey { option });
+    }
+}
+
+/// The logs intake URI of `endpoint`, or else of `site`, defaulting to the US site.
+///
+/// The deprecated `region` must have been converted into `site` by `normalized` beforehand.
+fn logs_uri(endpoint: Option<&String>, site: Option<&String>) -> Uri {
+    let endpoint = endpoint.cloned().unwrap_or_else(|| {
+        format!(
+            "https://http-intake.logs.{}/api/v2/logs",
+            get_base_domain(site, None)
+        )
+    });
+    Uri::try_from(endpoint).expect("URI not valid")
+}
+
 impl DatadogLogsConfig {
     // TODO: We should probably hoist this type of base URI generation so that all DD sinks can
     // utilize it, since it all follows the same pattern.
-    fn get_uri(&self) -> http::Uri {
-        let endpoint = self
-            .endpoint
-            .clone()
-            .or_else(|| {
-                self.site
-                    .as_ref()
-                    .map(|s| format!("https://http-intake.logs.{}/api/v2/logs", s))
-            })
-            .unwrap_or_else(|| match self.region {
-                Some(Region::Eu) => "https://http-intake.logs.datadoghq.eu/api/v2/logs".to_string(),
-                None | Some(Region::Us) => {
-                    "https://http-intake.logs.datadoghq.com/api/v2/logs".to_string()
+    fn get_uris(&self) -> Vec<Uri> {
+        self.endpoints()
+            .map(|endpoint| logs_uri(endpoint, self.site.as_ref()))
+            .collect()
+    }
+
+    /// The endpoints overriding the site in order of preference, or a single `None` if the
+    /// endpoint is derived from the site.
+    fn endpoints(&self) -> impl Iterator<Item = Option<&String>> {
+        let endpoints = self.endpoint.iter().flat_map(Endpoints::iter).map(Some);
+        let default = self.endpoint.is_none().then(|| None);
+        endpoints.chain(default)
+    }
+
+    /// The intakes events may be routed to by `route_by_field`, including its default.
+    fn route_targets(&self) -> impl Iterator<Item = &RouteTarget> {
+        self.route_by_field.iter().flat_map(|route_by_field| {
+            route_by_field
+                .routes
+                .values()
+                .chain(route_by_field.default.as_ref())
+        })
+    }
+
+    fn field_routes(&self) -> Option<FieldRoutes> {
+        self.route_by_field.as_ref().map(|route_by_field| {
+            let uri =
+                |target: &RouteTarget| logs_uri(target.endpoint.as_ref(), target.site.as_ref());
+            FieldRoutes::new(
+                route_by_field.field.clone(),
+                route_by_field
+                    .routes
+                    .iter()
+                    .map(|(value, target)| (value.clone(), uri(target)))
+                    .collect(),
+                route_by_field.default.as_ref().map(uri),
+            )
+        })
+    }
+
+    /// Gets the batch settings, capped to the size/log line limits imposed by the Datadog Logs API.
+    ///
+    /// Settings above the limits are lowered to them with a warning rather than rejected, as
+    /// payloads beyond them would be rejected by Datadog, but they may still be lowered if need be.
+    fn batch_settings(&self) -> crate::Result<BatcherSettings> {
+        let mut batch = self.batch.validate()?;
+        if let Some(max_bytes) = batch.max_bytes.filter(|n| *n > BATCH_GOAL_BYTES) {
+            warn!(
+                message =
+                    "Lowering `batch.max_bytes` to the payload size limit of the Datadog Logs API.",
+                max_bytes,
+                limit = BATCH_GOAL_BYTES
+            );
+            batch.max_bytes = Some(BATCH_GOAL_BYTES);
+        }
+        if let Some(max_events) = batch.max_events.filter(|n| *n > BATCH_MAX_EVENTS) {
+            warn!(
+                message =
+                    "Lowering `batch.max_events` to the log line limit of the Datadog Logs API.",
+                max_events,
+                limit = BATCH_MAX_EVENTS
+            );
+            batch.max_events = Some(BATCH_MAX_EVENTS);
+        }
+        Ok(batch.into_batcher_settings()?)
+    }
+
+    /// The compression of the codec in use.
+    fn codec_compression(&self) -> Option<DatadogLogsCompression> {
+        self.compression
+            .as_ref()
+            .and_then(|compression| compression.get(self.encoding.codec))
+    }
+
+    fn compression(&self) -> RequestCompression {
+        match self.codec_compression() {
+            None => RequestCompression::default(),
+            Some(DatadogLogsCompression::Standard(compression)) => compression,
+            Some(DatadogLogsCompression::Smart) => Compression::gzip_default().into(),
+        }
+    }
+
+    fn default_attributes(&self) -> DefaultAttributes {
+        DefaultAttributes {
+            ddsource: self.default_ddsource.clone(),
+            ddtags: self
+                .default_ddtags
+                .as_ref()
+                .filter(|tags| !tags.is_empty())
+                .map(|tags| tags.join(",")),
+            hostname: self.default_hostname.clone(),
+            service: self.default_service.clone(),
+        }
+    }
+
+    fn multiline(&self) -> crate::Result<Option<Multiline>> {
+        self.multiline
+            .as_ref()
+            .map(|multiline| {
+                if multiline.timeout_ms == 0 {
+                    return Err("`multiline.timeout_ms` must be greater than zero.".into());
                 }
-            });
-        http::Uri::try_from(endpoint).expect("URI not valid")
+                let start_pattern = Regex::new(&multiline.start_pattern).map_err(|error| {
+                    format!(
+                        "`multiline.start_pattern` is not a valid pattern: {}",
+                        error
+                    )
+                })?;
+                Ok(Multiline::new(
+                    start_pattern,
+                    Duration::from_millis(multiline.timeout_ms),
+                ))
+            })
+            .transpose()
+    }
+
+    /// Splits `default_api_key` into the template rendered for each event, if it interpolates
+    /// fields, and the API key of events without one.
+    fn api_keys(&self) -> crate::Result<(Option<Template>, &str)> {
+        let template = Template::try_from(self.default_api_key.expose())
+            .map_err(|error| format!("`default_api_key` is not a valid template: {}", error))?;
+        if !template.is_dynamic() {
+            return Ok((None, self.default_api_key.expose()));
+        }
+        match &self.fallback_api_key {
+            Some(fallback_api_key) => Ok((Some(template), fallback_api_key.expose())),
+            None => {
+                Err("`fallback_api_key` must be set when `default_api_key` is a template.".into())
+            }
+        }
+    }
+
+    /// Parses `request.headers`, rejecting the headers set by the sink itself.
+    fn custom_headers(&self) -> crate::Result<HeaderMap> {
+        let reserved = [
+            CONTENT_TYPE,
+            HeaderName::from_static("dd-api-key"),
+            CONTENT_ENCODING,
+            CONTENT_LENGTH,
+        ];
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.request.common.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|error| format!("{}: {}", error, name))?;
+            if reserved.contains(&name) {
+                return Err(format!(
+                    "`request.headers` can't set the `{}` header, which the sink sets itself.",
+                    name
+                )
+                .into());
+            }
+            let value = HeaderValue::from_bytes(value.as_bytes())
+                .map_err(|error| format!("{}: {}", error, value))?;
+            headers.insert(name, value);
+        }
+        Ok(headers)
+    }
+
+    /// The header carrying the idempotency key of each request, if enabled.
+    fn idempotency_header(&self) -> crate::Result<Option<HeaderName>> {
+        if !self.idempotency {
+            return Ok(None);
+        }
+        let name = HeaderName::from_bytes(self.idempotency_header.as_bytes())
+            .map_err(|error| format!("{}: {}", error, self.idempotency_header))?;
+        if self.custom_headers()?.contains_key(&name) {
+            return Err(format!(
+                "`request.headers` can't set the `{}` header, which carries the idempotency key.",
+                name
+            )
+            .into());
+        }
+        Ok(Some(name))
+    }
+
+    fn canary_trigger(&self) -> crate::Result<Option<CanaryTrigger>> {
+        if !self.canary_on_sigusr1 {
+            return Ok(None);
+        }
+        #[cfg(unix)]
+        {
+            Ok(Some(CanaryTrigger::default().on_sigusr1()?))
+        }
+        #[cfg(not(unix))]
+        {
+            Err("`canary_on_sigusr1` is only available on Unix.".into())
+        }
+    }
+
+    fn control_chars_to_strip(&self) -> Option<ControlChars> {
+        match (
+            self.strip_control_chars,
+            self.strip_control_chars_keep_whitespace,
+        ) {
+            (false, _) => None,
+            (true, true) => Some(ControlChars::ExceptWhitespace),
+            (true, false) => Some(ControlChars::All),
+        }
     }
 }
 
 impl DatadogLogsConfig {
+    pub fn builder() -> DatadogLogsConfigBuilder {
+        DatadogLogsConfigBuilder::default()
+    }
+
+    /// Converts the deprecated `region` into the equivalent `site`, so that the rest of the sink
+    /// only deals with `site` and `endpoint`.
+    ///
+    /// `region` is first checked against `site` and `endpoint`, as one of them would otherwise be
+    /// silently ignored, and its deprecation is reported.
+    pub fn normalized(&self) -> crate::Result<Self> {
+        let mut config = self.clone();
+        if let Some(region) = config.region.take() {
+            check_site_conflicts(self.endpoints(), self.site.as_ref(), Some(region))?;
+            config
+                .site
+                .get_or_insert_with(|| get_base_domain(None, Some(region)).to_owned());
+        }
+        Ok(config)
+    }
+
+    /// Checks the whole configuration of the sink, without building it.
+    ///
+    /// This is what `dry_run` relies on, so any option which can be rejected must be checked here
+    /// rather than only when it's used.
+    pub fn validate(&self) -> crate::Result<()> {
+        let (_, default_api_key) = self.api_keys()?;
+        if let Some(deterministic_id) = &self.deterministic_id {
+            if deterministic_id.fields.is_empty() {
+                return Err("`deterministic_id.fields` must contain at least one field.".into());
+            }
+        }
+        if matches!(&self.size_class_partitioning, Some(thresholds) if thresholds.is_empty()) {
+            return Err("`size_class_partitioning` must contain at least one threshold.".into());
+        }
+        if self.max_attribute_value_bytes == Some(0) {
+            return Err("`max_attribute_value_bytes` must be greater than zero.".into());
+        }
+        if let Some(mirror) = &self.mirror_to_file {
+            if mirror.max_bytes == 0 {
+                return Err("`mirror_to_file.max_bytes` must be greater than zero.".into());
+            }
+            if mirror.rotate_secs == Some(0) {
+                return Err("`mirror_to_file.rotate_secs` must be greater than zero.".into());
+            }
+        }
+        if self.buffer_metrics_interval_secs == Some(0) {
+            return Err("`buffer_metrics_interval_secs` must be greater than zero.".into());
+        }
+        if self.drop_summary_interval_secs == Some(0) {
+            return Err("`drop_summary_interval_secs` must be greater than zero.".into());
+        }
+        if self.target_retention_days == Some(0) {
+            return Err("`target_retention_days` must be greater than zero.".into());
+        }
+        if self.encode_concurrency == Some(0) {
+            return Err("`encode_concurrency` must be greater than zero.".into());
+        }
+        if self.request.deadline_secs == Some(0) {
+            return Err("`request.deadline_secs` must be greater than zero.".into());
+        }
+        if self.request.max_in_flight_bytes == Some(0) {
+            return Err("`request.max_in_flight_bytes` must be greater than zero.".into());
+        }
+        if self.strict_ack_verification && !cfg!(debug_assertions) {
+            return Err("`strict_ack_verification` is only available in debug builds.".into());
+        }
+        if self.max_bandwidth_bytes_per_sec == Some(0) {
+            return Err("`max_bandwidth_bytes_per_sec` must be greater than zero.".into());
+        }
+        if self
+            .per_partition_rate_limit
+            .values()
+            .any(|limit| *limit == 0)
+        {
+            return Err("`per_partition_rate_limit` limits must be greater than zero.".into());
+        }
+        if let Some(config) = &self.on_compression_rejected {
+            if config.consecutive_rejections == 0 {
+                return Err(
+                    "`on_compression_rejected.consecutive_rejections` must be greater than zero."
+                        .into(),
+                );
+            }
+        }
+        if matches!(&self.endpoint, Some(Endpoints::Failover(endpoints)) if endpoints.is_empty()) {
+            return Err("`endpoint` must not be an empty list.".into());
+        }
+        check_site_conflicts(self.endpoints(), self.site.as_ref(), self.region)?;
+        check_site(self.endpoints().flatten().next(), self.site.as_ref());
+        check_api_key("default_api_key", default_api_key);
+        if let Some(route_by_field) = &self.route_by_field {
+            if route_by_field.routes.is_empty() {
+                return Err("`route_by_field.routes` must contain at least one route.".into());
+            }
+        }
+        for target in self.route_targets() {
+            check_site_conflicts(
+                std::iter::once(target.endpoint.as_ref()),
+                target.site.as_ref(),
+                None,
+            )?;
+            check_site(target.endpoint.as_ref(), target.site.as_ref());
+        }
+        if let Some(dual_ship) = &self.dual_ship {
+            check_site(dual_ship.endpoint.as_ref(), dual_ship.site.as_ref());
+            check_api_key("dual_ship.api_key", dual_ship.api_key.expose());
+        }
+        let secondary_endpoint = self
+            .dual_ship
+            .as_ref()
+            .and_then(|dual_ship| dual_ship.endpoint.as_ref());
+        let route_endpoints = self
+            .route_targets()
+            .filter_map(|target| target.endpoint.as_ref());
+        for endpoint in self
+            .endpoints()
+            .flatten()
+            .chain(secondary_endpoint)
+            .chain(route_endpoints)
+        {
+            endpoint
+                .parse::<Uri>()
+                .map_err(|error| format!("`{}` is not a valid endpoint: {}", endpoint, error))?;
+        }
+        self.batch.validate()?;
+        self.custom_headers()?;
+        self.idempotency_header()?;
+        self.multiline()?;
+        Ok(())
+    }
+
     pub fn build_processor(
         &self,
         client: HttpClient,
         cx: SinkContext,
     ) -> crate::Result<VectorSink> {
-        let default_api_key: Arc<str> = Arc::from(self.default_api_key.clone().as_str());
-        let request_limits = self.request.unwrap_with(&Default::default());
-
-        // We forcefully cap the provided batch configuration to the size/log line limits imposed by
-        // the Datadog Logs API, but we still allow them to be lowered if need be.
-        let batch = self
-            .batch
-            .validate()?
-            .limit_max_bytes(BATCH_GOAL_BYTES)?
-            .limit_max_events(BATCH_MAX_EVENTS)?
-            .into_batcher_settings()?;
-
-        let service = ServiceBuilder::new()
-            .settings(request_limits, LogApiRetry)
-            .service(LogApiService::new(
-                client,
-                self.get_uri(),
-                cx.globals.enterprise,
-            ));
+        self.validate()?;
+        let (_, default_api_key) = self.api_keys()?;
+        let default_api_key: Arc<str> = Arc::from(default_api_key);
+        let request_limits = self.request.common.tower.unwrap_with(&Default::default());
+        let deadline = self.request.deadline_secs.map(Duration::from_secs);
+        let headers = self.custom_headers()?;
+        let idempotency_header = self.idempotency_header()?;
+
+        let batch = self.batch_settings()?;
+
+        // The secondary site gets its own service, so that its retries and concurrency are
+        // independent from the primary site.
+        let secondary = self.dual_ship.as_ref().map(|dual_ship| {
+            let uri = logs_uri(dual_ship.endpoint.as_ref(), dual_ship.site.as_ref());
+            let service = ServiceBuilder::new()
+                .settings(request_limits.clone(), LogApiRetry)
+                .service(
+                    LogApiService::new(client.clone(), uri, cx.globals.enterprise)
+                        .with_content_type(self.encoding.codec.content_type())
+                        .with_headers(headers.clone())
+                        .with_idempotency_header(idempotency_header.clone())
+                        .with_omit_content_length(self.omit_content_length),
+                );
+            SecondarySite::spawn(service, Arc::from(dual_ship.api_key.expose()))
+        });
+
+        let enterprise = cx.globals.enterprise;
+        let compression_fallback = self
+            .on_compression_rejected
+            .map(|config| Arc::new(CompressionFallback::new(config.consecutive_rejections)));
+        let partition_rate_limits = (!self.per_partition_rate_limit.is_empty())
+            .then(|| PartitionRateLimits::new(&self.per_partition_rate_limit));
+        let stall_tracker = self
+            .partition_stall_threshold_secs
+            .map(|secs| Arc::new(StallTracker::new(Duration::from_secs(secs))));
+        let ack_verifier = self
+            .strict_ack_verification
+            .then(|| Arc::new(AckVerifier::default()));
+        let self_metrics = self
+            .self_metrics
+            .as_ref()
+            .map(SelfMetrics::spawn)
+            .transpose()?;
+        let service = |request_limits: TowerRequestSettings| {
+            let service = ServiceBuilder::new()
+                .settings(request_limits, LogApiRetry)
+                .service(
+                    LogApiService::with_failover(client.clone(), self.get_uris(), enterprise)
+                        .with_content_type(self.encoding.codec.content_type())
+                        .with_compression_fallback(compression_fallback.clone())
+                        .with_stall_tracker(stall_tracker.clone())
+                        .with_partition_rate_limits(partition_rate_limits.clone())
+                        .with_ack_verifier(ack_verifier.clone())
+                        .with_self_metrics(self_metrics.clone())
+                        .with_headers(headers.clone())
+                        .with_idempotency_header(idempotency_header.clone())
+                        .with_omit_content_length(self.omit_content_length),
+                );
+            // The deadline spans all the retries of a request, so it wraps the retrying service.
+            DeadlineService::new(service, deadline)
+        };
+
+        match &self.time_bucketing {
+            None => self.build_sink(
+                service(request_limits),
+                cx,
+                default_api_key,
+                batch,
+                secondary,
+                ack_verifier,
+            ),
+            Some(time_bucketing) => {
+                // Each bucket gets its own service, so that backfill traffic has its own
+                // concurrency and rate limits.
+                let backfill_limits = time_bucketing
+                    .backfill_request
+                    .unwrap_with(&Default::default());
+                let service =
+                    TimeBucketRouter::new(service(request_limits), service(backfill_limits));
+                self.build_sink(service, cx, default_api_key, batch, secondary, ack_verifier)
+            }
+        }
+    }
+
+    fn build_sink<S>(
+        &self,
+        service: S,
+        cx: SinkContext,
+        default_api_key: Arc<str>,
+        batch: BatcherSettings,
+        secondary: Option<SecondarySite>,
+        ack_verifier: Option<Arc<AckVerifier>>,
+    ) -> crate::Result<VectorSink>
+    where
+        S: Service<LogApiRequest> + Send + 'static,
+        S::Future: Send + 'static,
+        S::Response: DriverResponse + Send + 'static,
+        S::Error: Debug + Into<crate::Error> + Send,
+    {
+        let metric_to_log = self.metrics.as_ref().map(|metrics| {
+            MetricToLog::new(
+                metrics.host_tag.clone(),
+                metrics.timezone.unwrap_or(cx.globals.timezone),
+            )
+        });
         let sink = LogSinkBuilder::new(service, cx, default_api_key, batch)
-            .encoding(self.encoding.clone())
-            .compression(self.compression.unwrap_or_default())
+            .api_key_template(self.api_keys()?.0)
+            .encoding(self.fixed_encoding())
+            .codec(self.encoding.codec)
+            .cef(self.cef.clone())
+            .deterministic_id(self.deterministic_id.clone())
+            .correlation_id_from_metadata(self.correlation_id_from_metadata)
+            .strip_control_chars(self.control_chars_to_strip())
+            .implausible_timestamp_threshold_secs(self.implausible_timestamp_threshold_secs)
+            .max_attributes_per_event(self.max_attributes_per_event)
+            .dotted_key_policy(self.dotted_key_policy)
+            .on_oversized(self.on_oversized)
+            .max_attribute_value_bytes(self.max_attribute_value_bytes)
+            .host_template(self.host_template.clone())
+            .pre_send_hook(self.pre_send_hook.clone())
+            .reserved_attributes(self.reserved_attributes.clone())
+            .default_attributes(self.default_attributes())
+            .invalid_timestamp(self.invalid_timestamp)
+            .invalid_utf8(self.invalid_utf8)
+            .fill_missing_message(self.fill_missing_message)
+            .fill_missing_timestamp(self.fill_missing_timestamp)
+            .ddtags_field(self.ddtags_field.clone())
+            .flatten_attributes(self.flatten_attributes)
+            .drop_summary_interval(self.drop_summary_interval_secs.map(Duration::from_secs))
+            .target_retention_days(self.target_retention_days)
+            .compression(self.compression())
+            .smart_compression(matches!(
+                self.codec_compression(),
+                Some(DatadogLogsCompression::Smart)
+            ))
+            .precompressed_field(self.precompressed_field.clone())
+            .encode_concurrency(self.encode_concurrency.and_then(NonZeroUsize::new))
+            .max_in_flight_bytes(self.request.max_in_flight_bytes)
+            .add_codec_tag(self.add_codec_tag)
+            .add_send_timestamp(self.add_send_timestamp)
+            .drain_order(self.drain_order)
+            .size_classes(self.size_class_partitioning.clone())
+            .max_bandwidth_bytes_per_sec(self.max_bandwidth_bytes_per_sec)
+            .secondary(secondary)
+            .mirror(self.mirror_to_file.as_ref().map(FileMirror::spawn))
+            .multiline(self.multiline()?)
+            .ack_verifier(ack_verifier)
+            .metric_to_log(metric_to_log)
+            .startup_canary(self.startup_canary)
+            .canary_trigger(self.canary_trigger()?)
+            .flush_on_change_field(self.flush_on_change_field.clone())
+            .flush_on_sequence_gap(self.flush_on_sequence_gap.clone())
+            .partition_key_fields(self.partition_key_fields.clone())
+            .field_routes(self.field_routes())
+            .buffer_metrics_interval(self.buffer_metrics_interval_secs.map(Duration::from_secs))
+            .hot_window(
+                self.time_bucketing
+                    .as_ref()
+                    .map(|time_bucketing| Duration::from_secs(time_bucketing.hot_window_secs)),
+            )
             .build();
 
         Ok(VectorSink::Stream(Box::new(sink)))
     }
 
+    /// The encoding rules of the sink, which only differ by codec from those of the JSON encoding.
+    fn fixed_encoding(&self) -> EncodingConfigFixed<DatadogLogsJsonEncoding> {
+        EncodingConfigFixed {
+            codec: Default::default(),
+            schema: self.encoding.schema.clone(),
+            only_fields: self.encoding.only_fields.clone(),
+            except_fields: self.encoding.except_fields.clone(),
+            timestamp_format: self.encoding.timestamp_format,
+        }
+    }
+
     pub fn build_healthcheck(&self, client: HttpClient) -> crate::Result<Healthcheck> {
-        let validate_endpoint =
-            get_api_validate_endpoint(self.endpoint.as_ref(), self.site.as_ref(), self.region)?;
-        Ok(healthcheck(client, validate_endpoint, self.default_api_key.clone()).boxed())
+        let retries = self.healthcheck_boot_retries;
+        let backoff = Duration::from_secs(self.healthcheck_boot_backoff_secs);
+        let api_key = self.api_keys()?.1.to_owned();
+        let headers = self.custom_headers()?;
+        let healthy = self
+            .healthcheck_healthy_statuses
+            .iter()
+            .map(|status| {
+                StatusCode::from_u16(*status).map_err(|_| {
+                    format!(
+                        "`healthcheck_healthy_statuses` contains an invalid status: {}.",
+                        status
+                    )
+                })
+            })
+            .collect::<Result<Arc<[_]>, _>>()?;
+        if self.healthcheck_use_real_body {
+            let uris = self.get_uris();
+            return Ok(retry_healthcheck(
+                move || {
+                    let (client, api_key, headers, healthy) = (
+                        client.clone(),
+                        api_key.clone(),
+                        headers.clone(),
+                        Arc::clone(&healthy),
+                    );
+                    any_healthy(uris.clone(), move |uri| {
+                        real_body_healthcheck(
+                            client.clone(),
+                            uri,
+                            api_key.clone(),
+                            headers.clone(),
+                            Arc::clone(&healthy),
+                        )
+                    })
+                },
+                retries,
+                backoff,
+            )
+            .map(|result| result.map_err(Into::into))
+            .boxed());
+        }
+        let validate_endpoints = self
+            .endpoints()
+            .map(|endpoint| get_api_validate_endpoint(endpoint, self.site.as_ref(), None))
+            .collect::<crate::Result<Vec<_>>>()?;
+        Ok(retry_healthcheck(
+            move || {
+                let (client, api_key, headers, healthy) = (
+                    client.clone(),
+                    api_key.clone(),
+                    headers.clone(),
+                    Arc::clone(&healthy),
+                );
+                any_healthy(validate_endpoints.clone(), move |uri| {
+                    healthcheck(
+                        client.clone(),
+                        uri,
+                        api_key.clone(),
+                        headers.clone(),
+                        Arc::clone(&healthy),
+                    )
+                })
+            },
+            retries,
+            backoff,
+        )
+        .map(|result| result.map_err(Into::into))
+        .boxed())
     }
 
     pub fn create_client(&self, proxy: &ProxyConfig) -> crate::Result<HttpClient> {
-        let tls_settings = MaybeTlsSettings::from_config(
-            &Some(self.tls.clone().unwrap_or_else(TlsConfig::enabled)),
-            false,
-        )?;
-        Ok(HttpClient::new(tls_settings, proxy)?)
+        if self.max_concurrent_handshakes == Some(0) {
+            return Err("`max_concurrent_handshakes` must be greater than zero.".into());
+        }
+        // The intake is only reachable over TLS, so a `tls` section which doesn't set `enabled`
+        // still applies, rather than silently dropping its client certificate.
+        let tls = match self.tls.clone() {
+            Some(tls) => TlsConfig {
+                enabled: Some(tls.enabled.unwrap_or(true)),
+                ..tls
+            },
+            None => TlsConfig::enabled(),
+        };
+        let tls_settings = MaybeTlsSettings::from_config(&Some(tls), false)?;
+        Ok(HttpClient::new_with_handshake_limit(
+            tls_settings,
+            proxy,
+            self.max_concurrent_handshakes,
+        )?)
+    }
+}
+
+/// Runs `healthcheck`, retrying it up to `retries` times with exponential backoff starting at
+/// `backoff`, so that Datadog being briefly unavailable at boot doesn't fail the healthcheck.
+///
+/// A rejected API key or request fails the healthcheck right away, as retrying won't change it.
+async fn retry_healthcheck<F, Fut>(
+    mut healthcheck: F,
+    retries: usize,
+    backoff: Duration,
+) -> Result<(), DatadogLogsError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), DatadogLogsError>>,
+{
+    let mut delay = backoff;
+    for attempt in 1..=retries {
+        match healthcheck().await {
+            Ok(()) => return Ok(()),
+            Err(
+                error @ (DatadogLogsError::Unauthorized { .. }
+                | DatadogLogsError::Malformed
+                | DatadogLogsError::PayloadTooLarge),
+            ) => return Err(error),
+            Err(error) => {
+                warn!(
+                    message = "Healthcheck failed, retrying.",
+                    %error,
+                    attempt,
+                    delay_secs = delay.as_secs()
+                );
+                sleep(delay).await;
+                delay *= 2;
+            }
+        }
+    }
+    healthcheck().await
+}
+
+/// Runs `healthcheck` against each of `uris` in turn, passing as soon as one of them is healthy, as
+/// requests fail over to the others.
+async fn any_healthy<F, Fut>(uris: Vec<Uri>, mut healthcheck: F) -> Result<(), DatadogLogsError>
+where
+    F: FnMut(Uri) -> Fut,
+    Fut: Future<Output = Result<(), DatadogLogsError>>,
+{
+    let mut result = Ok(());
+    for uri in uris {
+        result = healthcheck(uri).await;
+        if result.is_ok() {
+            break;
+        }
+    }
+    result
+}
+
+/// Checks the validity of the API key against the validation endpoint.
+async fn healthcheck(
+    client: HttpClient,
+    validate_endpoint: Uri,
+    api_key: String,
+    headers: HeaderMap,
+    healthy: Arc<[StatusCode]>,
+) -> Result<(), DatadogLogsError> {
+    let request = Request::get(validate_endpoint)
+        .header("DD-API-KEY", api_key)
+        .body(Body::empty())
+        .map_err(|_| DatadogLogsError::Malformed)?;
+
+    send_healthcheck(client, request, headers, &healthy).await
+}
+
+/// Checks the health of the intake by sending it a single synthetic log.
+///
+/// Some firewalls between us and Datadog reject requests with an empty body, which the usual
+/// healthcheck against the validation endpoint sends.
+async fn real_body_healthcheck(
+    client: HttpClient,
+    uri: Uri,
+    api_key: String,
+    headers: HeaderMap,
+    healthy: Arc<[StatusCode]>,
+) -> Result<(), DatadogLogsError> {
+    let body = serde_json::json!([{
+        "message": "Synthetic log sent by the Vector healthcheck.",
+        "ddsource": "vector",
+        "service": "vector-healthcheck",
+        "synthetic": true,
+    }]);
+    let request = Request::post(uri)
+        .header(CONTENT_TYPE, "application/json")
+        .header("DD-API-KEY", api_key)
+        .body(Body::from(body.to_string()))
+        .map_err(|_| DatadogLogsError::Malformed)?;
+
+    send_healthcheck(client, request, headers, &healthy).await
+}
+
+/// Sends a healthcheck request with the custom headers of the sink, so that it goes through the
+/// same gateway policies as the requests of events.
+///
+/// The healthcheck passes on a `2xx` response, or one with any of the `healthy` statuses.
+async fn send_healthcheck(
+    client: HttpClient,
+    mut request: Request<Body>,
+    headers: HeaderMap,
+    healthy: &[StatusCode],
+) -> Result<(), DatadogLogsError> {
+    request.headers_mut().extend(headers);
+    let response = client
+        .send(request)
+        .await
+        .map_err(|error| DatadogLogsError::HttpError { error })?;
+
+    if response.status().is_success() || healthy.contains(&response.status()) {
+        Ok(())
+    } else {
+        Err(DatadogLogsError::from_response(&response))
     }
 }
 
@@ -156,27 +1816,273 @@ impl DatadogLogsConfig {
 #[typetag::serde(name = "datadog_logs")]
 impl SinkConfig for DatadogLogsConfig {
     async fn build(&self, cx: SinkContext) -> crate::Result<(VectorSink, Healthcheck)> {
-        let client = self.create_client(&cx.proxy)?;
-        let healthcheck = self.build_healthcheck(client.clone())?;
-        let sink = self.build_processor(client, cx)?;
+        let config = self.normalized()?;
+        let client = config.create_client(&cx.proxy)?;
+        if config.dry_run {
+            // Only the configuration is checked, nothing is sent to Datadog.
+            config.validate()?;
+            let sink = DryRunSink::new(cx.acker);
+            return Ok((VectorSink::Stream(Box::new(sink)), future::ok(()).boxed()));
+        }
+        // A disabled healthcheck doesn't contact Datadog at all.
+        let healthcheck = if cx.healthcheck.enabled {
+            config.build_healthcheck(client.clone())?
+        } else {
+            future::ok(()).boxed()
+        };
+        let sink = config.build_processor(client, cx)?;
         Ok((sink, healthcheck))
     }
 
     fn input_type(&self) -> DataType {
-        DataType::Log
+        if self.metrics.is_some() {
+            DataType::Any
+        } else {
+            DataType::Log
+        }
     }
 
     fn sink_type(&self) -> &'static str {
         "datadog_logs"
     }
+
+    fn resources(&self) -> Vec<Resource> {
+        self.self_metrics
+            .iter()
+            .map(|self_metrics| Resource::tcp(self_metrics.address))
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::sinks::datadog::logs::DatadogLogsConfig;
+    use indoc::indoc;
+
+    use super::{RequestCompression, BATCH_GOAL_BYTES, BATCH_MAX_EVENTS};
+    use crate::{
+        config::{Resource, SinkConfig},
+        sinks::{
+            datadog::{logs::DatadogLogsConfig, Region},
+            util::Compression,
+        },
+    };
 
     #[test]
     fn generate_config() {
         crate::test_util::test_generate_config::<DatadogLogsConfig>();
     }
+
+    #[test]
+    fn batch_settings_capped_to_api_limits() {
+        let config: DatadogLogsConfig = toml::from_str(indoc! {r#"
+            default_api_key = "atoken"
+            batch.max_bytes = 10_000_000
+            batch.max_events = 5000
+        "#})
+        .unwrap();
+        let batch = config.batch_settings().unwrap();
+        assert_eq!(batch.size_limit, BATCH_GOAL_BYTES);
+        assert_eq!(batch.item_limit, BATCH_MAX_EVENTS);
+
+        let config: DatadogLogsConfig = toml::from_str(indoc! {r#"
+            default_api_key = "atoken"
+            batch.max_bytes = 1000
+            batch.max_events = 10
+        "#})
+        .unwrap();
+        let batch = config.batch_settings().unwrap();
+        assert_eq!(batch.size_limit, 1000);
+        assert_eq!(batch.item_limit, 10);
+    }
+
+    #[test]
+    fn self_metrics_address_is_a_resource() {
+        let config: DatadogLogsConfig = toml::from_str(indoc! {r#"
+            default_api_key = "atoken"
+        "#})
+        .unwrap();
+        assert!(config.resources().is_empty());
+
+        let config: DatadogLogsConfig = toml::from_str(indoc! {r#"
+            default_api_key = "atoken"
+            self_metrics.address = "0.0.0.0:9598"
+        "#})
+        .unwrap();
+        assert_eq!(
+            config.resources(),
+            vec![Resource::tcp("0.0.0.0:9598".parse().unwrap())]
+        );
+    }
+
+    #[test]
+    fn debug_redacts_api_keys() {
+        let config: DatadogLogsConfig = toml::from_str(indoc! {r#"
+            default_api_key = "{{ tenant_key }}"
+            fallback_api_key = "fallbacksecret"
+            dual_ship.api_key = "secondarysecret"
+            per_partition_rate_limit.partitionsecret = 10
+        "#})
+        .unwrap();
+        let debug = format!("{:?}", config);
+        for secret in [
+            "tenant_key",
+            "fallbacksecret",
+            "secondarysecret",
+            "partitionsecret",
+        ] {
+            assert!(!debug.contains(secret), "{} leaked into {}", secret, debug);
+        }
+        assert_eq!(config.api_keys().unwrap().1, "fallbacksecret");
+
+        let builder = DatadogLogsConfig::builder().api_key("buildersecret");
+        assert!(!format!("{:?}", builder).contains("buildersecret"));
+    }
+
+    #[test]
+    fn region_normalizes_to_site() {
+        for (region, site) in [("eu", "datadoghq.eu"), ("us", "datadoghq.com")] {
+            let config: DatadogLogsConfig = toml::from_str(&format!(
+                "default_api_key = \"atoken\"\nregion = \"{}\"",
+                region
+            ))
+            .unwrap();
+            let config = config.normalized().unwrap();
+            assert!(config.region.is_none());
+            assert_eq!(config.site.as_deref(), Some(site));
+            let uris = config
+                .get_uris()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                uris,
+                [format!("https://http-intake.logs.{}/api/v2/logs", site)]
+            );
+        }
+
+        // An agreeing `site` is kept as is.
+        let config: DatadogLogsConfig = toml::from_str(indoc! {r#"
+            default_api_key = "atoken"
+            site = "datadoghq.eu"
+            region = "eu"
+        "#})
+        .unwrap();
+        let config = config.normalized().unwrap();
+        assert!(config.region.is_none());
+        assert_eq!(config.site.as_deref(), Some("datadoghq.eu"));
+    }
+
+    #[test]
+    fn compression_by_codec() {
+        let compression = |codec: &str, compression: &str| {
+            let config: DatadogLogsConfig = toml::from_str(&format!(
+                "default_api_key = \"atoken\"\nencoding.codec = \"{}\"\n{}",
+                codec, compression
+            ))
+            .unwrap();
+            config.compression()
+        };
+
+        let by_codec = indoc! {r#"
+            compression.json = { algorithm = "gzip", level = 9 }
+            compression.ndjson = "zstd(19)"
+        "#};
+        assert_eq!(
+            compression("json", by_codec),
+            RequestCompression::Gzip(flate2::Compression::new(9))
+        );
+        assert_eq!(
+            compression("ndjson", by_codec),
+            RequestCompression::Zstd(19)
+        );
+        assert_eq!(compression("binary", by_codec), RequestCompression::None);
+
+        let all = r#"compression = { algorithm = "gzip", level = 3 }"#;
+        for codec in ["json", "ndjson", "binary"] {
+            assert_eq!(
+                compression(codec, all),
+                RequestCompression::Gzip(flate2::Compression::new(3))
+            );
+        }
+    }
+
+    #[test]
+    fn compression_level_out_of_range() {
+        for (compression, expected) in [
+            (
+                r#""gzip(12)""#,
+                "invalid value: 12, expected 0, 1, 2, 3, 4, 5, 6, 7, 8 or 9",
+            ),
+            (
+                r#"{ algorithm = "gzip", level = 10 }"#,
+                "invalid value: 10, expected 0, 1, 2, 3, 4, 5, 6, 7, 8 or 9",
+            ),
+            (
+                r#"{ json = "zstd(23)" }"#,
+                "expected an integer from 1 to 22",
+            ),
+            (
+                r#"{ algorithm = "zstd", level = 0 }"#,
+                "expected an integer from 1 to 22",
+            ),
+            (
+                r#"{ algorithm = "brotli", level = 12 }"#,
+                "expected an integer from 0 to 11",
+            ),
+        ] {
+            let error = toml::from_str::<DatadogLogsConfig>(&format!(
+                "default_api_key = \"atoken\"\ncompression = {}",
+                compression
+            ))
+            .unwrap_err();
+            assert!(
+                error.to_string().contains(expected),
+                "{}: {}",
+                compression,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn builder_matches_parsed_config() {
+        // The configuration of the `smoke` tests, with their local endpoint.
+        let parsed: DatadogLogsConfig = toml::from_str(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            endpoint = "http://127.0.0.1:9000"
+        "#})
+        .unwrap();
+        let built = DatadogLogsConfig::builder()
+            .api_key("atoken")
+            .compression(Compression::None)
+            .endpoint("http://127.0.0.1:9000")
+            .build()
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&built).unwrap(),
+            serde_json::to_value(&parsed).unwrap()
+        );
+
+        let error = DatadogLogsConfig::builder()
+            .api_key("atoken")
+            .site("datadoghq.eu")
+            .endpoint("https://http-intake.logs.datadoghq.com")
+            .build()
+            .unwrap_err();
+        assert!(error.to_string().contains("is not on `site`"), "{}", error);
+
+        let error = DatadogLogsConfig::builder()
+            .api_key("atoken")
+            .site("datadoghq.com")
+            .region(Region::Eu)
+            .build()
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("different Datadog sites"),
+            "{}",
+            error
+        );
+        assert!(DatadogLogsConfig::builder().build().is_err());
+    }
 }
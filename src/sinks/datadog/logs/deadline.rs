@@ -0,0 +1,64 @@
+use std::{
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{future::BoxFuture, FutureExt};
+use tower::Service;
+
+use super::service::{LogApiRequest, LogApiResponse};
+use crate::internal_events::DatadogLogsRequestDeadlineExceeded;
+
+/// Caps the total time spent on each request, across all of its retries, so that a batch which
+/// keeps failing doesn't hold back the newer batches behind it.
+///
+/// A request still running at its deadline is abandoned, and its events are finalized as errored.
+pub struct DeadlineService<S> {
+    inner: S,
+    deadline: Option<Duration>,
+}
+
+impl<S> DeadlineService<S> {
+    pub const fn new(inner: S, deadline: Option<Duration>) -> Self {
+        Self { inner, deadline }
+    }
+}
+
+impl<S> Service<LogApiRequest> for DeadlineService<S>
+where
+    S: Service<LogApiRequest, Response = LogApiResponse>,
+    S::Error: Into<crate::Error>,
+    S::Future: Send + 'static,
+{
+    type Response = LogApiResponse;
+    type Error = crate::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: LogApiRequest) -> Self::Future {
+        let count = request.batch_size;
+        let events_byte_size = request.events_byte_size;
+        let deadline = self.deadline;
+        let response = self.inner.call(request);
+        async move {
+            let deadline = match deadline {
+                Some(deadline) => deadline,
+                None => return response.await.map_err(Into::into),
+            };
+            match tokio::time::timeout(deadline, response).await {
+                Ok(result) => result.map_err(Into::into),
+                Err(_) => {
+                    emit!(&DatadogLogsRequestDeadlineExceeded {
+                        deadline,
+                        dropped_events: count,
+                    });
+                    Ok(LogApiResponse::errored(count, events_byte_size))
+                }
+            }
+        }
+        .boxed()
+    }
+}
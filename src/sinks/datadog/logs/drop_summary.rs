@@ -0,0 +1,63 @@
+use std::{
+    collections::BTreeMap,
+    mem,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use tokio::time::{interval_at, Instant};
+
+use crate::internal_events::DatadogLogDropSummary;
+
+/// Counts the events dropped by the sink by reason, emitting the counts once per interval rather
+/// than only as each event is dropped.
+#[derive(Debug, Default)]
+pub struct DropSummary {
+    counts: Mutex<BTreeMap<&'static str, usize>>,
+}
+
+impl DropSummary {
+    /// Creates a summary whose counts are emitted every `interval`, for as long as it is in use.
+    pub fn spawn(interval: Duration) -> Arc<Self> {
+        let summary = Arc::new(Self::default());
+        tokio::spawn(report(Arc::downgrade(&summary), interval));
+        summary
+    }
+
+    pub fn record(&self, reason: &'static str) {
+        let mut counts = self.counts.lock().expect("drop summary mutex poisoned");
+        *counts.entry(reason).or_default() += 1;
+    }
+
+    /// Emits the counts since the last summary, if any events were dropped.
+    fn emit(&self) {
+        let counts = mem::take(&mut *self.counts.lock().expect("drop summary mutex poisoned"));
+        if !counts.is_empty() {
+            emit!(&DatadogLogDropSummary { counts });
+        }
+    }
+}
+
+impl Drop for DropSummary {
+    fn drop(&mut self) {
+        // The drops since the last interval are reported when the sink shuts down.
+        self.emit();
+    }
+}
+
+impl PartialEq for DropSummary {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+async fn report(summary: Weak<DropSummary>, interval: Duration) {
+    let mut interval = interval_at(Instant::now() + interval, interval);
+    loop {
+        interval.tick().await;
+        match summary.upgrade() {
+            Some(summary) => summary.emit(),
+            None => break,
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use async_trait::async_trait;
+use futures::{stream::BoxStream, StreamExt};
+use vector_core::buffers::Acker;
+
+use crate::{event::Event, internal_events::DatadogLogsDryRunDiscarded, sinks::util::StreamSink};
+
+/// Stands in for the sink when `dry_run` is enabled, discarding events instead of sending them to
+/// Datadog, so that a configuration can be checked without shipping any data.
+pub struct DryRunSink {
+    acker: Acker,
+}
+
+impl DryRunSink {
+    pub const fn new(acker: Acker) -> Self {
+        Self { acker }
+    }
+}
+
+#[async_trait]
+impl StreamSink for DryRunSink {
+    async fn run(self: Box<Self>, input: BoxStream<'_, Event>) -> Result<(), ()> {
+        let mut chunks = input.ready_chunks(1024);
+        while let Some(events) = chunks.next().await {
+            emit!(&DatadogLogsDryRunDiscarded {
+                count: events.len()
+            });
+            self.acker.ack(events.len());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+use std::{fmt::Debug, sync::Arc};
+
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tower::{Service, ServiceExt};
+use vector_core::{
+    event::{EventFinalizers, EventStatus},
+    stream::DriverResponse,
+};
+
+use super::service::{LogApiRequest, LogApiResponse};
+
+/// The number of requests which may be waiting to be sent to the secondary site before further
+/// copies are dropped.
+const QUEUE_CAPACITY: usize = 64;
+
+/// Ships a copy of every request to a secondary Datadog site.
+///
+/// The copies are sent by a background task through their own service, with its own retries and
+/// concurrency, so that an outage of either site doesn't hold back the other. Delivery to the
+/// secondary site is best effort: events are acknowledged based on the primary site alone, and
+/// copies are dropped if the secondary site falls too far behind.
+#[derive(Clone, Debug)]
+pub struct SecondarySite {
+    api_key: Arc<str>,
+    tx: mpsc::Sender<LogApiRequest>,
+}
+
+impl SecondarySite {
+    /// Spawns the task sending requests to the secondary site through `service`, authenticating
+    /// them all with `api_key`.
+    pub fn spawn<S>(service: S, api_key: Arc<str>) -> Self
+    where
+        S: Service<LogApiRequest, Response = LogApiResponse> + Send + 'static,
+        S::Future: Send + 'static,
+        S::Error: Debug + Send,
+    {
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+        tokio::spawn(run(service, rx));
+        Self { api_key, tx }
+    }
+
+    /// Queues a copy of `request` for the secondary site.
+    pub fn ship(&self, request: &LogApiRequest) {
+        let request = LogApiRequest {
+            api_key: Arc::clone(&self.api_key),
+            // The secondary site isn't routed by field.
+            uri: None,
+            // Finalization only reflects the primary site.
+            finalizers: EventFinalizers::default(),
+            ack_id: None,
+            ..request.clone()
+        };
+        match self.tx.try_send(request) {
+            Ok(()) => {}
+            Err(TrySendError::Full(request)) => {
+                warn!(
+                    message = "Secondary site is falling behind, dropping request.",
+                    count = request.batch_size,
+                    internal_log_rate_secs = 10
+                );
+            }
+            Err(TrySendError::Closed(_)) => {
+                error!(
+                    message = "Secondary site is no longer running, dropping request.",
+                    internal_log_rate_secs = 10
+                );
+            }
+        }
+    }
+}
+
+async fn run<S>(mut service: S, mut rx: mpsc::Receiver<LogApiRequest>)
+where
+    S: Service<LogApiRequest, Response = LogApiResponse> + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Debug + Send,
+{
+    while let Some(request) = rx.recv().await {
+        let service = match service.ready().await {
+            Ok(service) => service,
+            Err(error) => {
+                error!(message = "Secondary site service failed.", ?error);
+                return;
+            }
+        };
+
+        let count = request.batch_size;
+        let response = service.call(request);
+        tokio::spawn(async move {
+            match response.await {
+                Ok(response) if response.event_status() == EventStatus::Delivered => {}
+                Ok(_) => error!(
+                    message = "Secondary site rejected request.",
+                    count,
+                    internal_log_rate_secs = 10
+                ),
+                Err(error) => error!(
+                    message = "Failed to send request to secondary site.",
+                    ?error,
+                    count,
+                    internal_log_rate_secs = 10
+                ),
+            }
+        });
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    convert::TryFrom,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures::{future::BoxFuture, FutureExt};
+use tokio::sync::Semaphore;
+use tower::Service;
+
+use super::service::LogApiRequest;
+
+/// Limits the total size of the request bodies in flight, so that a burst of large batches
+/// doesn't hold many of them in memory at once.
+///
+/// The budget is taken before a request is handed to the service, and given back once its response
+/// is received, retries included. As requests are acquired sequentially, this applies backpressure
+/// to the rest of the sink whenever the budget is exhausted. A body larger than the whole budget
+/// is sent on its own once every other request has completed.
+#[derive(Debug)]
+pub struct InFlightBytes {
+    limit: u32,
+    budget: Semaphore,
+}
+
+impl InFlightBytes {
+    pub fn new(max_bytes: u64) -> Self {
+        let limit = u32::try_from(max_bytes).unwrap_or(u32::MAX);
+        Self {
+            limit,
+            budget: Semaphore::new(limit as usize),
+        }
+    }
+
+    /// Waits until a body of `bytes` fits in the budget, and takes it from the budget.
+    pub async fn acquire(&self, bytes: usize) {
+        self.budget
+            .acquire_many(self.permits(bytes))
+            .await
+            .expect("in-flight budget is never closed")
+            .forget();
+    }
+
+    /// Gives back the budget taken for a body of `bytes`.
+    pub fn release(&self, bytes: usize) {
+        self.budget.add_permits(self.permits(bytes) as usize);
+    }
+
+    fn permits(&self, bytes: usize) -> u32 {
+        u32::try_from(bytes).map_or(self.limit, |bytes| bytes.min(self.limit))
+    }
+}
+
+/// Gives back the budget of each request to the in-flight limit once it completes.
+pub struct InFlightBytesService<S> {
+    inner: S,
+    in_flight: Option<Arc<InFlightBytes>>,
+}
+
+impl<S> InFlightBytesService<S> {
+    pub const fn new(inner: S, in_flight: Option<Arc<InFlightBytes>>) -> Self {
+        Self { inner, in_flight }
+    }
+}
+
+impl<S> Service<LogApiRequest> for InFlightBytesService<S>
+where
+    S: Service<LogApiRequest>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: LogApiRequest) -> Self::Future {
+        let bytes = request.body.len();
+        let in_flight = self.in_flight.clone();
+        let response = self.inner.call(request);
+        async move {
+            let result = response.await;
+            if let Some(in_flight) = in_flight {
+                in_flight.release(bytes);
+            }
+            result
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,135 @@
+use std::{io, path::PathBuf, time::Duration};
+
+use bytes::Bytes;
+use chrono::Utc;
+use tokio::{
+    fs::{self, File, OpenOptions},
+    io::AsyncWriteExt,
+    sync::mpsc::{self, error::TrySendError},
+    time::Instant,
+};
+
+use super::{compression::RequestCompression, config::FileMirrorConfig, service::LogApiRequest};
+use crate::internal_events::DatadogLogsMirrorWriteFailed;
+
+/// The number of request bodies which may be waiting to be written before further bodies are
+/// dropped.
+const QUEUE_CAPACITY: usize = 64;
+
+/// Appends the uncompressed body of every request to a local file, rotated by size and age, to
+/// retain a copy of everything sent to Datadog.
+///
+/// The bodies are written by a background task, so that a slow or failing disk doesn't hold back
+/// delivery. Failed writes are reported, and bodies are dropped if the writes fall too far behind.
+#[derive(Clone, Debug)]
+pub struct FileMirror {
+    tx: mpsc::Sender<(RequestCompression, Bytes)>,
+}
+
+impl FileMirror {
+    /// Spawns the task writing request bodies to the configured file.
+    pub fn spawn(config: &FileMirrorConfig) -> Self {
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+        let file = MirrorFile {
+            path: config.path.clone(),
+            max_bytes: config.max_bytes,
+            max_age: config.rotate_secs.map(Duration::from_secs),
+            current: None,
+        };
+        tokio::spawn(run(file, rx));
+        Self { tx }
+    }
+
+    /// Queues the body of `request` to be written.
+    pub fn mirror(&self, request: &LogApiRequest) {
+        match self
+            .tx
+            .try_send((request.compression, request.body.clone()))
+        {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                warn!(
+                    message = "Mirror file is falling behind, dropping request body.",
+                    count = request.batch_size,
+                    internal_log_rate_secs = 10
+                );
+            }
+            Err(TrySendError::Closed(_)) => {
+                error!(
+                    message = "Mirror file is no longer being written, dropping request body.",
+                    internal_log_rate_secs = 10
+                );
+            }
+        }
+    }
+}
+
+/// The mirror file, along with the handle it is currently written through, the size of the file and
+/// when the handle was opened.
+struct MirrorFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_age: Option<Duration>,
+    current: Option<(File, u64, Instant)>,
+}
+
+impl MirrorFile {
+    /// Appends `body` followed by a newline, rotating the file first if it is full or too old.
+    async fn write(&mut self, body: &[u8]) -> io::Result<()> {
+        let len = body.len() as u64 + 1;
+        let rotate = self.current.as_ref().map_or(false, |(_, written, opened)| {
+            // A body larger than `max_bytes` still gets written, on its own.
+            (*written > 0 && written + len > self.max_bytes)
+                || self
+                    .max_age
+                    .map_or(false, |max_age| opened.elapsed() >= max_age)
+        });
+        if rotate {
+            self.rotate().await?;
+        }
+
+        if self.current.is_none() {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await?;
+            let written = file.metadata().await?.len();
+            self.current = Some((file, written, Instant::now()));
+        }
+        if let Some((file, written, _)) = &mut self.current {
+            file.write_all(body).await?;
+            file.write_all(b"\n").await?;
+            file.flush().await?;
+            *written += len;
+        }
+        Ok(())
+    }
+
+    /// Moves the current file aside, suffixed with the current time.
+    async fn rotate(&mut self) -> io::Result<()> {
+        if let Some((mut file, _, _)) = self.current.take() {
+            file.flush().await?;
+        }
+        let mut rotated = self.path.clone().into_os_string();
+        rotated.push(Utc::now().format(".%Y%m%dT%H%M%S%.3fZ").to_string());
+        fs::rename(&self.path, rotated).await
+    }
+}
+
+async fn run(mut file: MirrorFile, mut rx: mpsc::Receiver<(RequestCompression, Bytes)>) {
+    while let Some((compression, body)) = rx.recv().await {
+        let result = match compression.decompress(&body) {
+            Ok(body) => file.write(&body).await,
+            Err(error) => Err(error),
+        };
+        if let Err(error) = result {
+            // The file is reopened for the next body, in case it was removed or its handle broke.
+            file.current = None;
+            emit!(&DatadogLogsMirrorWriteFailed {
+                error,
+                path: &file.path,
+            });
+        }
+    }
+}
@@ -24,11 +24,34 @@ mod integration_tests;
 #[cfg(test)]
 mod tests;
 
+mod ack_verification;
+mod bandwidth;
+mod body_buffer;
+mod buffer_depth;
+mod canary;
+mod cef;
+mod compression;
 mod config;
+mod deadline;
+mod drop_summary;
+mod dry_run;
+mod dual_ship;
+mod in_flight_bytes;
+mod mirror;
+mod multiline;
+mod routing;
+mod self_metrics;
 mod service;
 mod sink;
+mod smart_compression;
+mod stall;
+mod time_bucket;
 
-use crate::{config::SinkDescription, sinks::datadog::logs::config::DatadogLogsConfig};
+pub use self::config::{
+    DatadogLogsCodec, DatadogLogsConfig, DatadogLogsConfigBuilder, DatadogLogsDefaultBatchSettings,
+    DatadogLogsRequestConfig,
+};
+use crate::config::SinkDescription;
 
 inventory::submit! {
     SinkDescription::new::<DatadogLogsConfig>("datadog_logs")
@@ -0,0 +1,147 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use async_stream::stream;
+use bytes::{Bytes, BytesMut};
+use futures::{stream, Stream, StreamExt};
+use regex::bytes::Regex;
+use tokio::time::Instant;
+use vector_core::{
+    config::log_schema,
+    event::{Event, Finalizable, LogEvent, Value},
+};
+
+/// Coalesces the consecutive lines of one message, such as a stack trace, into a single log.
+///
+/// A log whose message matches `start_pattern` starts a new message, and the following logs of the
+/// same API key and source which don't match it are appended to that message as further lines. A message is
+/// sent once the next one starts, or once no line was appended to it for `timeout`.
+#[derive(Debug, Clone)]
+pub struct Multiline {
+    start_pattern: Regex,
+    timeout: Duration,
+}
+
+/// The messages being coalesced, by their source, with the time their last line was appended.
+type Pending = HashMap<SourceKey, (Event, Instant)>;
+
+/// Identifies where a line was read from, so that lines of different hosts, files or streams are
+/// never coalesced into one message.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct SourceKey {
+    api_key: Option<Arc<str>>,
+    host: Option<String>,
+    file: Option<String>,
+    stream: Option<String>,
+}
+
+impl SourceKey {
+    fn of(log: &LogEvent) -> Self {
+        let field = |name: &str| log.get(name).map(Value::to_string_lossy);
+        Self {
+            api_key: log.metadata().datadog_api_key().clone(),
+            host: field(log_schema().host_key()),
+            // As set by the `file` source, and by container sources respectively.
+            file: field("file"),
+            stream: field("stream"),
+        }
+    }
+}
+
+impl Multiline {
+    pub const fn new(start_pattern: Regex, timeout: Duration) -> Self {
+        Self {
+            start_pattern,
+            timeout,
+        }
+    }
+
+    pub fn coalesce<'a>(
+        self,
+        mut input: impl Stream<Item = Event> + Send + Unpin + 'a,
+    ) -> impl Stream<Item = Event> + Send + 'a {
+        let mut pending = Pending::new();
+        let mut flush = tokio::time::interval(self.timeout);
+        stream! {
+            loop {
+                let mut output = Vec::new();
+                let done = tokio::select! {
+                    _ = flush.tick() => {
+                        self.flush_expired(&mut pending, &mut output);
+                        false
+                    }
+                    event = input.next() => match event {
+                        Some(event) => {
+                            self.push(&mut pending, &mut output, event);
+                            false
+                        }
+                        None => {
+                            output.extend(pending.drain().map(|(_, (event, _))| event));
+                            true
+                        }
+                    }
+                };
+                yield stream::iter(output);
+                if done {
+                    break;
+                }
+            }
+        }
+        .flatten()
+    }
+
+    fn push(&self, pending: &mut Pending, output: &mut Vec<Event>, mut event: Event) {
+        let line = match &event {
+            Event::Log(log) => match log.get(log_schema().message_key()) {
+                Some(Value::Bytes(line)) => Some((line.clone(), SourceKey::of(log))),
+                _ => None,
+            },
+            _ => None,
+        };
+        let (line, key) = match line {
+            Some(line) => line,
+            None => {
+                // Events without a message are never part of one.
+                output.push(event);
+                return;
+            }
+        };
+        match pending.get_mut(&key) {
+            Some((message, last_line)) if !self.start_pattern.is_match(&line) => {
+                append_line(message, &line);
+                // The line is only acknowledged once the message it is part of is delivered, and
+                // is counted as read from the source along with it.
+                let source_events =
+                    message.metadata().source_events() + event.metadata().source_events();
+                let metadata = message.metadata_mut();
+                metadata.set_source_events(source_events);
+                metadata.merge_finalizers(event.take_finalizers());
+                *last_line = Instant::now();
+            }
+            _ => {
+                output.extend(pending.remove(&key).map(|(message, _)| message));
+                pending.insert(key, (event, Instant::now()));
+            }
+        }
+    }
+
+    fn flush_expired(&self, pending: &mut Pending, output: &mut Vec<Event>) {
+        let expired = pending
+            .iter()
+            .filter(|(_, (_, last_line))| last_line.elapsed() >= self.timeout)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in expired {
+            output.extend(pending.remove(&key).map(|(message, _)| message));
+        }
+    }
+}
+
+fn append_line(message: &mut Event, line: &Bytes) {
+    if let Some(Value::Bytes(message)) = message.as_mut_log().get_mut(log_schema().message_key()) {
+        let mut joined = BytesMut::with_capacity(message.len() + 1 + line.len());
+        joined.extend_from_slice(message);
+        joined.extend_from_slice(b"\n");
+        joined.extend_from_slice(line);
+        *message = joined.freeze();
+    }
+}
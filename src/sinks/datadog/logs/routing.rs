@@ -0,0 +1,36 @@
+use std::collections::HashMap;
+
+use http::Uri;
+use vector_core::event::Event;
+
+/// Routes each event to the intake matching its value of `field`, so that a single sink can send
+/// events to different Datadog sites, e.g. to keep EU-resident logs on the EU site.
+///
+/// Events whose value matches no route go to `default`, or to the endpoints of the sink if no
+/// default is configured.
+#[derive(Debug)]
+pub struct FieldRoutes {
+    field: String,
+    routes: HashMap<String, Uri>,
+    default: Option<Uri>,
+}
+
+impl FieldRoutes {
+    pub fn new(field: String, routes: HashMap<String, Uri>, default: Option<Uri>) -> Self {
+        Self {
+            field,
+            routes,
+            default,
+        }
+    }
+
+    /// Gets the intake `event` is routed to, if any.
+    pub fn route_of(&self, event: &Event) -> Option<Uri> {
+        event
+            .as_log()
+            .get(self.field.as_str())
+            .and_then(|value| self.routes.get(value.to_string_lossy().as_str()))
+            .or_else(|| self.default.as_ref())
+            .cloned()
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    convert::Infallible,
+    fmt::Write,
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Weak,
+    },
+};
+
+use futures::{future, FutureExt};
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Response, Server, StatusCode,
+};
+use tokio::sync::oneshot;
+
+use super::config::SelfMetricsConfig;
+
+/// Counts the outcome of the requests sent by the sink, and serves the counts in the Prometheus
+/// text format, for environments which don't consume Vector's internal metrics.
+///
+/// The server is shut down once the counts are no longer in use.
+#[derive(Debug)]
+pub struct SelfMetrics {
+    events_sent: AtomicU64,
+    events_dropped: AtomicU64,
+    bytes_sent: AtomicU64,
+    retries: AtomicU64,
+    in_flight: AtomicU64,
+    _shutdown: oneshot::Sender<()>,
+}
+
+impl SelfMetrics {
+    /// Binds the configured address, and spawns the server exposing the counts on it.
+    pub fn spawn(config: &SelfMetricsConfig) -> crate::Result<Arc<Self>> {
+        let listener = TcpListener::bind(config.address)?;
+        listener.set_nonblocking(true)?;
+
+        let (shutdown, shutdown_rx) = oneshot::channel();
+        let metrics = Arc::new(Self {
+            events_sent: AtomicU64::new(0),
+            events_dropped: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            retries: AtomicU64::new(0),
+            in_flight: AtomicU64::new(0),
+            _shutdown: shutdown,
+        });
+
+        // The server only holds a weak reference, so that it doesn't keep itself running.
+        let weak = Arc::downgrade(&metrics);
+        let service = make_service_fn(move |_| {
+            let weak = Weak::clone(&weak);
+            async move {
+                Ok::<_, Infallible>(service_fn(move |_| {
+                    future::ok::<_, Infallible>(match weak.upgrade() {
+                        Some(metrics) => Response::builder()
+                            .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+                            .body(Body::from(metrics.render()))
+                            .expect("response is valid"),
+                        None => Response::builder()
+                            .status(StatusCode::SERVICE_UNAVAILABLE)
+                            .body(Body::empty())
+                            .expect("response is valid"),
+                    })
+                }))
+            }
+        });
+        let server = Server::from_tcp(listener)?
+            .serve(service)
+            .with_graceful_shutdown(shutdown_rx.map(|_| ()));
+        tokio::spawn(async move {
+            if let Err(error) = server.await {
+                error!(message = "Self metrics server failed.", %error);
+            }
+        });
+
+        Ok(metrics)
+    }
+
+    /// Records the start of a request, until the returned guard is dropped.
+    pub fn start_request(self: &Arc<Self>) -> InFlight {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight(Arc::clone(self))
+    }
+
+    pub fn sent(&self, events: usize, bytes: usize) {
+        self.events_sent.fetch_add(events as u64, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn dropped(&self, events: usize) {
+        self.events_dropped
+            .fetch_add(events as u64, Ordering::Relaxed);
+    }
+
+    pub fn retried(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn render(&self) -> String {
+        let metrics = [
+            (
+                "events_sent_total",
+                "counter",
+                "Events delivered to Datadog.",
+                &self.events_sent,
+            ),
+            (
+                "events_dropped_total",
+                "counter",
+                "Events whose request failed and wasn't retried.",
+                &self.events_dropped,
+            ),
+            (
+                "bytes_sent_total",
+                "counter",
+                "Bytes of the request bodies delivered to Datadog.",
+                &self.bytes_sent,
+            ),
+            (
+                "retries_total",
+                "counter",
+                "Failed requests which are retried.",
+                &self.retries,
+            ),
+            (
+                "requests_in_flight",
+                "gauge",
+                "Requests waiting for a response.",
+                &self.in_flight,
+            ),
+        ];
+
+        let mut output = String::new();
+        for (name, kind, help, value) in metrics {
+            let name = format!("vector_datadog_logs_{}", name);
+            let _ = writeln!(output, "# HELP {} {}", name, help);
+            let _ = writeln!(output, "# TYPE {} {}", name, kind);
+            let _ = writeln!(output, "{} {}", name, value.load(Ordering::Relaxed));
+        }
+        output
+    }
+}
+
+/// Marks a request as in flight, for as long as it is held.
+pub struct InFlight(Arc<SelfMetrics>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
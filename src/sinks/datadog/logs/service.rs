@@ -1,17 +1,29 @@
 use std::{
-    sync::Arc,
+    hash::Hasher,
+    io,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
     task::{Context, Poll},
+    time::Duration,
 };
 
-use futures::future::BoxFuture;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{
+    future::{self, BoxFuture},
+    stream,
+};
 use http::{
-    header::{CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE},
-    Request, StatusCode, Uri,
+    header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, RETRY_AFTER},
+    HeaderMap, HeaderName, Request, Response, StatusCode, Uri,
 };
 use hyper::Body;
 use snafu::Snafu;
 use tower::Service;
 use tracing::Instrument;
+use twox_hash::XxHash64;
 use vector_core::{
     buffers::Ackable,
     event::{EventFinalizers, EventStatus, Finalizable},
@@ -19,24 +31,37 @@ use vector_core::{
     stream::DriverResponse,
 };
 
+use super::{
+    ack_verification::AckVerifier, bandwidth::PartitionRateLimits, compression::RequestCompression,
+    self_metrics::SelfMetrics, stall::StallTracker, time_bucket::TimeBucket,
+};
 use crate::{
-    http::HttpClient,
-    sinks::util::{retries::RetryLogic, Compression},
+    http::{HttpClient, HttpError},
+    internal_events::DatadogLogsEndpointFailover,
+    sinks::util::retries::RetryLogic,
 };
 
 #[derive(Debug, Default, Clone)]
 pub struct LogApiRetry;
 
 impl RetryLogic for LogApiRetry {
-    type Error = LogApiError;
+    type Error = DatadogLogsError;
     type Response = LogApiResponse;
 
     fn is_retriable_error(&self, error: &Self::Error) -> bool {
         match *error {
-            LogApiError::HttpError { .. }
-            | LogApiError::BadRequest
-            | LogApiError::PayloadTooLarge => false,
-            LogApiError::ServerError => true,
+            DatadogLogsError::HttpError { .. }
+            | DatadogLogsError::Unauthorized { .. }
+            | DatadogLogsError::Malformed
+            | DatadogLogsError::PayloadTooLarge => false,
+            DatadogLogsError::ServerError { .. } | DatadogLogsError::RateLimited { .. } => true,
+        }
+    }
+
+    fn retry_after(&self, error: &Self::Error) -> Option<Duration> {
+        match *error {
+            DatadogLogsError::RateLimited { retry_after } => retry_after,
+            _ => None,
         }
     }
 }
@@ -44,16 +69,24 @@ impl RetryLogic for LogApiRetry {
 #[derive(Debug, Clone)]
 pub struct LogApiRequest {
     pub batch_size: usize,
+    /// The number of events read from the source the events of the request stand for, which is
+    /// what the request acknowledges once it is done
+    pub source_events: usize,
     pub api_key: Arc<str>,
-    pub compression: Compression,
-    pub body: Vec<u8>,
+    /// The intake the request is routed to instead of the endpoints of the service, if any
+    pub uri: Option<Uri>,
+    pub time_bucket: Option<TimeBucket>,
+    pub compression: RequestCompression,
+    pub body: Bytes,
     pub finalizers: EventFinalizers,
     pub events_byte_size: usize,
+    /// The ID the request is tracked by, if acknowledgements are verified
+    pub ack_id: Option<u64>,
 }
 
 impl Ackable for LogApiRequest {
     fn ack_size(&self) -> usize {
-        self.batch_size
+        self.source_events
     }
 }
 
@@ -63,16 +96,38 @@ impl Finalizable for LogApiRequest {
     }
 }
 
+/// The failures of requests to Datadog, both for sending logs and for the healthcheck.
 #[derive(Debug, Snafu)]
-pub enum LogApiError {
-    #[snafu(display("Server responded with an error."))]
-    ServerError,
+pub enum DatadogLogsError {
+    #[snafu(display("Server responded with an error: {}.", status))]
+    ServerError { status: StatusCode },
+    #[snafu(display("Server is rate limiting requests."))]
+    RateLimited { retry_after: Option<Duration> },
+    #[snafu(display("Server rejected the API key: {}.", status))]
+    Unauthorized { status: StatusCode },
     #[snafu(display("Failed to make HTTP(S) request: {}", error))]
     HttpError { error: crate::http::HttpError },
     #[snafu(display("Client sent a payload that is too large."))]
     PayloadTooLarge,
     #[snafu(display("Client request was not valid for unknown reasons."))]
-    BadRequest,
+    Malformed,
+}
+
+impl DatadogLogsError {
+    /// Maps an unsuccessful response to the matching error.
+    pub fn from_response(response: &Response<Body>) -> Self {
+        match response.status() {
+            StatusCode::BAD_REQUEST => Self::Malformed,
+            status @ (StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => {
+                Self::Unauthorized { status }
+            }
+            StatusCode::PAYLOAD_TOO_LARGE => Self::PayloadTooLarge,
+            StatusCode::TOO_MANY_REQUESTS => Self::RateLimited {
+                retry_after: retry_after(response.headers()),
+            },
+            status => Self::ServerError { status },
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -82,6 +137,17 @@ pub struct LogApiResponse {
     events_byte_size: usize,
 }
 
+impl LogApiResponse {
+    /// The response of a request which was given up on, whose events are finalized as errored.
+    pub const fn errored(count: usize, events_byte_size: usize) -> Self {
+        Self {
+            event_status: EventStatus::Errored,
+            count,
+            events_byte_size,
+        }
+    }
+}
+
 impl DriverResponse for LogApiResponse {
     fn event_status(&self) -> EventStatus {
         self.event_status
@@ -95,6 +161,48 @@ impl DriverResponse for LogApiResponse {
     }
 }
 
+/// Tracks compressed requests rejected with `400 Bad Request` as their body couldn't be decoded.
+///
+/// Some relays between us and Datadog are misconfigured to reject compressed bodies while
+/// accepting uncompressed ones. Each rejected request is retried uncompressed, and once the
+/// configured number of consecutive compressed requests have been rejected, compression is
+/// disabled for the lifetime of the sink.
+#[derive(Debug)]
+pub struct CompressionFallback {
+    threshold: usize,
+    consecutive_rejections: AtomicUsize,
+    disabled: AtomicBool,
+}
+
+impl CompressionFallback {
+    pub const fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            consecutive_rejections: AtomicUsize::new(0),
+            disabled: AtomicBool::new(false),
+        }
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.disabled.load(Ordering::Relaxed)
+    }
+
+    /// Records a compressed request rejected as its body couldn't be decoded.
+    fn record_rejection(&self) {
+        let rejections = self.consecutive_rejections.fetch_add(1, Ordering::Relaxed) + 1;
+        if rejections >= self.threshold && !self.disabled.swap(true, Ordering::Relaxed) {
+            warn!(
+                message = "Compressed requests were repeatedly rejected, disabling compression.",
+                rejections
+            );
+        }
+    }
+
+    fn record_acceptance(&self) {
+        self.consecutive_rejections.store(0, Ordering::Relaxed);
+    }
+}
+
 /// Wrapper for the Datadog API.
 ///
 /// Provides a `tower::Service` for the Datadog Logs API, allowing it to be
@@ -103,33 +211,123 @@ impl DriverResponse for LogApiResponse {
 #[derive(Debug, Clone)]
 pub struct LogApiService {
     client: HttpClient,
-    uri: Uri,
+    /// The endpoints in order of preference, of which requests are sent to the active one
+    uris: Arc<[Uri]>,
+    active: Arc<AtomicUsize>,
     enterprise: bool,
+    compression_fallback: Option<Arc<CompressionFallback>>,
+    stall_tracker: Option<Arc<StallTracker>>,
+    partition_rate_limits: Option<PartitionRateLimits>,
+    omit_content_length: bool,
+    content_type: &'static str,
+    ack_verifier: Option<Arc<AckVerifier>>,
+    headers: HeaderMap,
+    idempotency_header: Option<HeaderName>,
+    self_metrics: Option<Arc<SelfMetrics>>,
 }
 
 impl LogApiService {
-    pub const fn new(client: HttpClient, uri: Uri, enterprise: bool) -> Self {
+    pub fn new(client: HttpClient, uri: Uri, enterprise: bool) -> Self {
+        Self::with_failover(client, vec![uri], enterprise)
+    }
+
+    /// Sends requests to the first of `uris`, failing over to the next one each time the active
+    /// one can't be connected to.
+    ///
+    /// Panics if `uris` is empty.
+    pub fn with_failover(client: HttpClient, uris: Vec<Uri>, enterprise: bool) -> Self {
+        assert!(!uris.is_empty(), "at least one endpoint is required");
         Self {
             client,
-            uri,
+            uris: uris.into(),
+            active: Arc::new(AtomicUsize::new(0)),
             enterprise,
+            compression_fallback: None,
+            stall_tracker: None,
+            partition_rate_limits: None,
+            omit_content_length: false,
+            content_type: "application/json",
+            ack_verifier: None,
+            headers: HeaderMap::new(),
+            idempotency_header: None,
+            self_metrics: None,
         }
     }
-}
 
-impl Service<LogApiRequest> for LogApiService {
-    type Response = LogApiResponse;
-    type Error = LogApiError;
-    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+    /// Sets the content type of request bodies, which depends on the codec they are encoded with.
+    pub const fn with_content_type(mut self, content_type: &'static str) -> Self {
+        self.content_type = content_type;
+        self
+    }
 
-    fn poll_ready(&mut self, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
-        Poll::Ready(Ok(()))
+    /// Sends request bodies chunked, without a `Content-Length` header, for relays which reject it.
+    pub const fn with_omit_content_length(mut self, omit_content_length: bool) -> Self {
+        self.omit_content_length = omit_content_length;
+        self
     }
 
-    fn call(&mut self, request: LogApiRequest) -> Self::Future {
-        let mut client = self.client.clone();
-        let http_request = Request::post(&self.uri)
-            .header(CONTENT_TYPE, "application/json")
+    /// Caps the request rate of individual partitions.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn with_partition_rate_limits(mut self, limits: Option<PartitionRateLimits>) -> Self {
+        self.partition_rate_limits = limits;
+        self
+    }
+
+    /// Tracks the last successful delivery of each partition, to detect stuck partitions.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn with_stall_tracker(mut self, stall_tracker: Option<Arc<StallTracker>>) -> Self {
+        self.stall_tracker = stall_tracker;
+        self
+    }
+
+    /// Counts the outcome of each request attempt in `metrics`.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn with_self_metrics(mut self, metrics: Option<Arc<SelfMetrics>>) -> Self {
+        self.self_metrics = metrics;
+        self
+    }
+
+    /// Confirms the delivery of each request answered with a `2xx` response to `verifier`.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn with_ack_verifier(mut self, verifier: Option<Arc<AckVerifier>>) -> Self {
+        self.ack_verifier = verifier;
+        self
+    }
+
+    /// Sets additional headers on every request, which must not be any of the headers set by the
+    /// service itself.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Sends a key derived from the contents of each request in `header`, which stays the same
+    /// when the request is retried, so that a relay can deduplicate the retries of requests it
+    /// already received.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn with_idempotency_header(mut self, header: Option<HeaderName>) -> Self {
+        self.idempotency_header = header;
+        self
+    }
+
+    /// Falls back to sending requests uncompressed once compressed ones are repeatedly rejected.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn with_compression_fallback(mut self, fallback: Option<Arc<CompressionFallback>>) -> Self {
+        self.compression_fallback = fallback;
+        self
+    }
+
+    fn build_http_request(
+        &self,
+        uri: &Uri,
+        api_key: &str,
+        idempotency_key: Option<&str>,
+        compression: RequestCompression,
+        body: Bytes,
+    ) -> Request<Body> {
+        let mut http_request = Request::post(uri)
+            .header(CONTENT_TYPE, self.content_type)
             .header(
                 "DD-EVP-ORIGIN",
                 if self.enterprise {
@@ -139,54 +337,317 @@ impl Service<LogApiRequest> for LogApiService {
                 },
             )
             .header("DD-EVP-ORIGIN-VERSION", crate::get_version())
-            .header("DD-API-KEY", request.api_key.to_string());
+            .header("DD-API-KEY", api_key);
+        for (name, value) in &self.headers {
+            http_request = http_request.header(name, value);
+        }
+        if let (Some(name), Some(key)) = (&self.idempotency_header, idempotency_key) {
+            http_request = http_request.header(name, key);
+        }
 
-        let http_request = if let Some(ce) = request.compression.content_encoding() {
+        let http_request = if let Some(ce) = compression.content_encoding() {
             http_request.header(CONTENT_ENCODING, ce)
         } else {
             http_request
         };
 
-        let http_request = http_request
-            .header(CONTENT_LENGTH, request.body.len())
-            .body(Body::from(request.body))
-            .expect("building HTTP request failed unexpectedly");
+        let http_request = if self.omit_content_length {
+            // A body of unknown length is sent chunked, as otherwise hyper sets the header itself.
+            http_request.body(Body::wrap_stream(stream::once(future::ok::<_, io::Error>(
+                body,
+            ))))
+        } else {
+            http_request
+                .header(CONTENT_LENGTH, body.len())
+                .body(Body::from(body))
+        };
+        http_request.expect("building HTTP request failed unexpectedly")
+    }
+
+    async fn send(
+        &self,
+        route: Option<&Uri>,
+        api_key: &str,
+        idempotency_key: Option<&str>,
+        mut compression: RequestCompression,
+        mut body: Bytes,
+        count: usize,
+        events_byte_size: usize,
+    ) -> Result<LogApiResponse, DatadogLogsError> {
+        let fallback = self.compression_fallback.as_ref();
+        if compression.is_compressed() && fallback.map_or(false, |f| f.is_disabled()) {
+            // The request was built before compression was disabled.
+            body = compression
+                .decompress(&body)
+                .map(Bytes::from)
+                .map_err(|_| DatadogLogsError::Malformed)?;
+            compression = RequestCompression::None;
+        }
+
+        let response = self
+            .call_with_failover(route, |uri| {
+                self.build_http_request(uri, api_key, idempotency_key, compression, body.clone())
+            })
+            .await?;
+        let status = response.status();
+
+        if let Some(fallback) = fallback.filter(|_| compression.is_compressed()) {
+            if status != StatusCode::BAD_REQUEST {
+                fallback.record_acceptance();
+            } else if is_decoding_failure(compression, response).await {
+                fallback.record_rejection();
+                // Retry the rejected batch uncompressed right away, as it would otherwise be
+                // dropped.
+                let body = compression
+                    .decompress(&body)
+                    .map(Bytes::from)
+                    .map_err(|_| DatadogLogsError::Malformed)?;
+                let response = self
+                    .call_with_failover(route, |uri| {
+                        self.build_http_request(
+                            uri,
+                            api_key,
+                            idempotency_key,
+                            RequestCompression::None,
+                            body.clone(),
+                        )
+                    })
+                    .await?;
+                return outcome(&response, count, events_byte_size);
+            } else {
+                // The logs of the batch were rejected rather than its compression.
+                return Err(DatadogLogsError::Malformed);
+            }
+        }
+
+        outcome(&response, count, events_byte_size)
+    }
+
+    /// Sends the request built by `build` to the active endpoint, failing over to the next ones in
+    /// turn while they can't be connected to.
+    ///
+    /// Connection errors aren't retried, so the active endpoint is given up on after the first one.
+    /// A request routed to its own intake is only sent there, without failing over.
+    async fn call_with_failover<F>(
+        &self,
+        route: Option<&Uri>,
+        build: F,
+    ) -> Result<Response<Body>, DatadogLogsError>
+    where
+        F: Fn(&Uri) -> Request<Body>,
+    {
+        let mut client = self.client.clone();
+        if let Some(uri) = route {
+            return client
+                .call(build(uri))
+                .in_current_span()
+                .await
+                .map_err(|error| DatadogLogsError::HttpError { error });
+        }
+        let active = self.active.load(Ordering::Relaxed);
+        let mut attempts = (0..self.uris.len()).map(|n| (active + n) % self.uris.len());
+        loop {
+            let index = attempts.next().expect("at least one endpoint");
+            let uri = &self.uris[index];
+            let error = match client.call(build(uri)).in_current_span().await {
+                Ok(response) => return Ok(response),
+                Err(error) => error,
+            };
+            let next = match attempts.clone().next() {
+                Some(next) if is_connection_error(&error) => next,
+                _ => return Err(DatadogLogsError::HttpError { error }),
+            };
+            // Concurrent requests may have failed over already.
+            if self
+                .active
+                .compare_exchange(index, next, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+            {
+                emit!(&DatadogLogsEndpointFailover {
+                    from: uri,
+                    to: &self.uris[next],
+                    error: &error,
+                });
+            }
+        }
+    }
+}
+
+/// Whether the `400 Bad Request` `response` to a request compressed with `compression` was sent as
+/// its body couldn't be decoded, rather than for the logs it holds.
+///
+/// This is the case if the response lists the encodings it accepts without that of the request, as
+/// in RFC 7694, or if its body mentions decoding or the encoding of the request.
+async fn is_decoding_failure(compression: RequestCompression, response: Response<Body>) -> bool {
+    let encoding = compression.content_encoding().unwrap_or("identity");
+    let accepts_encoding = response
+        .headers()
+        .get(ACCEPT_ENCODING)
+        .and_then(|accepted| accepted.to_str().ok())
+        .map(|accepted| {
+            accepted
+                .split(',')
+                .filter_map(|coding| coding.split(';').next())
+                .any(|coding| coding.trim().eq_ignore_ascii_case(encoding))
+        });
+    if accepts_encoding == Some(false) {
+        return true;
+    }
+    match hyper::body::to_bytes(response.into_body()).await {
+        Ok(body) => {
+            let body = String::from_utf8_lossy(&body).to_ascii_lowercase();
+            ["decod", "decompress", "content-encoding", encoding]
+                .iter()
+                .any(|hint| body.contains(hint))
+        }
+        Err(_) => false,
+    }
+}
 
+/// Derives the idempotency key of a request from its body.
+fn idempotency_key(body: &[u8]) -> String {
+    let hash = |seed| {
+        let mut hasher = XxHash64::with_seed(seed);
+        hasher.write(body);
+        hasher.finish()
+    };
+    format!("{:016x}{:016x}", hash(0), hash(1))
+}
+
+/// Whether `error` happened while connecting, so the request never reached the endpoint.
+fn is_connection_error(error: &HttpError) -> bool {
+    matches!(error, HttpError::CallRequest { source } if source.is_connect())
+}
+
+/// Parses the `Retry-After` header of `headers`, in either its delay-seconds or HTTP-date form.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    // A date in the past asks for no delay.
+    Some(
+        date.signed_duration_since(Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+/// Maps a Datadog Logs API response to the outcome of the request.
+fn outcome(
+    response: &Response<Body>,
+    count: usize,
+    events_byte_size: usize,
+) -> Result<LogApiResponse, DatadogLogsError> {
+    // From https://docs.datadoghq.com/api/latest/logs/:
+    //
+    // The status codes answered by the HTTP API are:
+    // 200: OK (v1)
+    // 202: Accepted (v2)
+    // 400: Bad request (likely an issue in the payload
+    //      formatting)
+    // 401: Unauthorized (the API key is missing)
+    // 403: Permission issue (likely using an invalid API Key)
+    // 413: Payload too large (batch is above 5MB uncompressed)
+    // 429: Too many requests, request should be retried after the
+    //      `Retry-After` delay, if any
+    // 5xx: Internal error, request should be retried after some
+    //      time
+    match response.status() {
+        StatusCode::FORBIDDEN => Ok(LogApiResponse {
+            event_status: EventStatus::Errored,
+            count,
+            events_byte_size,
+        }),
+        StatusCode::OK | StatusCode::ACCEPTED => Ok(LogApiResponse {
+            event_status: EventStatus::Delivered,
+            count,
+            events_byte_size,
+        }),
+        _ => Err(DatadogLogsError::from_response(response)),
+    }
+}
+
+impl Service<LogApiRequest> for LogApiService {
+    type Response = LogApiResponse;
+    type Error = DatadogLogsError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: LogApiRequest) -> Self::Future {
+        let service = self.clone();
         let count = request.batch_size;
         let events_byte_size = request.events_byte_size;
+        let api_key = request.api_key;
+        let route = request.uri;
+        let compression = request.compression;
+        // Retries send the request as it was built, so they get the same key.
+        let idempotency_key = service
+            .idempotency_header
+            .as_ref()
+            .map(|_| idempotency_key(&request.body));
+        let body = request.body;
+        let body_len = body.len();
+        let ack_id = request.ack_id;
+
         Box::pin(async move {
-            match client.call(http_request).in_current_span().await {
-                Ok(response) => {
-                    let status = response.status();
-                    // From https://docs.datadoghq.com/api/latest/logs/:
-                    //
-                    // The status codes answered by the HTTP API are:
-                    // 200: OK (v1)
-                    // 202: Accepted (v2)
-                    // 400: Bad request (likely an issue in the payload
-                    //      formatting)
-                    // 403: Permission issue (likely using an invalid API Key)
-                    // 413: Payload too large (batch is above 5MB uncompressed)
-                    // 5xx: Internal error, request should be retried after some
-                    //      time
-                    match status {
-                        StatusCode::BAD_REQUEST => Err(LogApiError::BadRequest),
-                        StatusCode::FORBIDDEN => Ok(LogApiResponse {
-                            event_status: EventStatus::Errored,
-                            count,
-                            events_byte_size,
-                        }),
-                        StatusCode::OK | StatusCode::ACCEPTED => Ok(LogApiResponse {
-                            event_status: EventStatus::Delivered,
-                            count,
-                            events_byte_size,
-                        }),
-                        StatusCode::PAYLOAD_TOO_LARGE => Err(LogApiError::PayloadTooLarge),
-                        _ => Err(LogApiError::ServerError),
-                    }
+            if count == 0 {
+                // All events of the request were dropped while encoding it, so there is nothing to
+                // send, but the request still completes for the reads of those events to be
+                // acknowledged.
+                if let (Some(verifier), Some(id)) = (&service.ack_verifier, ack_id) {
+                    verifier.confirm(id);
+                }
+                return Ok(LogApiResponse {
+                    event_status: EventStatus::Delivered,
+                    count,
+                    events_byte_size,
+                });
+            }
+            if let Some(limits) = &service.partition_rate_limits {
+                limits.acquire(&api_key).await;
+            }
+            let in_flight = service
+                .self_metrics
+                .as_ref()
+                .map(SelfMetrics::start_request);
+            let result = service
+                .send(
+                    route.as_ref(),
+                    &api_key,
+                    idempotency_key.as_deref(),
+                    compression,
+                    body,
+                    count,
+                    events_byte_size,
+                )
+                .await;
+            // Only a `2xx` response is mapped to delivered.
+            let delivered = matches!(
+                &result,
+                Ok(response) if response.event_status == EventStatus::Delivered
+            );
+            if let Some(stall_tracker) = &service.stall_tracker {
+                stall_tracker.record(&api_key, delivered);
+            }
+            if let Some(metrics) = &service.self_metrics {
+                drop(in_flight);
+                match &result {
+                    _ if delivered => metrics.sent(count, body_len),
+                    Err(error) if LogApiRetry.is_retriable_error(error) => metrics.retried(),
+                    _ => metrics.dropped(count),
                 }
-                Err(error) => Err(LogApiError::HttpError { error }),
             }
+            if let (Some(verifier), Some(id)) =
+                (&service.ack_verifier, ack_id.filter(|_| delivered))
+            {
+                verifier.confirm(id);
+            }
+            result
         })
     }
 }
@@ -1,41 +1,346 @@
 use std::{
-    fmt::Debug,
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Debug},
+    hash::Hasher,
     io::{self, Write},
     num::NonZeroUsize,
-    sync::Arc,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
 use async_trait::async_trait;
-use futures::{stream::BoxStream, StreamExt};
+use bytes::{BufMut, Bytes, BytesMut};
+use chrono::Utc;
+use futures::{
+    future,
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use http::Uri;
+use prost::Message;
 use snafu::Snafu;
-use tower::Service;
+use tower::{Service, ServiceExt};
+use twox_hash::XxHash64;
+use uuid::Uuid;
 use vector_core::{
     buffers::Acker,
     config::{log_schema, LogSchema},
-    event::{Event, EventFinalizers, Finalizable, Value},
+    event::{
+        proto::EventWrapper, Event, EventFinalizers, EventMetadata, EventStatus, Finalizable,
+        LogEvent, Metric, Value,
+    },
     partition::Partitioner,
     sink::StreamSink,
     stream::{BatcherSettings, DriverResponse},
     ByteSizeOf,
 };
 
-use super::{config::MAX_PAYLOAD_BYTES, service::LogApiRequest};
+use super::{
+    ack_verification::{AckVerificationService, AckVerifier},
+    bandwidth::BandwidthLimiter,
+    body_buffer::BodyBuffer,
+    buffer_depth::{BufferDepth, BufferDepthService},
+    canary::{canary_event, CanaryTrigger},
+    cef::encode_cef,
+    compression::{RequestCompression, RequestCompressor},
+    config::{
+        CefConfig, DatadogLogsCodec, DeterministicIdConfig, DottedKeyPolicy, DrainOrder,
+        InvalidTimestampPolicy, InvalidUtf8Policy, OversizedLogPolicy, ReservedAttributesConfig,
+        MAX_LOG_BYTES, MAX_PAYLOAD_BYTES,
+    },
+    drop_summary::DropSummary,
+    dual_ship::SecondarySite,
+    in_flight_bytes::{InFlightBytes, InFlightBytesService},
+    mirror::FileMirror,
+    multiline::Multiline,
+    routing::FieldRoutes,
+    service::LogApiRequest,
+    smart_compression::SmartCompression,
+    time_bucket::TimeBucket,
+};
 use crate::{
     config::SinkContext,
+    internal_events::{
+        DatadogLogAttributeValueTruncated, DatadogLogAttributesTruncated,
+        DatadogLogDottedKeyRejected, DatadogLogEncodingError, DatadogLogEventDropped,
+        DatadogLogEventTooLarge, DatadogLogsBatchFlushed, DatadogLogsImplausibleTimestamp,
+        DatadogLogsInvalidTimestamp, DatadogLogsRequestCompressed, TemplateRenderingFailed,
+    },
     sinks::util::{
-        encoding::{Encoder, EncodingConfigFixed, StandardEncodings},
-        Compression, Compressor, RequestBuilder, SinkBuilderExt,
+        encoding::{Encoder, EncodingConfigFixed, EncodingConfiguration, StandardEncodings},
+        Compression, RequestBuilder, SinkBuilderExt,
     },
+    template::Template,
+    transforms::metric_to_log::MetricToLog,
 };
+
+/// The maximum number of batches that are ready at the same time which `DrainOrder` reorders.
+const DRAIN_WINDOW: usize = 64;
+
+/// Reorders batches that are ready at the same time according to `drain_order`.
+///
+/// Ties keep their original order, as do batches without timestamps, which go last for
+/// `DrainOrder::NewestFirst`.
+fn arrange_batches(drain_order: DrainOrder, batches: &mut [(PartitionKey, Vec<Event>)]) {
+    match drain_order {
+        DrainOrder::Fifo => {}
+        DrainOrder::Lifo => batches.reverse(),
+        DrainOrder::NewestFirst => batches.sort_by_key(|(_, events)| {
+            std::cmp::Reverse(
+                events
+                    .iter()
+                    .filter_map(|event| {
+                        event
+                            .as_log()
+                            .get(log_schema().timestamp_key())
+                            .and_then(Value::as_timestamp)
+                            .copied()
+                    })
+                    .max(),
+            )
+        }),
+    }
+}
+
+/// Converts `metric` into a log, with its tags also in `ddtags` so they remain searchable.
+///
+/// Tags are sorted by key, so that the same tag set always yields the same `ddtags`.
+fn metric_into_log(metric_to_log: &MetricToLog, metric: Metric) -> Option<LogEvent> {
+    let ddtags = metric.tags().filter(|tags| !tags.is_empty()).map(|tags| {
+        tags.iter()
+            .map(|(key, value)| format!("{}:{}", key, value))
+            .collect::<Vec<_>>()
+            .join(",")
+    });
+    let mut log = metric_to_log.transform_one(metric)?;
+    if let Some(ddtags) = ddtags {
+        log.insert("ddtags", ddtags);
+    }
+    Some(log)
+}
+
+/// Rejects a metric which isn't sent as a log, acknowledging the events it was read from since
+/// no request will.
+fn reject_metric(
+    metadata: &EventMetadata,
+    reason: &'static str,
+    acker: &Acker,
+    drop_summary: Option<&DropSummary>,
+) {
+    metadata.update_status(EventStatus::Rejected);
+    emit!(&DatadogLogEventDropped { reason });
+    if let Some(summary) = drop_summary {
+        summary.record(reason);
+    }
+    acker.ack(metadata.source_events());
+}
+
+/// The key events are batched by, where the dimensions which aren't configured are `None`.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+struct PartitionKey {
+    api_key: Option<Arc<str>>,
+    /// The intake of the event, if routed by a field.
+    route: Option<Uri>,
+    time_bucket: Option<TimeBucket>,
+    size_class: Option<usize>,
+    /// The run of equal values of the `flush_on_change_field`.
+    run: Option<u64>,
+    /// The run of contiguous values of the `flush_on_sequence_gap` field.
+    sequence_run: Option<u64>,
+    /// The values of the `partition_key_fields`, where missing fields are `None`.
+    key_values: Vec<Option<String>>,
+}
+
+struct EventPartitioner {
+    routes: Option<FieldRoutes>,
+    hot_window: Option<chrono::Duration>,
+    /// Ascending byte size thresholds, where the size class of an event is the number of
+    /// thresholds its size reaches.
+    size_classes: Option<Vec<usize>>,
+    flush_on_change: Option<FlushOnChange>,
+    flush_on_sequence_gap: Option<FlushOnSequenceGap>,
+    /// Fields whose values split batches, where events missing a field share a partition.
+    key_fields: Vec<String>,
+}
+
+/// The current run of each API key, numbered across all API keys so that a run started after the
+/// state of its API key was evicted never reuses the number of an earlier one.
 #[derive(Default)]
-struct EventPartitioner;
+struct Runs<V> {
+    current: HashMap<Option<Arc<str>>, (V, u64)>,
+    next: u64,
+}
+
+impl<V> Runs<V> {
+    fn start(&mut self, api_key: Option<Arc<str>>, value: V) -> u64 {
+        let run = self.next;
+        self.next += 1;
+        self.current.insert(api_key, (value, run));
+        run
+    }
+
+    /// Drops the state of `api_key` if `run` is still its current run, as its batch was flushed.
+    fn evict(&mut self, api_key: &Option<Arc<str>>, run: u64) {
+        if matches!(self.current.get(api_key), Some((_, current)) if *current == run) {
+            self.current.remove(api_key);
+        }
+    }
+}
+
+/// Splits the events of each API key into runs of consecutive events with the same value of
+/// `field`, so that a batch never spans more than one run.
+///
+/// A new run ends the previous run of its API key, whose batches are then flushed right away. The
+/// state of an API key is dropped once the batch of its current run is flushed, so the next event
+/// of that API key starts a new run.
+struct FlushOnChange {
+    field: String,
+    runs: Mutex<Runs<Option<Value>>>,
+}
+
+impl FlushOnChange {
+    fn new(field: String) -> Self {
+        Self {
+            field,
+            runs: Mutex::default(),
+        }
+    }
+
+    /// Gets the number of the run `event` belongs to, starting a new run if its value of the field
+    /// differs from the previous event of its API key.
+    fn run_of(&self, event: &Event) -> u64 {
+        let value = event.as_log().get(self.field.as_str()).cloned();
+        let api_key = event.metadata().datadog_api_key();
+        let mut runs = self.runs.lock().expect("flush on change mutex poisoned");
+        if let Some((last_value, run)) = runs.current.get(api_key) {
+            if *last_value == value {
+                return *run;
+            }
+        }
+        runs.start(api_key.clone(), value)
+    }
+
+    fn flushed(&self, api_key: &Option<Arc<str>>, run: u64) {
+        let mut runs = self.runs.lock().expect("flush on change mutex poisoned");
+        runs.evict(api_key, run);
+    }
+}
+
+/// Splits the events of each API key into runs of consecutive events whose integer values of
+/// `field` each follow on from the previous one, so that a batch never spans a gap in the sequence.
+///
+/// Runs end and their state is dropped as for [`FlushOnChange`]. Events without an integer value of
+/// the field stay in the current run, and the next sequence number is checked against the last one
+/// seen.
+struct FlushOnSequenceGap {
+    field: String,
+    runs: Mutex<Runs<Option<i64>>>,
+}
+
+impl FlushOnSequenceGap {
+    fn new(field: String) -> Self {
+        Self {
+            field,
+            runs: Mutex::default(),
+        }
+    }
+
+    /// Gets the number of the run `event` belongs to, starting a new run if its sequence number
+    /// isn't one more than that of the previous event of its API key.
+    fn run_of(&self, event: &Event) -> u64 {
+        let sequence = match event.as_log().get(self.field.as_str()) {
+            Some(Value::Integer(sequence)) => Some(*sequence),
+            _ => None,
+        };
+        let api_key = event.metadata().datadog_api_key();
+        let mut runs = self
+            .runs
+            .lock()
+            .expect("flush on sequence gap mutex poisoned");
+        let current = runs.current.get(api_key).map(|(last, run)| (*last, *run));
+        match (current, sequence) {
+            (Some((Some(last), _)), Some(sequence)) if last.checked_add(1) != Some(sequence) => {
+                runs.start(api_key.clone(), Some(sequence))
+            }
+            (Some((_, run)), Some(sequence)) => {
+                runs.current.insert(api_key.clone(), (Some(sequence), run));
+                run
+            }
+            (Some((_, run)), None) => run,
+            (None, sequence) => runs.start(api_key.clone(), sequence),
+        }
+    }
+
+    fn flushed(&self, api_key: &Option<Arc<str>>, run: u64) {
+        let mut runs = self
+            .runs
+            .lock()
+            .expect("flush on sequence gap mutex poisoned");
+        runs.evict(api_key, run);
+    }
+}
 
 impl Partitioner for EventPartitioner {
     type Item = Event;
-    type Key = Option<Arc<str>>;
+    type Key = PartitionKey;
 
     fn partition(&self, item: &Self::Item) -> Self::Key {
-        item.metadata().datadog_api_key().clone()
+        let route = self
+            .routes
+            .as_ref()
+            .and_then(|routes| routes.route_of(item));
+        let time_bucket = self
+            .hot_window
+            .map(|hot_window| TimeBucket::of(item, hot_window));
+        let size_class = self.size_classes.as_ref().map(|thresholds| {
+            let size = item.size_of();
+            thresholds
+                .iter()
+                .take_while(|threshold| size >= **threshold)
+                .count()
+        });
+        let run = self
+            .flush_on_change
+            .as_ref()
+            .map(|flush_on_change| flush_on_change.run_of(item));
+        let sequence_run = self
+            .flush_on_sequence_gap
+            .as_ref()
+            .map(|flush_on_sequence_gap| flush_on_sequence_gap.run_of(item));
+        let log = item.as_log();
+        let key_values = self
+            .key_fields
+            .iter()
+            .map(|field| log.get(field.as_str()).map(|value| value.to_string_lossy()))
+            .collect();
+        PartitionKey {
+            api_key: item.metadata().datadog_api_key().clone(),
+            route,
+            time_bucket,
+            size_class,
+            run,
+            sequence_run,
+            key_values,
+        }
+    }
+
+    /// A new run of an API key ends its batches of earlier runs, as they won't receive any
+    /// further events.
+    fn ends(&self, item_key: &Self::Key, key: &Self::Key) -> bool {
+        key.api_key == item_key.api_key
+            && (key.run < item_key.run || key.sequence_run < item_key.sequence_run)
+    }
+
+    fn flushed(&self, key: &Self::Key) {
+        if let (Some(flush_on_change), Some(run)) = (&self.flush_on_change, key.run) {
+            flush_on_change.flushed(&key.api_key, run);
+        }
+        if let (Some(flush_on_sequence_gap), Some(run)) =
+            (&self.flush_on_sequence_gap, key.sequence_run)
+        {
+            flush_on_sequence_gap.flushed(&key.api_key, run);
+        }
     }
 }
 
@@ -45,8 +350,31 @@ pub struct LogSinkBuilder<S> {
     service: S,
     context: SinkContext,
     batch_settings: BatcherSettings,
-    compression: Option<Compression>,
+    compression: Option<RequestCompression>,
+    smart_compression: bool,
+    precompressed_field: Option<String>,
+    drain_order: DrainOrder,
     default_api_key: Arc<str>,
+    api_key_template: Option<Template>,
+    max_bandwidth_bytes_per_sec: Option<u64>,
+    secondary: Option<SecondarySite>,
+    mirror: Option<FileMirror>,
+    multiline: Option<Multiline>,
+    hot_window: Option<Duration>,
+    size_classes: Option<Vec<usize>>,
+    metric_to_log: Option<MetricToLog>,
+    startup_canary: bool,
+    canary_trigger: Option<CanaryTrigger>,
+    flush_on_change_field: Option<String>,
+    flush_on_sequence_gap: Option<String>,
+    partition_key_fields: Vec<String>,
+    field_routes: Option<FieldRoutes>,
+    buffer_metrics_interval: Option<Duration>,
+    add_codec_tag: bool,
+    add_send_timestamp: bool,
+    ack_verifier: Option<Arc<AckVerifier>>,
+    encode_concurrency: Option<NonZeroUsize>,
+    max_in_flight_bytes: Option<u64>,
 }
 
 impl<S> LogSinkBuilder<S> {
@@ -63,28 +391,387 @@ impl<S> LogSinkBuilder<S> {
             default_api_key,
             batch_settings,
             compression: None,
+            smart_compression: false,
+            precompressed_field: None,
+            drain_order: DrainOrder::Fifo,
+            api_key_template: None,
+            max_bandwidth_bytes_per_sec: None,
+            secondary: None,
+            mirror: None,
+            multiline: None,
+            hot_window: None,
+            size_classes: None,
+            metric_to_log: None,
+            startup_canary: false,
+            canary_trigger: None,
+            flush_on_change_field: None,
+            flush_on_sequence_gap: None,
+            partition_key_fields: Vec::new(),
+            field_routes: None,
+            buffer_metrics_interval: None,
+            add_codec_tag: false,
+            add_send_timestamp: false,
+            ack_verifier: None,
+            encode_concurrency: None,
+            max_in_flight_bytes: None,
         }
     }
 
+    /// Sets the encoding rules of the sink, replacing the codec options set so far, so it must be
+    /// called before the setters of codec options.
     #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
     pub fn encoding(mut self, encoding: EncodingConfigFixed<DatadogLogsJsonEncoding>) -> Self {
         self.encoding = encoding;
         self
     }
 
-    pub const fn compression(mut self, compression: Compression) -> Self {
+    /// Sets the format of request bodies.
+    pub const fn codec(mut self, codec: DatadogLogsCodec) -> Self {
+        self.encoding.codec.format = codec;
+        self
+    }
+
+    /// Sets the header fields of logs encoded with the `cef` codec.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn cef(mut self, cef: CefConfig) -> Self {
+        self.encoding.codec.cef = cef;
+        self
+    }
+
+    /// Stamps each event with an identifier derived from the configured fields.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn deterministic_id(mut self, deterministic_id: Option<DeterministicIdConfig>) -> Self {
+        self.encoding.codec.deterministic_id = deterministic_id;
+        self
+    }
+
+    /// Stamps each event with the correlation ID from its metadata, or a generated one marked as
+    /// synthetic when it has none.
+    pub const fn correlation_id_from_metadata(
+        mut self,
+        correlation_id_from_metadata: bool,
+    ) -> Self {
+        self.encoding.codec.correlation_id_from_metadata = correlation_id_from_metadata;
+        self
+    }
+
+    /// Strips control characters from the string fields of each event, optionally keeping tabs and
+    /// newlines.
+    pub const fn strip_control_chars(mut self, strip_control_chars: Option<ControlChars>) -> Self {
+        self.encoding.codec.strip_control_chars = strip_control_chars;
+        self
+    }
+
+    /// Warns about timestamps further than `threshold_secs` from the current time.
+    pub fn implausible_timestamp_threshold_secs(mut self, threshold_secs: Option<u64>) -> Self {
+        self.encoding.codec.implausible_timestamp_threshold_millis =
+            threshold_secs.map(|secs| i128::from(secs) * 1000);
+        self
+    }
+
+    /// Caps the number of attributes of each event, always retaining the reserved attributes.
+    pub const fn max_attributes_per_event(mut self, max_attributes: Option<usize>) -> Self {
+        self.encoding.codec.max_attributes_per_event = max_attributes;
+        self
+    }
+
+    /// Sanitizes or drops attribute keys containing dots, rather than letting Datadog nest them.
+    pub const fn dotted_key_policy(mut self, policy: DottedKeyPolicy) -> Self {
+        self.encoding.codec.dotted_key_policy = policy;
+        self
+    }
+
+    /// Derives the `host` of each event from `template`, in place of the host field.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn host_template(mut self, template: Option<Template>) -> Self {
+        self.encoding.codec.host_template = template;
+        self
+    }
+
+    /// Sets the attributes that the message, host and timestamp of each event are sent as.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn reserved_attributes(mut self, reserved_attributes: ReservedAttributesConfig) -> Self {
+        self.encoding.codec.reserved_attributes = reserved_attributes;
+        self
+    }
+
+    /// Sets attributes on each event which doesn't already have them.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn default_attributes(mut self, default_attributes: DefaultAttributes) -> Self {
+        self.encoding.codec.default_attributes = default_attributes;
+        self
+    }
+
+    /// Sets the handling of events whose timestamp isn't a time value.
+    pub const fn invalid_timestamp(mut self, policy: InvalidTimestampPolicy) -> Self {
+        self.encoding.codec.invalid_timestamp = policy;
+        self
+    }
+
+    /// Sets the handling of events whose message isn't valid UTF-8.
+    pub const fn invalid_utf8(mut self, policy: InvalidUtf8Policy) -> Self {
+        self.encoding.codec.invalid_utf8 = policy;
+        self
+    }
+
+    /// Sends the whole event, serialized as JSON, as the message of each event without one.
+    pub const fn fill_missing_message(mut self, fill_missing_message: bool) -> Self {
+        self.encoding.codec.fill_missing_message = fill_missing_message;
+        self
+    }
+
+    /// Stamps each event without a timestamp with the current time.
+    pub const fn fill_missing_timestamp(mut self, fill_missing_timestamp: bool) -> Self {
+        self.encoding.codec.fill_missing_timestamp = fill_missing_timestamp;
+        self
+    }
+
+    /// Adds the contents of `field` to the tags of each event, in addition to its `ddtags` or the
+    /// default tags.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn ddtags_field(mut self, field: Option<String>) -> Self {
+        self.encoding.codec.ddtags_field = field;
+        self
+    }
+
+    /// Flattens the maps and arrays nested in each event into dotted top-level attributes.
+    pub const fn flatten_attributes(mut self, flatten_attributes: bool) -> Self {
+        self.encoding.codec.flatten_attributes = flatten_attributes;
+        self
+    }
+
+    /// Emits the number of events dropped by reason every `interval`.
+    pub fn drop_summary_interval(mut self, interval: Option<Duration>) -> Self {
+        self.encoding.codec.drop_summary = interval.map(DropSummary::spawn);
+        self
+    }
+
+    /// Drops events whose timestamp is older than `retention_days`, which Datadog would drop at
+    /// ingest.
+    pub fn target_retention_days(mut self, retention_days: Option<u32>) -> Self {
+        self.encoding.codec.retention_millis =
+            retention_days.map(|days| i64::from(days) * 24 * 60 * 60 * 1000);
+        self
+    }
+
+    /// Truncates string attribute values longer than `max_bytes`, except for the message.
+    pub const fn max_attribute_value_bytes(mut self, max_bytes: Option<usize>) -> Self {
+        self.encoding.codec.max_attribute_value_bytes = max_bytes;
+        self
+    }
+
+    /// Drops or truncates logs above the size limit of the Datadog Logs API.
+    pub const fn on_oversized(mut self, policy: OversizedLogPolicy) -> Self {
+        self.encoding.codec.on_oversized = policy;
+        self
+    }
+
+    /// Runs `hook` on each event just before it is encoded.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn pre_send_hook(mut self, hook: Option<PreSendHook>) -> Self {
+        self.encoding.codec.pre_send_hook = hook;
+        self
+    }
+
+    pub const fn compression(mut self, compression: RequestCompression) -> Self {
         self.compression = Some(compression);
         self
     }
 
+    /// Skips compression for partitions whose payloads recently barely compressed.
+    pub const fn smart_compression(mut self, smart_compression: bool) -> Self {
+        self.smart_compression = smart_compression;
+        self
+    }
+
+    /// Skips compression for batches in which most events have `field`, as it indicates they carry
+    /// already compressed data.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn precompressed_field(mut self, field: Option<String>) -> Self {
+        self.precompressed_field = field;
+        self
+    }
+
+    /// Stamps each event with the compression algorithm applied to its request, as `vector.codec`.
+    pub const fn add_codec_tag(mut self, add_codec_tag: bool) -> Self {
+        self.add_codec_tag = add_codec_tag;
+        self
+    }
+
+    /// Stamps each event with the time its request was built, in milliseconds since the epoch, as
+    /// `vector.sent_at`.
+    pub const fn add_send_timestamp(mut self, add_send_timestamp: bool) -> Self {
+        self.add_send_timestamp = add_send_timestamp;
+        self
+    }
+
+    /// Orders batches that are ready at the same time, such as while draining a backlog.
+    pub const fn drain_order(mut self, drain_order: DrainOrder) -> Self {
+        self.drain_order = drain_order;
+        self
+    }
+
+    pub const fn max_bandwidth_bytes_per_sec(mut self, max_bandwidth: Option<u64>) -> Self {
+        self.max_bandwidth_bytes_per_sec = max_bandwidth;
+        self
+    }
+
+    /// Batches events separately by whether their timestamp is within `hot_window` of the
+    /// current time.
+    pub const fn hot_window(mut self, hot_window: Option<Duration>) -> Self {
+        self.hot_window = hot_window;
+        self
+    }
+
+    /// Batches events separately by size class, given as byte size thresholds.
+    pub fn size_classes(mut self, thresholds: Option<Vec<usize>>) -> Self {
+        self.size_classes = thresholds.map(|mut thresholds| {
+            thresholds.sort_unstable();
+            thresholds
+        });
+        self
+    }
+
+    /// Ends the current batch of an API key whenever the value of `field` changes, so that each
+    /// batch holds consecutive events with the same value.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn flush_on_change_field(mut self, field: Option<String>) -> Self {
+        self.flush_on_change_field = field;
+        self
+    }
+
+    /// Ends the current batch of an API key whenever the integer value of `field` isn't one more
+    /// than that of the previous event, so that each batch holds a contiguous run of the sequence.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn flush_on_sequence_gap(mut self, field: Option<String>) -> Self {
+        self.flush_on_sequence_gap = field;
+        self
+    }
+
+    /// Batches events by their values of `fields`, in addition to their API key.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn partition_key_fields(mut self, fields: Vec<String>) -> Self {
+        self.partition_key_fields = fields;
+        self
+    }
+
+    /// Sends events to the intake `routes` maps them to, batching them by that intake in addition
+    /// to their API key.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn field_routes(mut self, routes: Option<FieldRoutes>) -> Self {
+        self.field_routes = routes;
+        self
+    }
+
+    /// Emits the number of events held by the sink, and their byte size, every `interval`.
+    pub const fn buffer_metrics_interval(mut self, interval: Option<Duration>) -> Self {
+        self.buffer_metrics_interval = interval;
+        self
+    }
+
+    /// Converts metrics into logs, with their tags in `ddtags`.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn metric_to_log(mut self, metric_to_log: Option<MetricToLog>) -> Self {
+        self.metric_to_log = metric_to_log;
+        self
+    }
+
+    /// Renders the API key of each event from `template`, keeping the key of events it fails to
+    /// render for.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn api_key_template(mut self, template: Option<Template>) -> Self {
+        self.api_key_template = template;
+        self
+    }
+
+    /// Sends a canary log when the sink starts, failing the sink if it isn't accepted.
+    pub const fn startup_canary(mut self, startup_canary: bool) -> Self {
+        self.startup_canary = startup_canary;
+        self
+    }
+
+    /// Sends a canary log along with the events each time `trigger` fires, reporting its outcome.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn canary_trigger(mut self, trigger: Option<CanaryTrigger>) -> Self {
+        self.canary_trigger = trigger;
+        self
+    }
+
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn secondary(mut self, secondary: Option<SecondarySite>) -> Self {
+        self.secondary = secondary;
+        self
+    }
+
+    /// Writes the uncompressed body of every request to a local file.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn mirror(mut self, mirror: Option<FileMirror>) -> Self {
+        self.mirror = mirror;
+        self
+    }
+
+    /// Coalesces the consecutive lines of one message into a single log before batching.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn multiline(mut self, multiline: Option<Multiline>) -> Self {
+        self.multiline = multiline;
+        self
+    }
+
+    /// Tracks every request with `verifier`, checking that its events are only acknowledged as
+    /// delivered once the service confirmed it.
+    #[allow(clippy::missing_const_for_fn)] // const cannot run destructor
+    pub fn ack_verifier(mut self, verifier: Option<Arc<AckVerifier>>) -> Self {
+        self.ack_verifier = verifier;
+        self
+    }
+
+    /// Encodes up to `concurrency` batches at a time, keeping the requests in batch order. Defaults
+    /// to 64.
+    pub const fn encode_concurrency(mut self, concurrency: Option<NonZeroUsize>) -> Self {
+        self.encode_concurrency = concurrency;
+        self
+    }
+
+    /// Holds back new requests while the bodies of the requests in flight total `max_bytes` or
+    /// more, in addition to the limit on their number.
+    pub const fn max_in_flight_bytes(mut self, max_bytes: Option<u64>) -> Self {
+        self.max_in_flight_bytes = max_bytes;
+        self
+    }
+
     pub fn build(self) -> LogSink<S> {
         LogSink {
             default_api_key: self.default_api_key,
+            api_key_template: self.api_key_template,
             encoding: self.encoding,
             acker: self.context.acker(),
             service: self.service,
             batch_settings: self.batch_settings,
             compression: self.compression.unwrap_or_default(),
+            smart_compression: self.smart_compression,
+            precompressed_field: self.precompressed_field,
+            drain_order: self.drain_order,
+            bandwidth_limiter: self.max_bandwidth_bytes_per_sec.map(BandwidthLimiter::new),
+            secondary: self.secondary,
+            mirror: self.mirror,
+            multiline: self.multiline,
+            hot_window: self.hot_window,
+            size_classes: self.size_classes,
+            metric_to_log: self.metric_to_log,
+            startup_canary: self.startup_canary,
+            canary_trigger: self.canary_trigger,
+            flush_on_change_field: self.flush_on_change_field,
+            flush_on_sequence_gap: self.flush_on_sequence_gap,
+            partition_key_fields: self.partition_key_fields,
+            field_routes: self.field_routes,
+            buffer_metrics_interval: self.buffer_metrics_interval,
+            add_codec_tag: self.add_codec_tag,
+            add_send_timestamp: self.add_send_timestamp,
+            ack_verifier: self.ack_verifier,
+            encode_concurrency: self.encode_concurrency.or(NonZeroUsize::new(64)),
+            in_flight_bytes: self
+                .max_in_flight_bytes
+                .map(|max_bytes| Arc::new(InFlightBytes::new(max_bytes))),
         }
     }
 }
@@ -97,6 +784,8 @@ pub struct LogSink<S> {
     /// otherwise we will see `Event` instances with no associated key. In that
     /// case we batch them by this default.
     default_api_key: Arc<str>,
+    /// Renders the API key of each event, if configured
+    api_key_template: Option<Template>,
     /// The ack system for this sink to vector's buffer mechanism
     acker: Acker,
     /// The API service
@@ -104,9 +793,53 @@ pub struct LogSink<S> {
     /// The encoding of payloads
     encoding: EncodingConfigFixed<DatadogLogsJsonEncoding>,
     /// The compression technique to use when building the request body
-    compression: Compression,
+    compression: RequestCompression,
+    /// Whether to skip compression for partitions whose payloads barely compress
+    smart_compression: bool,
+    /// Skips compression for batches in which most events have this field, if configured
+    precompressed_field: Option<String>,
+    /// The order of batches that are ready at the same time
+    drain_order: DrainOrder,
     /// Batch settings: timeout, max events, max bytes, etc.
     batch_settings: BatcherSettings,
+    /// Caps the rate at which request bodies are sent, if configured
+    bandwidth_limiter: Option<BandwidthLimiter>,
+    /// Receives a copy of every request, if dual shipping is configured
+    secondary: Option<SecondarySite>,
+    /// Writes the body of every request to a local file, if configured
+    mirror: Option<FileMirror>,
+    /// Coalesces the lines of multi-line messages, if configured
+    multiline: Option<Multiline>,
+    /// Splits batches into recent and backfill events, if time bucketing is configured
+    hot_window: Option<Duration>,
+    /// Splits batches by the byte size of events, if size classes are configured
+    size_classes: Option<Vec<usize>>,
+    /// Converts metrics into logs, if metrics are accepted
+    metric_to_log: Option<MetricToLog>,
+    /// Whether to send a canary log before any events
+    startup_canary: bool,
+    /// Sends a canary log each time it fires, if configured
+    canary_trigger: Option<CanaryTrigger>,
+    /// Ends batches when the value of this field changes, if configured
+    flush_on_change_field: Option<String>,
+    /// Ends batches at gaps in the sequence numbers of this field, if configured
+    flush_on_sequence_gap: Option<String>,
+    /// Splits batches by the values of these fields
+    partition_key_fields: Vec<String>,
+    /// Routes events to an intake by the value of a field, if configured
+    field_routes: Option<FieldRoutes>,
+    /// The interval at which the number of events held by the sink is emitted, if configured
+    buffer_metrics_interval: Option<Duration>,
+    /// Whether to stamp each event with the compression algorithm of its request
+    add_codec_tag: bool,
+    /// Whether to stamp each event with the time its request was built
+    add_send_timestamp: bool,
+    /// Checks that events are only acknowledged once delivered, if configured
+    ack_verifier: Option<Arc<AckVerifier>>,
+    /// The number of batches encoded at a time
+    encode_concurrency: Option<NonZeroUsize>,
+    /// Caps the total size of the request bodies in flight, if configured
+    in_flight_bytes: Option<Arc<InFlightBytes>>,
 }
 
 /// Customized encoding specific to the Datadog Logs sink, as the logs API only accepts JSON encoded
@@ -115,6 +848,83 @@ pub struct LogSink<S> {
 pub struct DatadogLogsJsonEncoding {
     log_schema: &'static LogSchema,
     inner: StandardEncodings,
+    format: DatadogLogsCodec,
+    cef: CefConfig,
+    deterministic_id: Option<DeterministicIdConfig>,
+    correlation_id_from_metadata: bool,
+    strip_control_chars: Option<ControlChars>,
+    implausible_timestamp_threshold_millis: Option<i128>,
+    max_attributes_per_event: Option<usize>,
+    dotted_key_policy: DottedKeyPolicy,
+    pre_send_hook: Option<PreSendHook>,
+    host_template: Option<Template>,
+    reserved_attributes: ReservedAttributesConfig,
+    default_attributes: DefaultAttributes,
+    invalid_timestamp: InvalidTimestampPolicy,
+    invalid_utf8: InvalidUtf8Policy,
+    fill_missing_message: bool,
+    fill_missing_timestamp: bool,
+    ddtags_field: Option<String>,
+    flatten_attributes: bool,
+    on_oversized: OversizedLogPolicy,
+    max_attribute_value_bytes: Option<usize>,
+    drop_summary: Option<Arc<DropSummary>>,
+    retention_millis: Option<i64>,
+}
+
+/// Attributes with a special meaning to Datadog, set on each event which doesn't already have them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DefaultAttributes {
+    pub ddsource: Option<String>,
+    /// The tags, in the comma-separated form Datadog expects.
+    pub ddtags: Option<String>,
+    /// Set as the host attribute.
+    pub hostname: Option<String>,
+    pub service: Option<String>,
+}
+
+/// A closure run on each event just before it is encoded.
+#[derive(Clone)]
+pub struct PreSendHook(Arc<dyn Fn(&mut Event) + Send + Sync>);
+
+impl PreSendHook {
+    pub fn new(hook: Box<dyn Fn(&mut Event) + Send + Sync>) -> Self {
+        Self(Arc::from(hook))
+    }
+}
+
+impl Debug for PreSendHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PreSendHook")
+    }
+}
+
+impl PartialEq for PreSendHook {
+    fn eq(&self, other: &Self) -> bool {
+        // Only the data pointers are compared, as vtables may be duplicated across codegen units.
+        std::ptr::eq(
+            Arc::as_ptr(&self.0) as *const u8,
+            Arc::as_ptr(&other.0) as *const u8,
+        )
+    }
+}
+
+/// The control characters stripped from string fields.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ControlChars {
+    /// All control characters, except tabs and newlines.
+    ExceptWhitespace,
+    /// All control characters.
+    All,
+}
+
+impl ControlChars {
+    fn strips(self, c: char) -> bool {
+        match self {
+            ControlChars::ExceptWhitespace => c.is_control() && c != '\t' && c != '\n',
+            ControlChars::All => c.is_control(),
+        }
+    }
 }
 
 impl Default for DatadogLogsJsonEncoding {
@@ -122,97 +932,863 @@ impl Default for DatadogLogsJsonEncoding {
         DatadogLogsJsonEncoding {
             log_schema: log_schema(),
             inner: StandardEncodings::Json,
+            format: DatadogLogsCodec::Json,
+            cef: CefConfig::default(),
+            deterministic_id: None,
+            correlation_id_from_metadata: false,
+            strip_control_chars: None,
+            implausible_timestamp_threshold_millis: None,
+            max_attributes_per_event: None,
+            dotted_key_policy: DottedKeyPolicy::Nest,
+            pre_send_hook: None,
+            host_template: None,
+            reserved_attributes: Default::default(),
+            default_attributes: Default::default(),
+            invalid_timestamp: InvalidTimestampPolicy::Remove,
+            invalid_utf8: InvalidUtf8Policy::Pass,
+            fill_missing_message: false,
+            fill_missing_timestamp: false,
+            ddtags_field: None,
+            flatten_attributes: false,
+            on_oversized: OversizedLogPolicy::Drop,
+            max_attribute_value_bytes: None,
+            drop_summary: None,
+            retention_millis: None,
         }
     }
 }
 
-impl Encoder<Vec<Event>> for DatadogLogsJsonEncoding {
-    fn encode_input(&self, mut input: Vec<Event>, writer: &mut dyn io::Write) -> io::Result<usize> {
-        for event in input.iter_mut() {
-            let log = event.as_mut_log();
-            log.rename_key_flat(self.log_schema.message_key(), "message");
-            log.rename_key_flat(self.log_schema.host_key(), "host");
-            if let Some(Value::Timestamp(ts)) = log.remove(self.log_schema.timestamp_key()) {
-                log.insert_flat("timestamp", Value::Integer(ts.timestamp_millis()));
+/// Computes a stable identifier for the given event from the values of `fields`.
+///
+/// Values are hashed via their JSON representation, which is stable as maps are ordered, and a
+/// missing field hashes differently from any present value.
+fn deterministic_id(log: &LogEvent, fields: &[String]) -> String {
+    let mut hasher = XxHash64::with_seed(0);
+    for field in fields {
+        hasher.write_usize(field.len());
+        hasher.write(field.as_bytes());
+        match log.get(field) {
+            Some(value) => {
+                let encoded = serde_json::to_vec(value).unwrap_or_default();
+                hasher.write_u8(1);
+                hasher.write_usize(encoded.len());
+                hasher.write(&encoded);
+            }
+            None => hasher.write_u8(0),
+        }
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+/// Removes the given control characters from all strings within `value`.
+///
+/// Non-UTF-8 strings are left untouched, as any change to them would be lossy.
+fn strip_control_chars(value: &mut Value, control_chars: ControlChars) {
+    match value {
+        Value::Bytes(bytes) => {
+            if let Ok(s) = std::str::from_utf8(bytes) {
+                if s.chars().any(|c| control_chars.strips(c)) {
+                    let stripped = s.chars().filter(|c| !control_chars.strips(*c));
+                    *bytes = stripped.collect::<String>().into();
+                }
+            }
+        }
+        Value::Map(map) => map
+            .values_mut()
+            .for_each(|value| strip_control_chars(value, control_chars)),
+        Value::Array(array) => array
+            .iter_mut()
+            .for_each(|value| strip_control_chars(value, control_chars)),
+        _ => {}
+    }
+}
+
+/// Applies `policy` to the keys containing dots of `attributes`, and of all maps nested within.
+///
+/// A sanitized key replaces any attribute that already has its name.
+fn apply_dotted_key_policy(attributes: &mut BTreeMap<String, Value>, policy: DottedKeyPolicy) {
+    let dotted = attributes
+        .keys()
+        .filter(|key| key.contains('.'))
+        .cloned()
+        .collect::<Vec<_>>();
+    for key in dotted {
+        let value = attributes.remove(&key).expect("dotted key was just listed");
+        match policy {
+            DottedKeyPolicy::Nest => {
+                attributes.insert(key, value);
             }
+            DottedKeyPolicy::Sanitize => {
+                attributes.insert(key.replace('.', "_"), value);
+            }
+            DottedKeyPolicy::Reject => emit!(&DatadogLogDottedKeyRejected { key: &key }),
         }
+    }
+
+    attributes
+        .values_mut()
+        .for_each(|value| apply_dotted_key_policy_nested(value, policy));
+}
 
-        self.inner.encode_input(input, writer)
+fn apply_dotted_key_policy_nested(value: &mut Value, policy: DottedKeyPolicy) {
+    match value {
+        Value::Map(map) => apply_dotted_key_policy(map, policy),
+        Value::Array(array) => array
+            .iter_mut()
+            .for_each(|value| apply_dotted_key_policy_nested(value, policy)),
+        _ => {}
     }
 }
 
+/// Warns if `timestamp_millis` is further than `threshold_millis` from the current time.
+///
+/// Such timestamps are usually produced with a precision other than milliseconds, which Datadog
+/// silently misplaces on the timeline, so the most likely precision is reported along with them.
+/// Integers are read as milliseconds, as that is how Datadog reads them.
+fn check_timestamp(timestamp_millis: i64, threshold_millis: i128) {
+    let now_millis = Utc::now().timestamp_millis();
+    if (i128::from(timestamp_millis) - i128::from(now_millis)).abs() <= threshold_millis {
+        return;
+    }
+
+    let ratio = timestamp_millis as f64 / now_millis as f64;
+    let suspected_precision = if ratio > 1e5 {
+        "nanoseconds"
+    } else if ratio > 1e2 {
+        "microseconds"
+    } else if ratio > 1e-4 && ratio < 1e-2 {
+        "seconds"
+    } else {
+        "unknown"
+    };
+    emit!(&DatadogLogsImplausibleTimestamp {
+        timestamp_millis,
+        now_millis,
+        suspected_precision,
+    });
+}
+
+/// Converts the value of `ddtags_field` into tags.
+///
+/// Each entry of a map becomes a `key:value` tag, and each element of an array a tag of its own.
+fn field_tags(value: Value) -> Vec<String> {
+    let tags = match value {
+        Value::Map(map) => map
+            .into_iter()
+            .map(|(key, value)| format!("{}:{}", key, value.to_string_lossy()))
+            .collect(),
+        Value::Array(array) => array.iter().map(Value::to_string_lossy).collect(),
+        Value::Null => Vec::new(),
+        value => vec![value.to_string_lossy()],
+    };
+    tags.into_iter().filter(|tag| !tag.is_empty()).collect()
+}
+
+/// Flattens the maps and arrays nested in the attributes of `log` into top-level attributes keyed
+/// by their dotted path, with array elements keyed by their index, such as `a.b` and `a.0`.
+///
+/// The `reserved` attributes are left as they are, as are empty maps and arrays, which would
+/// otherwise vanish. A flattened key replaces any attribute that already has its name.
+fn flatten_attributes(log: &mut LogEvent, reserved: &[&str]) {
+    let attributes = log.as_map_mut();
+    let nested = attributes
+        .iter()
+        .filter(|(key, value)| {
+            !reserved.contains(&key.as_str()) && matches!(value, Value::Map(_) | Value::Array(_))
+        })
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<_>>();
+    for key in nested {
+        let value = attributes.remove(&key).expect("nested key was just listed");
+        flatten_into(attributes, key, value);
+    }
+}
+
+fn flatten_into(attributes: &mut BTreeMap<String, Value>, key: String, value: Value) {
+    match value {
+        Value::Map(map) if !map.is_empty() => {
+            for (child, value) in map {
+                flatten_into(attributes, format!("{}.{}", key, child), value);
+            }
+        }
+        Value::Array(array) if !array.is_empty() => {
+            for (index, value) in array.into_iter().enumerate() {
+                flatten_into(attributes, format!("{}.{}", key, index), value);
+            }
+        }
+        value => {
+            attributes.insert(key, value);
+        }
+    }
+}
+
+/// Appended to attribute values truncated by `truncate_attribute_values`.
+const TRUNCATION_MARKER: &str = "...";
+
+/// Truncates the strings within `value` to `max_bytes`, including a marker showing they were
+/// truncated, without splitting UTF-8 characters.
+///
+/// `key` is the top-level attribute the strings belong to.
+fn truncate_attribute_values(key: &str, value: &mut Value, max_bytes: usize) {
+    match value {
+        Value::Bytes(bytes) if bytes.len() > max_bytes => {
+            // Values are cut short of the limit if the marker doesn't fit.
+            let marker = &TRUNCATION_MARKER.as_bytes()[..TRUNCATION_MARKER.len().min(max_bytes)];
+            let mut len = max_bytes - marker.len();
+            if let Ok(s) = std::str::from_utf8(bytes) {
+                while !s.is_char_boundary(len) {
+                    len -= 1;
+                }
+            }
+            emit!(&DatadogLogAttributeValueTruncated {
+                key,
+                byte_size: bytes.len(),
+                max_byte_size: max_bytes,
+            });
+            let mut truncated = BytesMut::with_capacity(max_bytes);
+            truncated.extend_from_slice(&bytes[..len]);
+            truncated.extend_from_slice(marker);
+            *bytes = truncated.freeze();
+        }
+        Value::Map(map) => map
+            .values_mut()
+            .for_each(|value| truncate_attribute_values(key, value, max_bytes)),
+        Value::Array(array) => array
+            .iter_mut()
+            .for_each(|value| truncate_attribute_values(key, value, max_bytes)),
+        _ => {}
+    }
+}
+
+/// Attributes with a special meaning to Datadog, which are never dropped by
+/// `truncate_attributes`.
+const RESERVED_ATTRIBUTES: &[&str] = &[
+    "ddsource",
+    "ddtags",
+    "host",
+    "message",
+    "service",
+    "status",
+    "timestamp",
+    "trace_id",
+];
+
+/// Drops top-level attributes beyond the first `max_attributes`, counting and always retaining
+/// the reserved attributes.
+fn truncate_attributes(log: &mut LogEvent, max_attributes: usize) {
+    let attributes = log.as_map_mut();
+    let len = attributes.len();
+    if len <= max_attributes {
+        return;
+    }
+
+    let reserved = attributes
+        .keys()
+        .filter(|key| RESERVED_ATTRIBUTES.contains(&key.as_str()))
+        .count();
+    let mut remaining = max_attributes.saturating_sub(reserved);
+    attributes.retain(|key, _| {
+        if RESERVED_ATTRIBUTES.contains(&key.as_str()) {
+            true
+        } else if remaining > 0 {
+            remaining -= 1;
+            true
+        } else {
+            false
+        }
+    });
+
+    emit!(&DatadogLogAttributesTruncated {
+        max_attributes,
+        dropped_attributes: len - attributes.len(),
+    });
+}
+
+/// Gets the size of `log` once encoded, if it may be above `MAX_LOG_BYTES`.
+///
+/// Escaping at most inflates the in-memory size six-fold, so only logs that could exceed the limit
+/// are encoded to measure them.
+fn oversized_encoded_size(log: &LogEvent) -> io::Result<Option<usize>> {
+    if log.size_of() * 6 <= MAX_LOG_BYTES {
+        return Ok(None);
+    }
+    let size = serde_json::to_vec(log)?.len();
+    Ok((size > MAX_LOG_BYTES).then(|| size))
+}
+
+/// Shortens the message of `log` by at least `excess` bytes, keeping valid UTF-8 intact.
+///
+/// Returns whether there was a message to shorten.
+fn truncate_message(log: &mut LogEvent, message_key: &str, excess: usize) -> bool {
+    match log.get_mut(message_key) {
+        Some(Value::Bytes(bytes)) => {
+            let mut len = bytes.len().saturating_sub(excess);
+            if let Ok(message) = std::str::from_utf8(bytes) {
+                while !message.is_char_boundary(len) {
+                    len -= 1;
+                }
+            }
+            *bytes = bytes.slice(..len);
+            true
+        }
+        _ => false,
+    }
+}
+
+impl DatadogLogsJsonEncoding {
+    /// The key of the message once remapped.
+    fn message_key(&self) -> &str {
+        match self.reserved_attributes.message.as_str() {
+            "" => self.log_schema.message_key(),
+            message => message,
+        }
+    }
+
+    /// The key of the host once remapped.
+    fn host_key(&self) -> &str {
+        match self.reserved_attributes.host.as_str() {
+            "" => self.log_schema.host_key(),
+            host => host,
+        }
+    }
+
+    /// Applies `on_oversized` to `log` if it is above the size limit, returning whether to keep it.
+    fn check_size(&self, log: &mut LogEvent) -> io::Result<bool> {
+        let byte_size = match oversized_encoded_size(log)? {
+            Some(byte_size) => byte_size,
+            None => return Ok(true),
+        };
+
+        let keep = self.on_oversized == OversizedLogPolicy::Truncate
+            && truncate_message(log, self.message_key(), byte_size - MAX_LOG_BYTES)
+            // Escaping may make the message take more space than the bytes it lost.
+            && oversized_encoded_size(log)?.is_none();
+        emit!(&DatadogLogEventTooLarge {
+            byte_size,
+            max_byte_size: MAX_LOG_BYTES,
+            dropped: !keep,
+        });
+        if let Some(summary) = self.drop_summary.as_ref().filter(|_| !keep) {
+            summary.record("oversized");
+        }
+        Ok(keep)
+    }
+}
+
+impl DatadogLogsJsonEncoding {
+    /// Prepares each of `input` to be sent, dropping those which can't or mustn't be sent.
+    ///
+    /// The dropped events are finalized as rejected, as they are never sent.
+    fn prepare(&self, input: Vec<Event>) -> io::Result<Vec<Event>> {
+        let mut events = Vec::with_capacity(input.len());
+        for mut event in input {
+            if self.normalize(&mut event)? {
+                events.push(event);
+            } else {
+                event.metadata().update_status(EventStatus::Rejected);
+            }
+        }
+        Ok(events)
+    }
+
+    /// Normalizes `event` for Datadog, returning whether to keep it.
+    fn normalize(&self, event: &mut Event) -> io::Result<bool> {
+        if let Some(PreSendHook(hook)) = &self.pre_send_hook {
+            hook(event);
+        }
+        let host = self.host_template.as_ref().map(|template| {
+            template.render_string(&*event).map_err(|error| {
+                emit!(&TemplateRenderingFailed {
+                    error,
+                    field: Some("host_template"),
+                    drop_event: false,
+                });
+            })
+        });
+        let log = event.as_mut_log();
+        if self.invalid_utf8 != InvalidUtf8Policy::Pass {
+            let replaced = match log.get(self.log_schema.message_key()) {
+                Some(Value::Bytes(message)) => match String::from_utf8_lossy(message) {
+                    Cow::Owned(replaced) => Some(replaced),
+                    Cow::Borrowed(_) => None,
+                },
+                _ => None,
+            };
+            if let Some(replaced) = replaced {
+                if self.invalid_utf8 == InvalidUtf8Policy::Drop {
+                    emit!(&DatadogLogEventDropped {
+                        reason: "invalid_utf8",
+                    });
+                    if let Some(summary) = &self.drop_summary {
+                        summary.record("invalid_utf8");
+                    }
+                    return Ok(false);
+                }
+                log.insert(self.log_schema.message_key(), replaced);
+            }
+        }
+        if let Some(control_chars) = self.strip_control_chars {
+            log.as_map_mut()
+                .values_mut()
+                .for_each(|value| strip_control_chars(value, control_chars));
+        }
+        if self.dotted_key_policy != DottedKeyPolicy::Nest {
+            apply_dotted_key_policy(log.as_map_mut(), self.dotted_key_policy);
+        }
+        if let Some(config) = &self.deterministic_id {
+            let id = deterministic_id(log, &config.fields);
+            log.insert(config.target.as_str(), id);
+        }
+        if self.correlation_id_from_metadata {
+            let id = match log.metadata().correlation_id().clone() {
+                Some(id) => id.to_string(),
+                None => {
+                    log.insert_flat("correlation_id_synthetic", true);
+                    Uuid::new_v4().to_string()
+                }
+            };
+            log.insert_flat("correlation_id", id);
+        }
+        if let Some(threshold_millis) = self.implausible_timestamp_threshold_millis {
+            match log.get(self.log_schema.timestamp_key()) {
+                Some(Value::Integer(timestamp)) => check_timestamp(*timestamp, threshold_millis),
+                Some(Value::Timestamp(ts)) => {
+                    check_timestamp(ts.timestamp_millis(), threshold_millis)
+                }
+                _ => {}
+            }
+        }
+        if let Some(retention_millis) = self.retention_millis {
+            let timestamp_millis = match log.get(self.log_schema.timestamp_key()) {
+                Some(Value::Integer(timestamp)) => Some(*timestamp),
+                Some(Value::Timestamp(ts)) => Some(ts.timestamp_millis()),
+                _ => None,
+            };
+            let oldest_millis = Utc::now().timestamp_millis() - retention_millis;
+            if matches!(timestamp_millis, Some(timestamp) if timestamp < oldest_millis) {
+                emit!(&DatadogLogEventDropped {
+                    reason: "beyond_retention",
+                });
+                if let Some(summary) = &self.drop_summary {
+                    summary.record("beyond_retention");
+                }
+                return Ok(false);
+            }
+        }
+        if self.fill_missing_message && !log.as_map().contains_key(self.log_schema.message_key()) {
+            // The event is serialized before any of the default attributes are added.
+            let message = serde_json::to_string(log.as_map())?;
+            log.insert_flat(self.log_schema.message_key(), message);
+        }
+        if self.fill_missing_timestamp
+            && !log.as_map().contains_key(self.log_schema.timestamp_key())
+        {
+            log.insert_flat(
+                self.log_schema.timestamp_key(),
+                Value::Timestamp(Utc::now()),
+            );
+        }
+        log.rename_key_flat(self.log_schema.message_key(), self.message_key());
+        log.rename_key_flat(self.log_schema.host_key(), self.host_key());
+        // The plain host field is kept if the template can't be rendered.
+        if let Some(Ok(host)) = host {
+            log.insert_flat(self.host_key(), host);
+        }
+        // Attributes already set on the event take precedence over the defaults.
+        let defaults = &self.default_attributes;
+        if let Some(field) = &self.ddtags_field {
+            let tags = log
+                .remove(field.as_str())
+                .map(field_tags)
+                .unwrap_or_default();
+            if !tags.is_empty() {
+                let base = match log.get("ddtags") {
+                    Some(ddtags) => Some(ddtags.to_string_lossy()),
+                    None => defaults.ddtags.clone(),
+                };
+                let ddtags = base
+                    .into_iter()
+                    .filter(|base| !base.is_empty())
+                    .chain(tags)
+                    .collect::<Vec<_>>()
+                    .join(",");
+                log.insert_flat("ddtags", ddtags);
+            }
+        }
+        for (key, value) in [
+            ("ddsource", &defaults.ddsource),
+            ("ddtags", &defaults.ddtags),
+            ("service", &defaults.service),
+            (self.host_key(), &defaults.hostname),
+        ] {
+            if let Some(value) = value.as_ref().filter(|_| !log.contains(key)) {
+                log.insert_flat(key, value.clone());
+            }
+        }
+        let timestamp_key = self.reserved_attributes.timestamp.as_str();
+        if !timestamp_key.is_empty() {
+            match log.remove(self.log_schema.timestamp_key()) {
+                Some(Value::Timestamp(ts)) => {
+                    log.insert_flat(timestamp_key, Value::Integer(ts.timestamp_millis()));
+                }
+                Some(value) => {
+                    emit!(&DatadogLogsInvalidTimestamp {
+                        kind: value.kind(),
+                        policy: self.invalid_timestamp.as_str(),
+                        dropped: self.invalid_timestamp == InvalidTimestampPolicy::Drop,
+                    });
+                    match self.invalid_timestamp {
+                        InvalidTimestampPolicy::Remove => {}
+                        InvalidTimestampPolicy::Now => {
+                            let now = Value::Integer(Utc::now().timestamp_millis());
+                            log.insert_flat(timestamp_key, now);
+                        }
+                        InvalidTimestampPolicy::Drop => {
+                            if let Some(summary) = &self.drop_summary {
+                                summary.record("invalid_timestamp");
+                            }
+                            return Ok(false);
+                        }
+                        InvalidTimestampPolicy::Leave => {
+                            log.insert_flat(timestamp_key, value);
+                        }
+                    }
+                }
+                None => {}
+            }
+        }
+        if self.flatten_attributes {
+            let timestamp_key = match timestamp_key {
+                "" => self.log_schema.timestamp_key(),
+                timestamp_key => timestamp_key,
+            };
+            flatten_attributes(log, &[self.message_key(), self.host_key(), timestamp_key]);
+        }
+        if let Some(max_bytes) = self.max_attribute_value_bytes {
+            // The message is only subject to the size limit of the whole log.
+            let message_key = self.message_key();
+            log.as_map_mut()
+                .iter_mut()
+                .filter(|(key, _)| key.as_str() != message_key)
+                .for_each(|(key, value)| truncate_attribute_values(key, value, max_bytes));
+        }
+        if let Some(max_attributes) = self.max_attributes_per_event {
+            truncate_attributes(log, max_attributes);
+        }
+        self.check_size(log)
+    }
+
+    /// Writes `events` in the configured format, returning the number of bytes written.
+    fn write(&self, events: Vec<Event>, writer: &mut dyn io::Write) -> io::Result<usize> {
+        match self.format {
+            DatadogLogsCodec::Json => self.inner.encode_input(events, writer),
+            DatadogLogsCodec::Ndjson => StandardEncodings::Ndjson.encode_input(events, writer),
+            DatadogLogsCodec::Binary => encode_frames(events, writer),
+            DatadogLogsCodec::Cef => encode_cef(&self.cef, self.message_key(), events, writer),
+        }
+    }
+}
+
+impl Encoder<Vec<Event>> for DatadogLogsJsonEncoding {
+    fn encode_input(&self, input: Vec<Event>, writer: &mut dyn io::Write) -> io::Result<usize> {
+        let events = self.prepare(input)?;
+        self.write(events, writer)
+    }
+}
+
+/// Writes each event in its protobuf encoding, prefixed by the length of that encoding as a
+/// big-endian `u32`.
+fn encode_frames(events: Vec<Event>, writer: &mut dyn io::Write) -> io::Result<usize> {
+    let mut written = 0;
+    for event in events {
+        let frame = EventWrapper::from(event).encode_to_vec();
+        writer.write_all(&(frame.len() as u32).to_be_bytes())?;
+        writer.write_all(&frame)?;
+        written += 4 + frame.len();
+    }
+    Ok(written)
+}
+
 #[derive(Debug, Snafu)]
 pub enum RequestBuildError {
     #[snafu(display("Encoded payload is greater than the max limit."))]
-    PayloadTooBig,
+    PayloadTooBig { events: usize },
     #[snafu(display("Failed to build payload with error: {}", error))]
-    Io { error: std::io::Error },
+    Io {
+        error: std::io::Error,
+        events: usize,
+    },
+}
+
+impl RequestBuildError {
+    /// Gets the telemetry-friendly string version of this error.
+    pub const fn as_error_type(&self) -> &'static str {
+        match self {
+            Self::PayloadTooBig { .. } => "payload_too_large",
+            Self::Io { .. } => "encode_failed",
+        }
+    }
+
+    /// Gets the number of events of the batch which failed to build.
+    pub const fn dropped_events(&self) -> usize {
+        match self {
+            Self::PayloadTooBig { events } | Self::Io { events, .. } => *events,
+        }
+    }
+}
+
+/// An encoded payload, along with the compression that was applied to it and the events it holds.
+///
+/// Events dropped while encoding the payload aren't part of it.
+struct LogPayload {
+    body: Bytes,
+    compression: RequestCompression,
+    batch_size: usize,
+    finalizers: EventFinalizers,
+    events_byte_size: usize,
 }
 
-impl From<io::Error> for RequestBuildError {
-    fn from(error: io::Error) -> RequestBuildError {
-        RequestBuildError::Io { error }
+impl From<Vec<u8>> for LogPayload {
+    fn from(body: Vec<u8>) -> Self {
+        Self {
+            body: Bytes::from(body),
+            compression: RequestCompression::None,
+            batch_size: 0,
+            finalizers: EventFinalizers::default(),
+            events_byte_size: 0,
+        }
     }
 }
 
 struct LogRequestBuilder {
     default_api_key: Arc<str>,
     encoding: EncodingConfigFixed<DatadogLogsJsonEncoding>,
-    compression: Compression,
+    compression: RequestCompression,
+    smart_compression: Option<SmartCompression>,
+    precompressed_field: Option<String>,
+    add_codec_tag: bool,
+    add_send_timestamp: bool,
+    body_buffer: BodyBuffer,
+}
+
+/// Everything a request is built from besides its payload.
+struct RequestMetadata {
+    api_key: Arc<str>,
+    route: Option<Uri>,
+    time_bucket: Option<TimeBucket>,
+    /// The number of events read from the source the request is done with, which includes those
+    /// dropped while encoding its payload.
+    source_events: usize,
 }
 
-impl RequestBuilder<(Option<Arc<str>>, Vec<Event>)> for LogRequestBuilder {
-    type Metadata = (Arc<str>, usize, EventFinalizers, usize);
+impl RequestBuilder<(PartitionKey, Vec<Event>)> for LogRequestBuilder {
+    type Metadata = RequestMetadata;
     type Events = Vec<Event>;
     type Encoder = EncodingConfigFixed<DatadogLogsJsonEncoding>;
-    type Payload = Vec<u8>;
+    type Payload = LogPayload;
     type Request = LogApiRequest;
     type Error = RequestBuildError;
 
     fn compression(&self) -> Compression {
-        self.compression
+        // This is only used by the default `encode_events`, which is overridden so that brotli and
+        // zstd, which the common `Compression` lacks, can be applied.
+        Compression::None
     }
 
     fn encoder(&self) -> &Self::Encoder {
         &self.encoding
     }
 
-    fn split_input(&self, input: (Option<Arc<str>>, Vec<Event>)) -> (Self::Metadata, Self::Events) {
-        let (api_key, mut events) = input;
+    fn split_input(&self, input: (PartitionKey, Vec<Event>)) -> (Self::Metadata, Self::Events) {
+        let (key, events) = input;
+        let metadata = RequestMetadata {
+            api_key: key
+                .api_key
+                .unwrap_or_else(|| Arc::clone(&self.default_api_key)),
+            route: key.route,
+            time_bucket: key.time_bucket,
+            source_events: events
+                .iter()
+                .map(|event| event.metadata().source_events())
+                .sum(),
+        };
+        (metadata, events)
+    }
+
+    fn encode_events(&self, mut events: Self::Events) -> Result<Self::Payload, Self::Error> {
+        // The Datadog Logs API has a limit on uncompressed data, so we can't use the default
+        // implementation of this method, which only knows the compressed size.
+        let partition = events
+            .first()
+            .and_then(|event| event.metadata().datadog_api_key().clone());
+        let precompressed = self.precompressed_field.as_ref().map_or(false, |field| {
+            let marked = events
+                .iter()
+                .filter(|event| event.as_log().contains(field.as_str()))
+                .count();
+            marked * 2 > events.len()
+        });
+        // Compression is skipped if it isn't worthwhile for this batch or partition. This is decided
+        // up front, so that the events can be tagged with it.
+        let compression = match &self.smart_compression {
+            _ if precompressed => RequestCompression::None,
+            Some(smart) if !smart.should_compress(&partition) => RequestCompression::None,
+            _ => self.compression,
+        };
+        if self.add_codec_tag {
+            for event in events.iter_mut() {
+                event
+                    .as_mut_log()
+                    .insert_flat("vector.codec", compression.name());
+            }
+        }
+        if self.add_send_timestamp {
+            // All events of the request share the same time, which is kept across retries.
+            let sent_at = Value::Integer(Utc::now().timestamp_millis());
+            for event in events.iter_mut() {
+                event
+                    .as_mut_log()
+                    .insert_flat("vector.sent_at", sent_at.clone());
+            }
+        }
+
         let events_len = events.len();
+        let io_error = |error| RequestBuildError::Io {
+            error,
+            events: events_len,
+        };
+        // The events are prepared before they are counted, so that those dropped meanwhile are
+        // neither counted as sent nor finalized along with the request.
+        for event in events.iter_mut() {
+            self.encoding.apply_rules(event);
+        }
+        let codec = self.encoding.codec();
+        let mut events = codec.prepare(events).map_err(io_error)?;
+        let batch_size = events.len();
         let finalizers = events.take_finalizers();
         let events_byte_size = events.size_of();
 
-        let api_key = api_key.unwrap_or_else(|| Arc::clone(&self.default_api_key));
-        ((api_key, events_len, finalizers, events_byte_size), events)
-    }
-
-    fn encode_events(&self, events: Self::Events) -> Result<Self::Payload, Self::Error> {
-        // We need to first serialize the payload separately so that we can figure out how big it is
-        // before compression.  The Datadog Logs API has a limit on uncompressed data, so we can't
-        // use the default implementation of this method.
-        let mut buf = Vec::new();
-        let n = self.encoder().encode_input(events, &mut buf)?;
+        // Events are encoded straight into the compressor, so that the uncompressed payload is never
+        // held in memory alongside the compressed one. Its size is known from the bytes written.
+        let mut compressor =
+            RequestCompressor::with_buffer(compression, self.body_buffer.take().writer());
+        let n = codec.write(events, &mut compressor).map_err(io_error)?;
         if n > MAX_PAYLOAD_BYTES {
-            return Err(RequestBuildError::PayloadTooBig);
+            return Err(RequestBuildError::PayloadTooBig { events: events_len });
         }
+        let body = self
+            .body_buffer
+            .freeze(compressor.into_inner().into_inner());
+        emit!(&DatadogLogsRequestCompressed {
+            compression: compression.name(),
+            uncompressed_bytes: n,
+            compressed_bytes: body.len(),
+        });
 
-        // Now just compress it like normal.
-        let mut compressor = Compressor::from(self.compression);
-        let _ = compressor.write_all(&buf)?;
+        if let Some(smart) = self
+            .smart_compression
+            .as_ref()
+            .filter(|_| compression.is_compressed())
+        {
+            smart.record(&partition, n, body.len());
+        }
 
-        Ok(compressor.into_inner())
+        Ok(LogPayload {
+            body,
+            compression,
+            batch_size,
+            finalizers,
+            events_byte_size,
+        })
     }
 
     fn build_request(&self, metadata: Self::Metadata, payload: Self::Payload) -> Self::Request {
-        let (api_key, batch_size, finalizers, events_byte_size) = metadata;
         LogApiRequest {
-            batch_size,
-            api_key,
-            compression: self.compression,
-            body: payload,
-            finalizers,
-            events_byte_size,
+            batch_size: payload.batch_size,
+            source_events: metadata.source_events,
+            api_key: metadata.api_key,
+            uri: metadata.route,
+            time_bucket: metadata.time_bucket,
+            compression: payload.compression,
+            body: payload.body,
+            finalizers: payload.finalizers,
+            events_byte_size: payload.events_byte_size,
+            ack_id: None,
+        }
+    }
+}
+
+/// Reports how full a flushed batch is relative to the batch limits.
+///
+/// The batcher doesn't tell why a batch was flushed, so a batch is taken to have been flushed by its
+/// size when it couldn't have held another event of its average size, and by its timeout otherwise.
+fn emit_batch_utilization(settings: &BatcherSettings, events: &[Event]) {
+    if events.is_empty() {
+        return;
+    }
+    let byte_size = events.iter().map(Event::size_of).sum::<usize>();
+    let full = events.len() >= settings.item_limit
+        || byte_size + byte_size / events.len() > settings.size_limit;
+    emit!(&DatadogLogsBatchFlushed {
+        events_ratio: events.len() as f64 / settings.item_limit as f64,
+        bytes_ratio: byte_size as f64 / settings.size_limit as f64,
+        trigger: if full { "size" } else { "timeout" },
+    });
+}
+
+/// Sets the API key of `event` from `template`, keeping its current key if the template fails to
+/// render.
+fn render_api_key(template: &Template, event: &mut Event) {
+    match template.render_string(&*event) {
+        Ok(api_key) => {
+            event
+                .metadata_mut()
+                .set_datadog_api_key(Some(Arc::from(api_key)));
+        }
+        Err(error) => emit!(&TemplateRenderingFailed {
+            error,
+            field: Some("default_api_key"),
+            drop_event: false,
+        }),
+    }
+}
+
+/// Sends a single well-known log through the request builder and service, to verify the whole
+/// delivery path before any events are sent.
+async fn send_canary<S>(service: &mut S, request_builder: &LogRequestBuilder) -> Result<(), ()>
+where
+    S: Service<LogApiRequest>,
+    S::Response: DriverResponse,
+    S::Error: Debug,
+{
+    let canary = canary_event("Startup canary sent by Vector.");
+    let (metadata, events) = request_builder.split_input((PartitionKey::default(), vec![canary]));
+    let payload = request_builder
+        .encode_events(events)
+        .map_err(|error| error!(message = "Failed to build startup canary.", %error))?;
+    let request = request_builder.build_request(metadata, payload);
+
+    let service = service.ready().await.map_err(|error| {
+        error!(
+            message = "Service failed before sending startup canary.",
+            ?error
+        )
+    })?;
+    match service.call(request).await {
+        Ok(response) if response.event_status() == EventStatus::Delivered => {
+            info!(message = "Startup canary was accepted.");
+            Ok(())
+        }
+        Ok(response) => {
+            error!(
+                message = "Startup canary was rejected.",
+                status = ?response.event_status()
+            );
+            Err(())
+        }
+        Err(error) => {
+            error!(message = "Failed to send startup canary.", ?error);
+            Err(())
         }
     }
 }
@@ -227,28 +1803,155 @@ where
     async fn run_inner(self: Box<Self>, input: BoxStream<'_, Event>) -> Result<(), ()> {
         let default_api_key = Arc::clone(&self.default_api_key);
 
-        let partitioner = EventPartitioner::default();
+        let partitioner = EventPartitioner {
+            routes: self.field_routes,
+            hot_window: self.hot_window.map(|hot_window| {
+                chrono::Duration::from_std(hot_window)
+                    .unwrap_or_else(|_| chrono::Duration::max_value())
+            }),
+            size_classes: self.size_classes,
+            flush_on_change: self.flush_on_change_field.map(FlushOnChange::new),
+            flush_on_sequence_gap: self.flush_on_sequence_gap.map(FlushOnSequenceGap::new),
+            key_fields: self.partition_key_fields,
+        };
 
-        let builder_limit = NonZeroUsize::new(64);
+        let builder_limit = self.encode_concurrency;
+        let metric_drop_summary = self.encoding.codec.drop_summary.clone();
         let request_builder = LogRequestBuilder {
             default_api_key,
             encoding: self.encoding,
             compression: self.compression,
+            smart_compression: self.smart_compression.then(SmartCompression::default),
+            precompressed_field: self.precompressed_field,
+            add_codec_tag: self.add_codec_tag,
+            add_send_timestamp: self.add_send_timestamp,
+            body_buffer: BodyBuffer::default(),
         };
+        let mut service = self.service;
+        if self.startup_canary {
+            send_canary(&mut service, &request_builder).await?;
+        }
+        let bandwidth_limiter = self.bandwidth_limiter;
+        let secondary = self.secondary;
+        let mirror = self.mirror;
 
+        let drain_order = self.drain_order;
+        let batch_settings = self.batch_settings;
+        let metric_to_log = self.metric_to_log;
+        let metric_acker = self.acker.clone();
+        let buffer_depth = self.buffer_metrics_interval.map(BufferDepth::spawn);
+        let service = BufferDepthService::new(service, buffer_depth.clone());
+        let ack_verifier = self.ack_verifier;
+        let service = AckVerificationService::new(service, ack_verifier.clone());
+        let in_flight_bytes = self.in_flight_bytes;
+        let service = InFlightBytesService::new(service, in_flight_bytes.clone());
+        let received_depth = buffer_depth.clone();
+        let batched_depth = buffer_depth.clone();
+        let input = match self.api_key_template {
+            Some(template) => input
+                .map(move |mut event| {
+                    render_api_key(&template, &mut event);
+                    event
+                })
+                .boxed(),
+            None => input,
+        };
+        let input = match self.multiline {
+            Some(multiline) => multiline.coalesce(input).boxed(),
+            None => input,
+        };
+        let input = match self.canary_trigger {
+            Some(trigger) => trigger.inject(input).boxed(),
+            None => input,
+        };
         let sink = input
+            .filter_map(move |event| {
+                future::ready(match event {
+                    Event::Metric(metric) => {
+                        let metadata = metric.metadata().clone();
+                        let log = match &metric_to_log {
+                            Some(metric_to_log) => {
+                                metric_into_log(metric_to_log, metric).ok_or("metric_not_converted")
+                            }
+                            None => Err("metric_to_log_unset"),
+                        };
+                        log.map_err(|reason| {
+                            reject_metric(
+                                &metadata,
+                                reason,
+                                &metric_acker,
+                                metric_drop_summary.as_deref(),
+                            )
+                        })
+                        .ok()
+                        .map(Event::Log)
+                    }
+                    event => Some(event),
+                })
+            })
+            .inspect(move |event| {
+                if let Some(depth) = &received_depth {
+                    depth.add(1, event.size_of());
+                }
+            })
             .batched_partitioned(partitioner, self.batch_settings)
+            .ready_chunks(DRAIN_WINDOW)
+            .flat_map(move |mut batches| {
+                for (_, events) in &batches {
+                    emit_batch_utilization(&batch_settings, events);
+                }
+                arrange_batches(drain_order, &mut batches);
+                if let Some(depth) = &batched_depth {
+                    // The events are counted again as part of their request once it is built.
+                    for (_, events) in &batches {
+                        depth.remove(events.len(), events.iter().map(Event::size_of).sum());
+                    }
+                }
+                stream::iter(batches)
+            })
             .request_builder(builder_limit, request_builder)
             .filter_map(|request| async move {
                 match request {
-                    Err(e) => {
-                        error!("Failed to build Datadog Logs request: {:?}.", e);
+                    Err(error) => {
+                        emit!(&DatadogLogEncodingError {
+                            error: &error,
+                            error_type: error.as_error_type(),
+                            dropped_events: error.dropped_events(),
+                        });
                         None
                     }
                     Ok(req) => Some(req),
                 }
             })
-            .into_driver(self.service, self.acker);
+            .inspect(move |request| {
+                if request.batch_size == 0 {
+                    return;
+                }
+                if let Some(secondary) = &secondary {
+                    secondary.ship(request);
+                }
+                if let Some(mirror) = &mirror {
+                    mirror.mirror(request);
+                }
+            })
+            .then(move |mut request| {
+                let bandwidth_limiter = bandwidth_limiter.clone();
+                let in_flight_bytes = in_flight_bytes.clone();
+                if let Some(depth) = &buffer_depth {
+                    depth.add(request.batch_size, request.events_byte_size);
+                }
+                request.ack_id = ack_verifier.as_ref().map(|verifier| verifier.enqueue());
+                async move {
+                    if let Some(limiter) = bandwidth_limiter {
+                        limiter.acquire(request.body.len()).await;
+                    }
+                    if let Some(in_flight_bytes) = in_flight_bytes {
+                        in_flight_bytes.acquire(request.body.len()).await;
+                    }
+                    request
+                }
+            })
+            .into_driver(service, self.acker);
 
         sink.run().await
     }
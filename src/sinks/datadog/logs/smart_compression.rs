@@ -0,0 +1,75 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+/// The number of recent compression ratios the decision is based on.
+const WINDOW: usize = 5;
+/// The compression ratio above which compressing is not worth the CPU time, i.e. compression
+/// saves less than a fifth of the bandwidth.
+const POOR_RATIO: f64 = 0.8;
+/// While compression is skipped, every `SAMPLE_INTERVAL`th payload is still compressed, so that
+/// compression is resumed if the payloads become more compressible.
+const SAMPLE_INTERVAL: usize = 10;
+
+/// Decides, per partition, whether compressing payloads is worthwhile.
+///
+/// The ratios of compressed to uncompressed size of the most recent payloads of each partition are
+/// tracked, and compression is skipped for partitions whose payloads recently barely compressed.
+#[derive(Debug, Default)]
+pub struct SmartCompression {
+    partitions: Mutex<HashMap<Option<Arc<str>>, PartitionState>>,
+}
+
+#[derive(Debug, Default)]
+struct PartitionState {
+    ratios: VecDeque<f64>,
+    skipped: usize,
+}
+
+impl PartitionState {
+    fn is_poor(&self) -> bool {
+        self.ratios.len() == WINDOW && self.ratios.iter().sum::<f64>() / WINDOW as f64 > POOR_RATIO
+    }
+}
+
+impl SmartCompression {
+    /// Gets whether the next payload of `partition` should be compressed.
+    pub fn should_compress(&self, partition: &Option<Arc<str>>) -> bool {
+        let mut partitions = self
+            .partitions
+            .lock()
+            .expect("smart compression mutex poisoned");
+        let state = partitions.entry(partition.clone()).or_default();
+        if !state.is_poor() {
+            return true;
+        }
+
+        state.skipped += 1;
+        if state.skipped >= SAMPLE_INTERVAL {
+            state.skipped = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Records the sizes of a payload of `partition` before and after compression.
+    pub fn record(&self, partition: &Option<Arc<str>>, uncompressed: usize, compressed: usize) {
+        if uncompressed == 0 {
+            return;
+        }
+
+        let mut partitions = self
+            .partitions
+            .lock()
+            .expect("smart compression mutex poisoned");
+        let state = partitions.entry(partition.clone()).or_default();
+        if state.ratios.len() == WINDOW {
+            state.ratios.pop_front();
+        }
+        state
+            .ratios
+            .push_back(compressed as f64 / uncompressed as f64);
+    }
+}
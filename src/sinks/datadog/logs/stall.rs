@@ -0,0 +1,78 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::internal_events::DatadogLogPartitionStalled;
+
+/// Tracks the last successful delivery of each partition, to detect a partition which is stuck
+/// while the others are delivered.
+///
+/// Partitions are identified by their API key, and a partition which never succeeded is measured
+/// from its first delivery attempt.
+#[derive(Debug)]
+pub struct StallTracker {
+    threshold: Duration,
+    partitions: Mutex<HashMap<Arc<str>, PartitionState>>,
+}
+
+#[derive(Debug)]
+struct PartitionState {
+    first_attempt: Instant,
+    last_success: Option<Instant>,
+}
+
+impl StallTracker {
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            partitions: Mutex::default(),
+        }
+    }
+
+    /// Records the outcome of a delivery attempt for the partition of `api_key`.
+    pub fn record(&self, api_key: &Arc<str>, delivered: bool) {
+        let now = Instant::now();
+        let stalled_for = {
+            let mut partitions = self
+                .partitions
+                .lock()
+                .expect("stall tracker mutex poisoned");
+            let state = partitions
+                .entry(Arc::clone(api_key))
+                .or_insert(PartitionState {
+                    first_attempt: now,
+                    last_success: None,
+                });
+            if delivered {
+                state.last_success = Some(now);
+                return;
+            }
+
+            let stalled_for = now - state.last_success.unwrap_or(state.first_attempt);
+            let others_delivered = partitions.iter().any(|(key, state)| {
+                key != api_key
+                    && state
+                        .last_success
+                        .map_or(false, |last_success| now - last_success <= self.threshold)
+            });
+            (stalled_for > self.threshold && others_delivered).then(|| stalled_for)
+        };
+
+        if let Some(stalled_for) = stalled_for {
+            emit!(&DatadogLogPartitionStalled {
+                partition: &redact(api_key),
+                stalled_secs: stalled_for.as_secs(),
+            });
+        }
+    }
+}
+
+/// Identifies a partition by the end of its API key, so that the key itself isn't logged.
+fn redact(api_key: &str) -> String {
+    let suffix = api_key.chars().rev().take(4).collect::<Vec<_>>();
+    format!("...{}", suffix.into_iter().rev().collect::<String>())
+}
@@ -1,25 +1,61 @@
 #![allow(clippy::print_stdout)] // tests
 
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
 use bytes::Bytes;
-use chrono::Utc;
+use chrono::{TimeZone, Utc};
 use futures::{
-    channel::mpsc::{Receiver, TryRecvError},
-    stream, StreamExt,
+    channel::mpsc::{self, Receiver, TryRecvError},
+    future, stream, FutureExt, SinkExt, StreamExt, TryFutureExt,
 };
 use http::request::Parts;
-use hyper::StatusCode;
-use indoc::indoc;
-use vector_core::event::{BatchNotifier, BatchStatus, Event};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use indoc::{formatdoc, indoc};
+use prost::Message;
+use rand::{thread_rng, Rng};
+use stream_cancel::{Trigger, Tripwire};
+use vector_core::{
+    buffers::Acker,
+    config::{log_schema, proxy::ProxyConfig},
+    event::{
+        proto::EventWrapper, BatchNotifier, BatchStatus, Event, Metric, MetricKind, MetricValue,
+    },
+    event_test_util::{clear_recorded_events, contains_name},
+};
 
+use super::{
+    canary::CanaryTrigger,
+    config::DatadogLogsDefaultBatchSettings,
+    service::{DatadogLogsError, LogApiService},
+    sink::LogSinkBuilder,
+};
 use crate::{
-    config::SinkConfig,
+    config::{SinkConfig, SinkContext},
+    http::HttpClient,
+    metrics::Controller,
     sinks::{
         datadog::logs::DatadogLogsConfig,
-        util::test::{build_test_server_status, load_sink},
+        util::{
+            test::{build_test_server_status, load_sink},
+            BatchConfig, Compression, Compressor,
+        },
+        VectorSink,
+    },
+    test_util::{components, next_addr, random_lines_with_stream, temp_file},
+    tls::{
+        MaybeTlsSettings, TlsConfig, TlsOptions, TEST_PEM_CA_PATH, TEST_PEM_CRT_PATH,
+        TEST_PEM_KEY_PATH,
     },
-    test_util::{next_addr, random_lines_with_stream},
 };
 
 // The sink must support v1 and v2 API endpoints which have different codes for
@@ -31,6 +67,7 @@ enum ApiStatus {
     OKv2,
     Forbiddenv1,
     Forbiddenv2,
+    BadRequestv2,
 }
 
 fn test_server(
@@ -45,6 +82,7 @@ fn test_server(
         ApiStatus::OKv1 => StatusCode::OK,
         ApiStatus::OKv2 => StatusCode::ACCEPTED,
         ApiStatus::Forbiddenv1 | ApiStatus::Forbiddenv2 => StatusCode::FORBIDDEN,
+        ApiStatus::BadRequestv2 => StatusCode::BAD_REQUEST,
     };
 
     // NOTE: we pass `Trigger` out to the caller even though this suite never
@@ -53,6 +91,141 @@ fn test_server(
     build_test_server_status(addr, status)
 }
 
+/// Starts a test server whose responses are generated by `responder`
+///
+/// Unlike `test_server`, the response may depend on the request, and every
+/// request is forwarded to the returned `Receiver`, regardless of the status
+/// it was answered with.
+fn test_server_with<F>(
+    addr: std::net::SocketAddr,
+    responder: F,
+) -> (
+    Receiver<(Parts, Bytes)>,
+    Trigger,
+    impl std::future::Future<Output = Result<(), ()>>,
+)
+where
+    F: Fn(&Parts, &Bytes) -> Response<Body> + Clone + Send + Sync + 'static,
+{
+    let (tx, rx) = mpsc::channel(100);
+    let service = make_service_fn(move |_| {
+        let responder = responder.clone();
+        let tx = tx.clone();
+        async move {
+            Ok::<_, crate::Error>(service_fn(move |req: Request<Body>| {
+                let responder = responder.clone();
+                let mut tx = tx.clone();
+                async move {
+                    let (parts, body) = req.into_parts();
+                    let body = hyper::body::to_bytes(body).await?;
+                    let response = responder(&parts, &body);
+                    tx.send((parts, body)).await?;
+                    Ok::<_, crate::Error>(response)
+                }
+            }))
+        }
+    });
+
+    let (trigger, tripwire) = Tripwire::new();
+    let server = Server::bind(&addr)
+        .serve(service)
+        .with_graceful_shutdown(tripwire.then(crate::stream::tripwire_handler))
+        .map_err(|error| panic!("Server error: {}", error));
+
+    (rx, trigger, server)
+}
+
+/// Starts a test server which only accepts TLS connections presenting a client
+/// certificate signed by the test CA
+///
+/// Every request is answered with `202 Accepted`, and forwarded to the
+/// returned `Receiver`.
+async fn mtls_test_server(
+    addr: std::net::SocketAddr,
+) -> (
+    Receiver<Parts>,
+    Trigger,
+    impl std::future::Future<Output = Result<(), ()>>,
+) {
+    let tls = MaybeTlsSettings::from_config(
+        &Some(TlsConfig {
+            enabled: Some(true),
+            options: TlsOptions {
+                verify_certificate: Some(true),
+                ca_file: Some(TEST_PEM_CA_PATH.into()),
+                crt_file: Some(TEST_PEM_CRT_PATH.into()),
+                key_file: Some(TEST_PEM_KEY_PATH.into()),
+                ..TlsOptions::default()
+            },
+        }),
+        true,
+    )
+    .unwrap();
+    let listener = tls.bind(&addr).await.unwrap();
+
+    let (tx, rx) = mpsc::channel(100);
+    let service = make_service_fn(move |_| {
+        let tx = tx.clone();
+        async move {
+            Ok::<_, crate::Error>(service_fn(move |req: Request<Body>| {
+                let mut tx = tx.clone();
+                async move {
+                    let (parts, _) = req.into_parts();
+                    tx.send(parts).await?;
+                    Ok::<_, crate::Error>(response_with_status(StatusCode::ACCEPTED))
+                }
+            }))
+        }
+    });
+
+    let (trigger, tripwire) = Tripwire::new();
+    let server = Server::builder(hyper::server::accept::from_stream(listener.accept_stream()))
+        .serve(service)
+        .with_graceful_shutdown(tripwire.then(crate::stream::tripwire_handler))
+        .map_err(|error| panic!("Server error: {}", error));
+
+    (rx, trigger, server)
+}
+
+fn response_with_status(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// Builds a sink from the given configuration and runs `events` through it
+///
+/// The sink's endpoint is swapped out for a local test server, which is
+/// expected to receive `expected_requests` requests. The decoded JSON payload
+/// of each request is returned, in the order the server received them.
+async fn run_with_config(
+    config: &str,
+    events: Vec<Event>,
+    expected_requests: usize,
+) -> Vec<Vec<serde_json::Value>> {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(config).unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    let endpoint = format!("http://{}", addr);
+    config.endpoint = Some(endpoint.into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) = test_server(addr, ApiStatus::OKv2);
+    tokio::spawn(server);
+
+    let () = sink.run(stream::iter(events)).await.unwrap();
+
+    rx.take(expected_requests)
+        .map(|(_, body)| {
+            serde_json::from_slice::<Vec<serde_json::Value>>(&body[..]).expect("decoding json")
+        })
+        .collect::<Vec<_>>()
+        .await
+}
+
 fn event_with_api_key(msg: &str, key: &str) -> Event {
     let mut e = Event::from(msg);
     e.as_mut_log()
@@ -84,7 +257,7 @@ async fn start_test(
     // Swap out the endpoint so we can force send it
     // to our local server
     let endpoint = format!("http://{}", addr);
-    config.endpoint = Some(endpoint.clone());
+    config.endpoint = Some(endpoint.clone().into());
 
     let (sink, _) = config.build(cx).await.unwrap();
 
@@ -144,6 +317,176 @@ async fn smoke() {
     }
 }
 
+#[tokio::test]
+/// Assert the basic functionality of the sink with the `ndjson` codec
+///
+/// Each line of the body must be a complete JSON log on its own, in the order
+/// the events were received.
+async fn smoke_ndjson() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            encoding.codec = "ndjson"
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) = test_server(addr, ApiStatus::OKv2);
+    tokio::spawn(server);
+
+    let (batch, receiver) = BatchNotifier::new_with_receiver();
+    let (expected, events) = random_lines_with_stream(100, 10, Some(batch));
+    let () = sink.run(events).await.unwrap();
+    assert_eq!(receiver.await, BatchStatus::Delivered);
+
+    let (parts, body) = rx.take(1).collect::<Vec<_>>().await.remove(0);
+    assert_eq!(
+        parts.headers.get("Content-Type").unwrap(),
+        "application/json"
+    );
+
+    let body = std::str::from_utf8(&body[..]).unwrap();
+    assert!(body.ends_with('\n'));
+    let messages = body
+        .lines()
+        .map(|line| {
+            let log: serde_json::Value = serde_json::from_str(line).expect("decoding json line");
+            assert!(log["timestamp"].is_i64());
+            log["message"].as_str().unwrap().to_string()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(messages, expected);
+}
+
+#[tokio::test]
+/// Assert that the line based codecs separate each event by exactly one newline
+///
+/// Messages ending in their own line breaks must neither merge with the next
+/// event nor add blank lines to the body.
+async fn line_codecs_separate_events() {
+    for codec in ["ndjson", "cef"] {
+        let (mut config, cx) = load_sink::<DatadogLogsConfig>(&formatdoc! {r#"
+                default_api_key = "atoken"
+                compression = "none"
+                encoding.codec = "{}"
+            "#, codec})
+        .unwrap();
+
+        let addr = next_addr();
+        // Swap out the endpoint so we can force send it to our local server
+        config.endpoint = Some(format!("http://{}", addr).into());
+
+        let (sink, _) = config.build(cx).await.unwrap();
+
+        let (rx, _trigger, server) =
+            test_server_with(addr, |_, _| response_with_status(StatusCode::ACCEPTED));
+        tokio::spawn(server);
+
+        let events = ["first", "second\n", "third\r\n"].map(Event::from);
+        let () = sink.run(stream::iter(events)).await.unwrap();
+
+        let (_, body) = rx.take(1).collect::<Vec<_>>().await.remove(0);
+        let body = std::str::from_utf8(&body[..]).unwrap();
+        assert!(body.ends_with('\n'), "{}", codec);
+        let lines = body.split_terminator('\n').collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3, "{}: {:?}", codec, body);
+        for (line, message) in lines.iter().zip(["first", "second", "third"]) {
+            assert!(line.contains(message), "{}: {:?}", codec, line);
+        }
+    }
+}
+
+#[tokio::test]
+/// Assert that a sink built in dry run mode discards events
+///
+/// Neither the healthcheck nor the events may reach the server, while an
+/// invalid configuration must still fail to build.
+async fn dry_run() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            dry_run = true
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, healthcheck) = config.build(cx).await.unwrap();
+
+    let (mut rx, _trigger, server) = test_server(addr, ApiStatus::OKv2);
+    tokio::spawn(server);
+
+    healthcheck.await.unwrap();
+    let (batch, receiver) = BatchNotifier::new_with_receiver();
+    let (_, events) = random_lines_with_stream(100, 10, Some(batch));
+    sink.run(events).await.unwrap();
+    assert_eq!(receiver.await, BatchStatus::Delivered);
+    assert!(matches!(rx.try_next(), Err(TryRecvError { .. })));
+
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            dry_run = true
+            request.headers.Content-Type = "text/plain"
+        "#})
+    .unwrap();
+    config.endpoint = Some(format!("http://{}", addr).into());
+    assert!(config.build(cx).await.is_err());
+}
+
+#[tokio::test]
+/// Assert that the client certificate is presented to the intake
+///
+/// The server requires a client certificate, so both the healthcheck and the
+/// events must only get through when `tls.crt_file` and `tls.key_file` are
+/// set, even though `tls.enabled` isn't.
+async fn mutual_tls() {
+    let addr = next_addr();
+    let (mut rx, _trigger, server) = mtls_test_server(addr).await;
+    tokio::spawn(server);
+
+    let build = |client_certificate: bool| async move {
+        let mut config = formatdoc! {r#"
+            default_api_key = "atoken"
+            endpoint = "https://localhost:{port}"
+            healthcheck_boot_retries = 0
+            tls.ca_file = "{ca}"
+        "#, port = addr.port(), ca = TEST_PEM_CA_PATH};
+        if client_certificate {
+            config += &formatdoc! {r#"
+                tls.crt_file = "{crt}"
+                tls.key_file = "{key}"
+            "#, crt = TEST_PEM_CRT_PATH, key = TEST_PEM_KEY_PATH};
+        }
+        let (config, cx) = load_sink::<DatadogLogsConfig>(&config).unwrap();
+        config.build(cx).await.unwrap()
+    };
+
+    let (_, healthcheck) = build(false).await;
+    assert!(healthcheck.await.is_err());
+    assert!(matches!(rx.try_next(), Err(TryRecvError { .. })));
+
+    let (sink, healthcheck) = build(true).await;
+    healthcheck.await.unwrap();
+    let (batch, receiver) = BatchNotifier::new_with_receiver();
+    let (_, events) = random_lines_with_stream(100, 10, Some(batch));
+    sink.run(events).await.unwrap();
+    assert_eq!(receiver.await, BatchStatus::Delivered);
+
+    // The healthcheck, then the events.
+    let paths = rx
+        .take(2)
+        .map(|parts| parts.uri.path().to_string())
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(paths, ["/api/v1/validate", "/"]);
+}
+
 #[tokio::test]
 /// Assert delivery error behavior for v1 API
 ///
@@ -170,6 +513,16 @@ async fn handles_failure_v2() {
     assert!(matches!(res, Err(TryRecvError { .. })));
 }
 
+#[tokio::test]
+/// Assert delivery error behavior for a malformed payload, v2 API
+///
+/// A `400 Bad Request` is not retried, so the events of the request must be
+/// finalized as rejected rather than delivered, letting acknowledging sources
+/// and disk buffers know they were not accepted.
+async fn handles_bad_request_v2() {
+    start_test(ApiStatus::BadRequestv2, BatchStatus::Rejected).await;
+}
+
 #[tokio::test]
 /// Assert that metadata API keys are passed correctly, v2 API
 ///
@@ -202,7 +555,7 @@ async fn api_key_in_metadata_inner(api_status: ApiStatus) {
     let addr = next_addr();
     // Swap out the endpoint so we can force send it to our local server
     let endpoint = format!("http://{}", addr);
-    config.endpoint = Some(endpoint.clone());
+    config.endpoint = Some(endpoint.clone().into());
 
     let (sink, _) = config.build(cx).await.unwrap();
 
@@ -282,7 +635,7 @@ async fn multiple_api_keys_inner(api_status: ApiStatus) {
     // Swap out the endpoint so we can force send it
     // to our local server
     let endpoint = format!("http://{}", addr);
-    config.endpoint = Some(endpoint.clone());
+    config.endpoint = Some(endpoint.clone().into());
 
     let (sink, _) = config.build(cx).await.unwrap();
 
@@ -307,6 +660,99 @@ async fn multiple_api_keys_inner(api_status: ApiStatus) {
     assert_eq!(keys, vec!["atoken", "pkc", "vvo"])
 }
 
+#[tokio::test]
+/// Assert that API keys are rendered from event fields when templated
+///
+/// Events the template fails to render for keep the API key of their metadata,
+/// or otherwise use the fallback API key, which is required.
+async fn templated_api_key() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "{{ tenant_key }}"
+            fallback_api_key = "atoken"
+            compression = "none"
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it
+    // to our local server
+    let endpoint = format!("http://{}", addr);
+    config.endpoint = Some(endpoint.clone().into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) = test_server(addr, ApiStatus::OKv2);
+    tokio::spawn(server);
+
+    let mut templated = event_with_api_key("templated", "pkc");
+    templated.as_mut_log().insert("tenant_key", "mow");
+    let events = vec![
+        templated,
+        event_with_api_key("no tenant key", "vvo"),
+        Event::from("no API key in metadata"),
+    ];
+
+    let _ = sink.run(stream::iter(events)).await.unwrap();
+
+    let mut keys = rx
+        .take(3)
+        .map(|r| r.0.headers.get("DD-API-KEY").unwrap().clone())
+        .collect::<Vec<_>>()
+        .await;
+
+    keys.sort();
+    assert_eq!(keys, vec!["atoken", "mow", "vvo"]);
+
+    let (config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "{{ tenant_key }}"
+        "#})
+    .unwrap();
+    assert!(config.build(cx).await.is_err());
+}
+
+#[tokio::test]
+/// Assert that a busy API key doesn't hold back the batch timeout of others
+///
+/// The events of one API key keep the input ready, filling batch after batch,
+/// while another API key only has a single event. Its batch must still be
+/// flushed once the batch timeout expires.
+async fn busy_api_key_does_not_starve_others() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            batch.max_events = 100
+            batch.timeout_secs = 1
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) =
+        test_server_with(addr, |_, _| response_with_status(StatusCode::ACCEPTED));
+    tokio::spawn(server);
+
+    let quiet = stream::once(async { event_with_api_key("quiet", "quiet") });
+    let busy = stream::iter(0..).map(|i| event_with_api_key(&format!("busy {}", i), "busy"));
+    tokio::spawn(sink.run(quiet.chain(busy)));
+
+    let started = tokio::time::Instant::now();
+    let mut quiet_requests =
+        rx.filter(|(parts, _)| future::ready(parts.headers.get("DD-API-KEY").unwrap() == "quiet"));
+    let (_, body) = tokio::time::timeout(Duration::from_secs(5), quiet_requests.next())
+        .await
+        .expect("the quiet API key was starved")
+        .unwrap();
+    assert!(started.elapsed() < Duration::from_secs(3));
+
+    let logs = serde_json::from_slice::<Vec<serde_json::Value>>(&body[..]).unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0]["message"], "quiet");
+}
+
 #[tokio::test]
 /// Assert that events are sent and the DD-EVP-ORIGIN header is set when
 /// 'enterprise' is flagged on, v2 API
@@ -341,7 +787,7 @@ async fn enterprise_headers_inner(api_status: ApiStatus) {
     let addr = next_addr();
     // Swap out the endpoint so we can force send it to our local server
     let endpoint = format!("http://{}", addr);
-    config.endpoint = Some(endpoint.clone());
+    config.endpoint = Some(endpoint.clone().into());
 
     cx.globals.enterprise = true;
     let (sink, _) = config.build(cx).await.unwrap();
@@ -405,7 +851,7 @@ async fn no_enterprise_headers_inner(api_status: ApiStatus) {
     let addr = next_addr();
     // Swap out the endpoint so we can force send it to our local server
     let endpoint = format!("http://{}", addr);
-    config.endpoint = Some(endpoint.clone());
+    config.endpoint = Some(endpoint.clone().into());
 
     cx.globals.enterprise = false;
     let (sink, _) = config.build(cx).await.unwrap();
@@ -431,3 +877,3294 @@ async fn no_enterprise_headers_inner(api_status: ApiStatus) {
     assert_eq!(parts.headers.get("DD-EVP-ORIGIN").unwrap(), "vector");
     assert!(parts.headers.get("DD-EVP-ORIGIN-VERSION").is_some());
 }
+
+#[tokio::test]
+/// Assert that the deterministic ID only depends on the configured fields
+///
+/// Events with identical values for the configured fields must be stamped with
+/// the same ID, so that Datadog can deduplicate them, while events with
+/// differing values must be stamped with different IDs.
+async fn deterministic_id() {
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            deterministic_id.fields = ["message", "service"]
+        "#};
+
+    let mut first = Event::from("hello");
+    first.as_mut_log().insert("service", "web");
+    let second = first.clone();
+    let mut third = Event::from("hello");
+    third.as_mut_log().insert("service", "db");
+
+    let payloads = run_with_config(config, vec![first, second, third], 1).await;
+    let ids = payloads[0]
+        .iter()
+        .map(|log| log.get("event_id").unwrap().as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+
+    assert_eq!(ids.len(), 3);
+    assert_eq!(ids[0], ids[1]);
+    assert_ne!(ids[0], ids[2]);
+}
+
+#[tokio::test]
+/// Assert that the sink's egress stays under the configured bandwidth cap
+///
+/// The sink is flooded with several times the configured bandwidth worth of
+/// events. As the limiter allows up to one second's worth of bytes to be sent
+/// in a burst, the bytes received by the server must not exceed the cap
+/// multiplied by the elapsed time plus that one second.
+async fn max_bandwidth_bytes_per_sec() {
+    let cap = 10_000;
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(&format!(
+        indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            max_bandwidth_bytes_per_sec = {}
+            batch.max_events = 5
+        "#},
+        cap
+    ))
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    let endpoint = format!("http://{}", addr);
+    config.endpoint = Some(endpoint.into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) = test_server(addr, ApiStatus::OKv2);
+    tokio::spawn(server);
+
+    let events = (0..30)
+        .map(|_| Event::from("x".repeat(1_000)))
+        .collect::<Vec<_>>();
+
+    let start = std::time::Instant::now();
+    let () = sink.run(stream::iter(events)).await.unwrap();
+    let received_bytes: usize = rx
+        .take(6)
+        .map(|(_, body)| body.len())
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .sum();
+    let elapsed = start.elapsed().as_secs_f64();
+
+    assert!(received_bytes > 3 * cap);
+    assert!((received_bytes as f64) <= cap as f64 * (elapsed + 1.0));
+}
+
+#[tokio::test]
+/// Assert that `request.max_in_flight_bytes` caps the bytes in flight
+///
+/// The server answers slowly, so the sink would otherwise have up to
+/// `request.concurrency` requests in flight. With a budget fitting two bodies,
+/// it must stop issuing requests once two are outstanding.
+async fn max_in_flight_bytes() {
+    const MAX_BYTES: usize = 2_500;
+
+    #[derive(Default)]
+    struct Intake {
+        in_flight: AtomicUsize,
+        in_flight_bytes: AtomicUsize,
+        max_in_flight: AtomicUsize,
+        max_in_flight_bytes: AtomicUsize,
+        received: AtomicUsize,
+    }
+
+    let intake = Arc::new(Intake::default());
+    let addr = next_addr();
+    let service = {
+        let intake = Arc::clone(&intake);
+        make_service_fn(move |_| {
+            let intake = Arc::clone(&intake);
+            async move {
+                Ok::<_, crate::Error>(service_fn(move |req: Request<Body>| {
+                    let intake = Arc::clone(&intake);
+                    async move {
+                        let bytes = hyper::body::to_bytes(req.into_body()).await?.len();
+                        let in_flight = intake.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                        let in_flight_bytes =
+                            intake.in_flight_bytes.fetch_add(bytes, Ordering::SeqCst) + bytes;
+                        intake.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+                        intake
+                            .max_in_flight_bytes
+                            .fetch_max(in_flight_bytes, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(200)).await;
+                        intake.in_flight.fetch_sub(1, Ordering::SeqCst);
+                        intake.in_flight_bytes.fetch_sub(bytes, Ordering::SeqCst);
+                        intake.received.fetch_add(1, Ordering::SeqCst);
+                        Ok::<_, crate::Error>(response_with_status(StatusCode::ACCEPTED))
+                    }
+                }))
+            }
+        })
+    };
+    tokio::spawn(Server::bind(&addr).serve(service));
+
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(&formatdoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            batch.max_events = 1
+            request.concurrency = 10
+            request.max_in_flight_bytes = {}
+        "#, MAX_BYTES})
+    .unwrap();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    // Each body is a bit over 1000 bytes, so only two fit in the budget.
+    let events = (0..10)
+        .map(|_| Event::from("x".repeat(1_000)))
+        .collect::<Vec<_>>();
+    let () = sink.run(stream::iter(events)).await.unwrap();
+
+    assert_eq!(intake.received.load(Ordering::SeqCst), 10);
+    assert_eq!(intake.max_in_flight.load(Ordering::SeqCst), 2);
+    assert!(intake.max_in_flight_bytes.load(Ordering::SeqCst) <= MAX_BYTES);
+}
+
+#[tokio::test]
+/// Assert that the correlation ID of events is taken from their metadata
+///
+/// Events whose metadata carries a correlation ID must be sent with it as the
+/// `correlation_id` attribute, while events without one must get a generated
+/// ID marked as synthetic.
+async fn correlation_id_from_metadata() {
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            correlation_id_from_metadata = true
+        "#};
+
+    let mut traced = Event::from("traced");
+    traced
+        .as_mut_log()
+        .metadata_mut()
+        .set_correlation_id(Some(Arc::from("4bf92f3577b34da6")));
+    let untraced = Event::from("untraced");
+
+    let payloads = run_with_config(config, vec![traced, untraced], 1).await;
+    let logs = &payloads[0];
+
+    assert_eq!(logs[0]["correlation_id"], "4bf92f3577b34da6");
+    assert!(logs[0].get("correlation_id_synthetic").is_none());
+    assert!(!logs[1]["correlation_id"].as_str().unwrap().is_empty());
+    assert_eq!(logs[1]["correlation_id_synthetic"], true);
+}
+
+#[tokio::test]
+/// Assert that compression is disabled once compressed requests are rejected
+///
+/// Some relays reject compressed bodies with a 400 while accepting uncompressed
+/// ones. Each rejected batch must be retried uncompressed, and after the
+/// configured number of consecutive rejections all later batches must be sent
+/// uncompressed too.
+async fn compression_fallback_on_rejection() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "gzip"
+            on_compression_rejected.consecutive_rejections = 2
+            batch.max_events = 1
+            request.concurrency = 1
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    let endpoint = format!("http://{}", addr);
+    config.endpoint = Some(endpoint.into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) = test_server_with(addr, |parts, _| {
+        if parts.headers.contains_key("Content-Encoding") {
+            Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(Body::from("unable to decode gzip body"))
+                .unwrap()
+        } else {
+            response_with_status(StatusCode::ACCEPTED)
+        }
+    });
+    tokio::spawn(server);
+
+    let events = vec![Event::from("a"), Event::from("b"), Event::from("c")];
+    let () = sink.run(stream::iter(events)).await.unwrap();
+
+    // The first two batches are rejected compressed and retried uncompressed,
+    // and the third batch is sent uncompressed.
+    let requests = rx.take(5).collect::<Vec<_>>().await;
+    let (compressed, uncompressed): (Vec<_>, Vec<_>) = requests
+        .into_iter()
+        .partition(|(parts, _)| parts.headers.contains_key("Content-Encoding"));
+
+    assert_eq!(compressed.len(), 2);
+    let mut messages = uncompressed
+        .into_iter()
+        .map(|(_, body)| {
+            let logs: Vec<serde_json::Value> = serde_json::from_slice(&body[..]).unwrap();
+            assert_eq!(logs.len(), 1);
+            logs[0]["message"].as_str().unwrap().to_owned()
+        })
+        .collect::<Vec<_>>();
+    messages.sort();
+    assert_eq!(messages, vec!["a", "b", "c"]);
+}
+
+#[tokio::test]
+/// Assert that compressed requests rejected for their logs don't count
+/// towards the compression fallback
+///
+/// A 400 that doesn't indicate its body failed to be decoded is a rejection
+/// of the logs, so the batch isn't retried uncompressed.
+async fn compression_fallback_ignores_other_rejections() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "gzip"
+            on_compression_rejected.consecutive_rejections = 1
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    config.endpoint = Some(format!("http://{}", addr).into());
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (mut rx, _trigger, server) = test_server_with(addr, |_, _| {
+        Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(Body::from("invalid log attribute"))
+            .unwrap()
+    });
+    tokio::spawn(server);
+
+    let (batch, receiver) = BatchNotifier::new_with_receiver();
+    let events = vec![Event::from("a").with_batch_notifier(&batch)];
+    drop(batch);
+    let () = sink.run(stream::iter(events)).await.unwrap();
+    assert_eq!(receiver.await, BatchStatus::Rejected);
+
+    let (parts, _) = rx.next().await.unwrap();
+    assert!(parts.headers.contains_key("Content-Encoding"));
+    assert!(matches!(rx.try_next(), Err(TryRecvError { .. })));
+}
+
+#[tokio::test]
+/// Assert that control characters are stripped from string fields
+///
+/// Tabs and newlines are kept unless `strip_control_chars_keep_whitespace` is
+/// disabled.
+async fn strip_control_chars() {
+    let message = "bi\u{0}na\u{7}ry\tline\n\u{1b}[0m";
+    let mut event = Event::from(message);
+    event.as_mut_log().insert("nested.field", "a\u{8}b");
+
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            strip_control_chars = true
+        "#};
+    let payloads = run_with_config(config, vec![event.clone()], 1).await;
+    let log = &payloads[0][0];
+    assert_eq!(log["message"], "binary\tline\n[0m");
+    assert_eq!(log["nested"]["field"], "ab");
+
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            strip_control_chars = true
+            strip_control_chars_keep_whitespace = false
+        "#};
+    let payloads = run_with_config(config, vec![event], 1).await;
+    assert_eq!(payloads[0][0]["message"], "binaryline[0m");
+}
+
+#[tokio::test]
+/// Assert that every event is shipped to both sites when dual shipping
+///
+/// The secondary site must receive the same events as the primary site, and
+/// authenticate them with its own API key.
+async fn dual_ship() {
+    let primary_addr = next_addr();
+    let secondary_addr = next_addr();
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(&format!(
+        indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            dual_ship.endpoint = "http://{}"
+            dual_ship.api_key = "secondary-token"
+        "#},
+        secondary_addr
+    ))
+    .unwrap();
+
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", primary_addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (primary_rx, _primary_trigger, primary_server) = test_server(primary_addr, ApiStatus::OKv2);
+    tokio::spawn(primary_server);
+    let (secondary_rx, _secondary_trigger, secondary_server) =
+        test_server(secondary_addr, ApiStatus::OKv2);
+    tokio::spawn(secondary_server);
+
+    let (expected, events) = random_lines_with_stream(100, 10, None);
+    let () = sink.run(events).await.unwrap();
+
+    let (primary_parts, primary_body) = primary_rx.take(1).collect::<Vec<_>>().await.pop().unwrap();
+    let (secondary_parts, secondary_body) = secondary_rx
+        .take(1)
+        .collect::<Vec<_>>()
+        .await
+        .pop()
+        .unwrap();
+
+    assert_eq!(primary_parts.headers.get("DD-API-KEY").unwrap(), "atoken");
+    assert_eq!(
+        secondary_parts.headers.get("DD-API-KEY").unwrap(),
+        "secondary-token"
+    );
+    assert_eq!(primary_body, secondary_body);
+
+    let logs = serde_json::from_slice::<Vec<serde_json::Value>>(&primary_body[..]).unwrap();
+    let messages = logs
+        .iter()
+        .map(|log| log["message"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(messages, expected);
+}
+
+#[tokio::test]
+/// Assert that timestamps with the wrong precision are flagged
+///
+/// A nanosecond timestamp is read by Datadog as milliseconds far in the
+/// future, which must be reported, while a correct timestamp must not be.
+async fn implausible_timestamp() {
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            implausible_timestamp_threshold_secs = 86400
+        "#};
+
+    let mut plausible = Event::from("plausible");
+    plausible
+        .as_mut_log()
+        .insert("timestamp", Utc::now().timestamp_millis());
+    clear_recorded_events();
+    run_with_config(config, vec![plausible], 1).await;
+    assert!(!contains_name("DatadogLogsImplausibleTimestamp"));
+
+    let mut nanoseconds = Event::from("nanoseconds");
+    nanoseconds
+        .as_mut_log()
+        .insert("timestamp", Utc::now().timestamp_nanos());
+    clear_recorded_events();
+    run_with_config(config, vec![nanoseconds], 1).await;
+    assert!(contains_name("DatadogLogsImplausibleTimestamp"));
+}
+
+#[tokio::test]
+/// Assert that events older than the target retention are dropped
+///
+/// Datadog would drop them at ingest, so they must not be sent, while recent
+/// events are.
+async fn target_retention() {
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            target_retention_days = 7
+        "#};
+
+    let mut recent = Event::from("recent");
+    recent
+        .as_mut_log()
+        .insert("timestamp", Utc::now() - chrono::Duration::days(6));
+    let mut expired = Event::from("expired");
+    expired
+        .as_mut_log()
+        .insert("timestamp", Utc::now() - chrono::Duration::days(8));
+
+    clear_recorded_events();
+    let logs = run_with_config(config, vec![expired, recent], 1).await;
+    assert_eq!(logs[0].len(), 1);
+    assert_eq!(logs[0][0]["message"], "recent");
+    assert!(contains_name("DatadogLogEventDropped"));
+}
+
+#[tokio::test]
+/// Assert that events dropped while encoding are rejected rather than sent
+///
+/// The reads of dropped events are still acknowledged, and a batch whose
+/// events were all dropped isn't sent at all.
+async fn encoder_drops_are_rejected() {
+    async fn run(events: Vec<Event>) -> (Receiver<(Parts, Bytes)>, usize) {
+        let (mut config, mut cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+                default_api_key = "atoken"
+                compression = "none"
+                target_retention_days = 7
+            "#})
+        .unwrap();
+        let (acker, ack_counter) = Acker::basic();
+        cx.acker = acker;
+        let addr = next_addr();
+        config.endpoint = Some(format!("http://{}", addr).into());
+        let (sink, _) = config.build(cx).await.unwrap();
+        let (rx, _trigger, server) = test_server(addr, ApiStatus::OKv2);
+        tokio::spawn(server);
+
+        let () = sink.run(stream::iter(events)).await.unwrap();
+        (rx, ack_counter.load(Ordering::Relaxed))
+    }
+    let expired = |batch: &Arc<BatchNotifier>| {
+        let mut event = Event::from("expired").with_batch_notifier(batch);
+        event
+            .as_mut_log()
+            .insert("timestamp", Utc::now() - chrono::Duration::days(8));
+        event
+    };
+
+    let (expired_batch, expired_receiver) = BatchNotifier::new_with_receiver();
+    let (recent_batch, recent_receiver) = BatchNotifier::new_with_receiver();
+    let events = vec![
+        expired(&expired_batch),
+        Event::from("recent").with_batch_notifier(&recent_batch),
+    ];
+    drop((expired_batch, recent_batch));
+    let (rx, acked) = run(events).await;
+    let (_, body) = rx.take(1).collect::<Vec<_>>().await.remove(0);
+    let logs = serde_json::from_slice::<Vec<serde_json::Value>>(&body[..]).unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0]["message"], "recent");
+    assert_eq!(expired_receiver.await, BatchStatus::Rejected);
+    assert_eq!(recent_receiver.await, BatchStatus::Delivered);
+    assert_eq!(acked, 2);
+
+    let (batch, receiver) = BatchNotifier::new_with_receiver();
+    let events = vec![expired(&batch), expired(&batch)];
+    drop(batch);
+    let (mut rx, acked) = run(events).await;
+    assert_eq!(receiver.await, BatchStatus::Rejected);
+    assert_eq!(acked, 2);
+    assert!(matches!(rx.try_next(), Err(TryRecvError { .. })));
+}
+
+#[tokio::test]
+/// Assert that attributes beyond the cap are dropped
+///
+/// Reserved attributes count toward the cap but are always retained, and the
+/// remaining slots go to the first other attributes.
+async fn max_attributes_per_event() {
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            max_attributes_per_event = 4
+        "#};
+
+    let mut event = Event::from("message");
+    let log = event.as_mut_log();
+    log.insert("service", "vector");
+    for attribute in ["a", "b", "c", "d"] {
+        log.insert(attribute, attribute);
+    }
+
+    clear_recorded_events();
+    let payloads = run_with_config(config, vec![event], 1).await;
+    assert!(contains_name("DatadogLogAttributesTruncated"));
+
+    let attributes = payloads[0][0]
+        .as_object()
+        .unwrap()
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>();
+    assert_eq!(attributes, vec!["a", "message", "service", "timestamp"]);
+}
+
+#[tokio::test]
+/// Assert that smart compression skips compression for incompressible partitions
+///
+/// Payloads of random printable characters barely compress, so compression
+/// must eventually be skipped for their partition, while another partition
+/// with compressible payloads is still compressed.
+async fn smart_compression() {
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "smart"
+            batch.max_events = 1
+        "#};
+
+    let random_message = || {
+        let mut rng = thread_rng();
+        std::iter::repeat_with(|| char::from(rng.gen_range(b'!'..=b'~')))
+            .filter(|c| *c != '"' && *c != '\\')
+            .take(8192)
+            .collect::<String>()
+    };
+    let mut events = Vec::new();
+    for _ in 0..20 {
+        events.push(event_with_api_key(&random_message(), "random"));
+        events.push(event_with_api_key(&"repetitive ".repeat(512), "repetitive"));
+    }
+
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(config).unwrap();
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) = test_server(addr, ApiStatus::OKv2);
+    tokio::spawn(server);
+
+    let () = sink.run(stream::iter(events)).await.unwrap();
+
+    let requests = rx.take(40).collect::<Vec<_>>().await;
+    let uncompressed = |api_key: &str| {
+        requests
+            .iter()
+            .filter(|(parts, _)| parts.headers.get("DD-API-KEY").unwrap() == api_key)
+            .filter(|(parts, _)| !parts.headers.contains_key("Content-Encoding"))
+            .count()
+    };
+    assert!(uncompressed("random") > 0);
+    assert_eq!(uncompressed("repetitive"), 0);
+}
+
+#[tokio::test]
+/// Assert that a pre-send hook registered on the config builder runs on each
+/// event
+///
+/// This is only available when building the config programmatically, so the
+/// config comes from `DatadogLogsConfig::builder` rather than from TOML.
+async fn pre_send_hook() {
+    let addr = next_addr();
+    let (rx, _trigger, server) = test_server(addr, ApiStatus::OKv2);
+    tokio::spawn(server);
+
+    let config = DatadogLogsConfig::builder()
+        .api_key("atoken")
+        .endpoint(format!("http://{}", addr))
+        .with_pre_send_hook(Box::new(|event: &mut Event| {
+            event.as_mut_log().insert("stamped", true);
+        }))
+        .build()
+        .unwrap();
+    let (sink, _) = config.build(SinkContext::new_test()).await.unwrap();
+
+    let (expected, events) = random_lines_with_stream(100, 10, None);
+    let () = sink.run(events).await.unwrap();
+
+    let (_, body) = rx.take(1).collect::<Vec<_>>().await.pop().unwrap();
+    let logs = serde_json::from_slice::<Vec<serde_json::Value>>(&body[..]).unwrap();
+    assert_eq!(logs.len(), expected.len());
+    for log in logs {
+        assert_eq!(log["stamped"], true);
+    }
+}
+
+#[tokio::test]
+/// Assert that a canary is sent each time the canary trigger fires
+///
+/// The canary goes through the sink along with the events, and its outcome is
+/// reported once its request completes. As it wasn't read from the input, it
+/// isn't acknowledged.
+async fn canary_trigger() {
+    let addr = next_addr();
+    let (mut rx, _trigger, server) = test_server(addr, ApiStatus::OKv2);
+    tokio::spawn(server);
+
+    let client = HttpClient::new(None, &Default::default()).unwrap();
+    let uri = format!("http://{}", addr).parse().unwrap();
+    let service = LogApiService::new(client, uri, false);
+    let mut batch = BatchConfig::<DatadogLogsDefaultBatchSettings>::default();
+    batch.timeout_secs = Some(1);
+    let batch = batch.into_batcher_settings().unwrap();
+    let canary_trigger = CanaryTrigger::default();
+    let mut cx = SinkContext::new_test();
+    let (acker, ack_counter) = Acker::basic();
+    cx.acker = acker;
+    let sink = LogSinkBuilder::new(service, cx, Arc::from("atoken"), batch)
+        .encoding(Default::default())
+        .canary_trigger(Some(canary_trigger.clone()))
+        .build();
+    let sink = VectorSink::Stream(Box::new(sink));
+
+    clear_recorded_events();
+    // The input is kept open until the canary is sent, as the sink stops once it ends.
+    let (tx, events) = mpsc::channel::<Event>(1);
+    canary_trigger.trigger();
+    let run = tokio::spawn(sink.run(events));
+
+    let (_, body) = rx.next().await.unwrap();
+    let logs = serde_json::from_slice::<Vec<serde_json::Value>>(&body[..]).unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0]["canary"], true);
+    assert_eq!(logs[0]["service"], "vector-canary");
+
+    drop(tx);
+    run.await.unwrap().unwrap();
+    tokio::time::timeout(Duration::from_secs(1), async {
+        while !contains_name("DatadogLogsCanaryCompleted") {
+            tokio::task::yield_now().await;
+        }
+    })
+    .await
+    .expect("canary outcome was not reported");
+    assert_eq!(ack_counter.load(Ordering::Relaxed), 0);
+}
+
+#[tokio::test]
+/// Assert that recent and old events are sent in separate requests
+///
+/// With time bucketing, events within the hot window and backfilled events
+/// must never share a request.
+async fn time_bucketing() {
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            time_bucketing.hot_window_secs = 60
+            time_bucketing.backfill_request.concurrency = 1
+        "#};
+
+    let now = Utc::now();
+    let mut events = Vec::new();
+    for i in 0..10 {
+        let mut event = Event::from(format!("event {}", i));
+        let timestamp = if i % 2 == 0 {
+            now
+        } else {
+            now - chrono::Duration::hours(1)
+        };
+        event.as_mut_log().insert("timestamp", timestamp);
+        events.push(event);
+    }
+
+    let payloads = run_with_config(config, events, 2).await;
+    let cutoff = (now - chrono::Duration::seconds(60)).timestamp_millis();
+    let mut buckets = payloads
+        .iter()
+        .map(|logs| {
+            assert_eq!(logs.len(), 5);
+            let hot = logs
+                .iter()
+                .map(|log| log["timestamp"].as_i64().unwrap() > cutoff)
+                .collect::<Vec<_>>();
+            assert!(hot.iter().all(|h| *h == hot[0]));
+            hot[0]
+        })
+        .collect::<Vec<_>>();
+    buckets.sort_unstable();
+    assert_eq!(buckets, vec![false, true]);
+}
+
+#[tokio::test]
+/// Assert that a site which isn't a known Datadog site is warned about
+///
+/// A typo'd site must be reported, while a known site, or any site
+/// overridden by an endpoint, must not.
+async fn unknown_site() {
+    async fn warns(config: &str) -> bool {
+        let (config, cx) = load_sink::<DatadogLogsConfig>(config).unwrap();
+        clear_recorded_events();
+        let _ = config.build(cx).await.unwrap();
+        contains_name("DatadogLogsUnknownSite")
+    }
+
+    assert!(
+        warns(indoc! {r#"
+            default_api_key = "atoken"
+            site = "datadoghq.co"
+        "#})
+        .await
+    );
+    assert!(
+        !warns(indoc! {r#"
+            default_api_key = "atoken"
+            site = "us3.datadoghq.com"
+        "#})
+        .await
+    );
+    assert!(
+        !warns(indoc! {r#"
+            default_api_key = "atoken"
+            site = "relay.internal"
+            endpoint = "http://relay.internal:8080"
+        "#})
+        .await
+    );
+}
+
+#[tokio::test]
+/// Assert that the deprecated region option is warned about
+///
+/// Setting `region` at all must be reported, even when it agrees with `site`,
+/// but only once as the config is parsed, not again on every build.
+async fn deprecated_region() {
+    async fn warns(config: &str) -> bool {
+        clear_recorded_events();
+        let (config, cx) = load_sink::<DatadogLogsConfig>(config).unwrap();
+        let warned = contains_name("DatadogLogsDeprecatedRegion");
+        clear_recorded_events();
+        let _ = config.build(cx).await.unwrap();
+        assert!(!contains_name("DatadogLogsDeprecatedRegion"));
+        warned
+    }
+
+    assert!(
+        warns(indoc! {r#"
+            default_api_key = "atoken"
+            region = "eu"
+        "#})
+        .await
+    );
+    assert!(
+        warns(indoc! {r#"
+            default_api_key = "atoken"
+            site = "datadoghq.eu"
+            region = "eu"
+        "#})
+        .await
+    );
+    assert!(
+        !warns(indoc! {r#"
+            default_api_key = "atoken"
+            site = "datadoghq.eu"
+        "#})
+        .await
+    );
+}
+
+#[tokio::test]
+/// Assert that contradicting endpoint, site and region options are rejected
+///
+/// Only one of them would be used, so the error must name the options
+/// involved rather than silently ignoring the others.
+async fn conflicting_sites() {
+    async fn error(config: &str) -> String {
+        let (config, cx) = load_sink::<DatadogLogsConfig>(config).unwrap();
+        match config.build(cx).await {
+            Ok(_) => panic!("conflicting options were accepted"),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    let message = error(indoc! {r#"
+            default_api_key = "atoken"
+            site = "datadoghq.com"
+            region = "eu"
+        "#})
+    .await;
+    assert!(message.contains("`site`") && message.contains("`region`"));
+
+    let message = error(indoc! {r#"
+            default_api_key = "atoken"
+            endpoint = "https://http-intake.logs.datadoghq.eu"
+            site = "datadoghq.com"
+        "#})
+    .await;
+    assert!(message.contains("`endpoint`") && message.contains("`site`"));
+
+    let message = error(indoc! {r#"
+            default_api_key = "atoken"
+            endpoint = "https://http-intake.logs.datadoghq.com"
+            region = "eu"
+        "#})
+    .await;
+    assert!(message.contains("`endpoint`") && message.contains("`region`"));
+}
+
+#[tokio::test]
+/// Assert that an endpoint which isn't on any Datadog site is only warned about
+///
+/// Relays, proxies and local servers are on domains of their own, so they must
+/// be accepted along with the site they forward to.
+async fn relay_endpoint_with_site() {
+    for endpoint in ["http://127.0.0.1:9000", "https://dd-relay.example.com"] {
+        let (config, cx) = load_sink::<DatadogLogsConfig>(&formatdoc! {r#"
+                default_api_key = "atoken"
+                endpoint = "{}"
+                site = "datadoghq.eu"
+            "#, endpoint})
+        .unwrap();
+        clear_recorded_events();
+        let _ = config.build(cx).await.unwrap();
+        assert!(contains_name("DatadogLogsEndpointNotOnSite"));
+    }
+}
+
+#[tokio::test]
+/// Assert that batches of precompressed events are sent uncompressed
+///
+/// When most events of a batch have the indicator field, recompressing the
+/// batch gains little, so compression must be skipped for it, while other
+/// batches are still compressed.
+async fn precompressed_field() {
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "gzip"
+            precompressed_field = "blob"
+            batch.max_events = 3
+            request.concurrency = 1
+        "#};
+
+    let mut events = Vec::new();
+    for i in 0..3 {
+        let mut event = Event::from(format!("precompressed {}", i));
+        if i < 2 {
+            event.as_mut_log().insert("blob", "H4sIAAAAAAAA/w==");
+        }
+        events.push(event);
+    }
+    events.extend((0..3).map(|i| Event::from(format!("plain {}", i))));
+
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(config).unwrap();
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) = test_server(addr, ApiStatus::OKv2);
+    tokio::spawn(server);
+
+    let () = sink.run(stream::iter(events)).await.unwrap();
+
+    let requests = rx.take(2).collect::<Vec<_>>().await;
+    let (uncompressed, compressed): (Vec<_>, Vec<_>) = requests
+        .into_iter()
+        .partition(|(parts, _)| !parts.headers.contains_key("Content-Encoding"));
+    assert_eq!(uncompressed.len(), 1);
+    assert_eq!(compressed.len(), 1);
+
+    let logs = serde_json::from_slice::<Vec<serde_json::Value>>(&uncompressed[0].1[..]).unwrap();
+    assert!(logs.iter().all(|log| log["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("precompressed")));
+}
+
+#[tokio::test]
+/// Assert that an API key shaped like an application key is warned about
+///
+/// Application keys are 40 hexadecimal characters, while API keys are 32.
+async fn api_key_looks_like_app_key() {
+    async fn warns(api_key: &str) -> bool {
+        let config = format!(r#"default_api_key = "{}""#, api_key);
+        let (config, cx) = load_sink::<DatadogLogsConfig>(&config).unwrap();
+        clear_recorded_events();
+        let _ = config.build(cx).await.unwrap();
+        contains_name("DatadogLogsApiKeyLooksLikeAppKey")
+    }
+
+    assert!(warns("0123456789abcdef0123456789abcdef01234567").await);
+    assert!(!warns("0123456789abcdef0123456789abcdef").await);
+}
+
+#[tokio::test]
+/// Assert that a backlog is drained newest first with `drain_order = "newest_first"`
+///
+/// All events are available at once, as they would be when draining a buffer
+/// after an outage, and are sent one per request, so the requests must arrive
+/// ordered by descending timestamp.
+async fn drain_order_newest_first() {
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            drain_order = "newest_first"
+            batch.max_events = 1
+            request.concurrency = 1
+        "#};
+
+    let now = Utc::now();
+    let events = (0..10)
+        .map(|i| {
+            let mut event = Event::from(format!("event {}", i));
+            let timestamp = now - chrono::Duration::minutes(10 - i);
+            event.as_mut_log().insert("timestamp", timestamp);
+            event
+        })
+        .collect::<Vec<_>>();
+
+    let payloads = run_with_config(config, events, 10).await;
+    let messages = payloads
+        .iter()
+        .map(|logs| logs[0]["message"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    let expected = (0..10)
+        .rev()
+        .map(|i| format!("event {}", i))
+        .collect::<Vec<_>>();
+    assert_eq!(messages, expected);
+}
+
+#[tokio::test]
+/// Assert that encoding batches concurrently keeps them in order
+///
+/// Events are sent one per request and vary in size, so the batches take
+/// different times to encode, yet the requests of the partition must still
+/// arrive in the order of their events.
+async fn encode_concurrency() {
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            encode_concurrency = 8
+            batch.max_events = 1
+            request.concurrency = 1
+        "#};
+
+    let events = (0..50)
+        .map(|i| Event::from(format!("event {} {}", i, "x".repeat((50 - i) * 1000))))
+        .collect::<Vec<_>>();
+
+    let payloads = run_with_config(config, events, 50).await;
+    let messages = payloads
+        .iter()
+        .map(|logs| logs[0]["message"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    let expected = (0..50)
+        .map(|i| format!("event {} {}", i, "x".repeat((50 - i) * 1000)))
+        .collect::<Vec<_>>();
+    assert_eq!(messages, expected);
+
+    let (config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            encode_concurrency = 0
+        "#})
+    .unwrap();
+    assert!(config.build(cx).await.is_err());
+}
+
+#[tokio::test]
+/// Assert that events are batched separately by size class
+///
+/// Tiny and large events are interleaved, but must be sent in distinct
+/// requests, one per size class.
+async fn size_class_partitioning() {
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            size_class_partitioning = [1024]
+        "#};
+
+    let large = "x".repeat(4096);
+    let events = (0..10)
+        .map(|i| {
+            if i % 2 == 0 {
+                Event::from("tiny")
+            } else {
+                Event::from(large.as_str())
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let payloads = run_with_config(config, events, 2).await;
+    let mut sizes = payloads
+        .iter()
+        .map(|logs| {
+            assert_eq!(logs.len(), 5);
+            let sizes = logs
+                .iter()
+                .map(|log| log["message"].as_str().unwrap().len())
+                .collect::<Vec<_>>();
+            assert!(sizes.iter().all(|size| *size == sizes[0]));
+            sizes[0]
+        })
+        .collect::<Vec<_>>();
+    sizes.sort_unstable();
+    assert_eq!(sizes, vec![4, 4096]);
+}
+
+#[tokio::test]
+/// Assert that the healthcheck can send a real body
+///
+/// Some firewalls reject requests with an empty body, failing the usual
+/// healthcheck, which `healthcheck_use_real_body` avoids by sending a single
+/// synthetic log.
+async fn healthcheck_use_real_body() {
+    async fn healthcheck(use_real_body: bool) -> (crate::Result<()>, Option<Bytes>) {
+        let (mut config, cx) = load_sink::<DatadogLogsConfig>(&format!(
+            indoc! {r#"
+                default_api_key = "atoken"
+                healthcheck_use_real_body = {}
+            "#},
+            use_real_body
+        ))
+        .unwrap();
+
+        let addr = next_addr();
+        // Swap out the endpoint so we can force send it to our local server
+        config.endpoint = Some(format!("http://{}", addr).into());
+
+        let (_, healthcheck) = config.build(cx).await.unwrap();
+
+        let (mut rx, _trigger, server) = test_server_with(addr, |_, body| {
+            if body.is_empty() {
+                response_with_status(StatusCode::FORBIDDEN)
+            } else {
+                response_with_status(StatusCode::ACCEPTED)
+            }
+        });
+        tokio::spawn(server);
+
+        let result = healthcheck.await;
+        let body = rx.next().await.map(|(_, body)| body);
+        (result, body)
+    }
+
+    let (result, _) = healthcheck(false).await;
+    assert!(result.is_err());
+
+    let (result, body) = healthcheck(true).await;
+    assert!(result.is_ok());
+    let logs = serde_json::from_slice::<Vec<serde_json::Value>>(&body.unwrap()[..]).unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0]["synthetic"], true);
+}
+
+#[tokio::test]
+/// Assert that a disabled healthcheck doesn't contact the intake
+///
+/// The healthcheck must pass without a request, and the only request received
+/// must be the batch of events.
+async fn healthcheck_disabled() {
+    let (mut config, mut cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+        "#})
+    .unwrap();
+    cx.healthcheck.enabled = false;
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, healthcheck) = config.build(cx).await.unwrap();
+
+    let (mut rx, _trigger, server) = test_server(addr, ApiStatus::OKv2);
+    tokio::spawn(server);
+
+    healthcheck.await.unwrap();
+    let () = sink
+        .run(stream::iter(vec![Event::from("a")]))
+        .await
+        .unwrap();
+
+    let (parts, body) = rx.next().await.unwrap();
+    assert_eq!(parts.method, "POST");
+    let logs = serde_json::from_slice::<Vec<serde_json::Value>>(&body[..]).unwrap();
+    assert_eq!(logs[0]["message"], "a");
+    assert!(matches!(rx.try_next(), Ok(None) | Err(_)));
+}
+
+#[tokio::test]
+/// Assert that `healthcheck_healthy_statuses` passes the healthcheck on the
+/// given statuses
+///
+/// Write-only keys are rejected by the validation endpoint, which is then
+/// expected rather than a failure.
+async fn healthcheck_healthy_statuses() {
+    async fn healthcheck(healthy_statuses: &str) -> crate::Result<()> {
+        let (mut config, cx) = load_sink::<DatadogLogsConfig>(&formatdoc! {r#"
+                default_api_key = "atoken"
+                healthcheck_healthy_statuses = {}
+            "#, healthy_statuses})
+        .unwrap();
+
+        let addr = next_addr();
+        // Swap out the endpoint so we can force send it to our local server
+        config.endpoint = Some(format!("http://{}", addr).into());
+
+        let (_, healthcheck) = config.build(cx).await.unwrap();
+
+        let (_rx, _trigger, server) =
+            test_server_with(addr, |_, _| response_with_status(StatusCode::FORBIDDEN));
+        tokio::spawn(server);
+
+        healthcheck.await
+    }
+
+    assert!(healthcheck("[]").await.is_err());
+    assert!(healthcheck("[403]").await.is_ok());
+}
+
+#[tokio::test]
+/// Assert that custom request headers are set on requests and the healthcheck
+///
+/// Gateways may require them on every request, so the healthcheck must carry
+/// them too, while the headers set by the sink itself can't be overridden.
+async fn custom_request_headers() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            request.headers.X-Tenant = "blue"
+            request.headers.X-Trace-Id = "abc123"
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, healthcheck) = config.build(cx).await.unwrap();
+
+    let (mut rx, _trigger, server) = test_server(addr, ApiStatus::OKv2);
+    tokio::spawn(server);
+
+    healthcheck.await.unwrap();
+    let () = sink
+        .run(stream::iter(vec![Event::from("event")]))
+        .await
+        .unwrap();
+
+    for _ in 0..2 {
+        let (parts, _) = rx.next().await.unwrap();
+        assert_eq!(parts.headers.get("X-Tenant").unwrap(), "blue");
+        assert_eq!(parts.headers.get("X-Trace-Id").unwrap(), "abc123");
+        assert_eq!(parts.headers.get("DD-API-KEY").unwrap(), "atoken");
+    }
+
+    let (config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            request.headers.DD-API-KEY = "another"
+        "#})
+    .unwrap();
+    assert!(config.build(cx).await.is_err());
+}
+
+#[tokio::test]
+/// Assert that requests fail over to the next endpoint of the list
+///
+/// Nothing listens on the first endpoint, so connections to it are refused,
+/// and both the healthcheck and the events must go to the second endpoint.
+async fn failover_endpoints() {
+    clear_recorded_events();
+
+    let down = next_addr();
+    let up = next_addr();
+    let (config, cx) = load_sink::<DatadogLogsConfig>(&formatdoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            endpoint = ["http://{}", "http://{}"]
+        "#, down, up})
+    .unwrap();
+
+    let (sink, healthcheck) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) = test_server(up, ApiStatus::OKv2);
+    tokio::spawn(server);
+
+    healthcheck.await.unwrap();
+    let events = (0..3).map(|i| Event::from(format!("event {}", i)));
+    let () = sink.run(stream::iter(events)).await.unwrap();
+
+    // The healthcheck and the batch of events.
+    let requests = rx.take(2).collect::<Vec<_>>().await;
+    assert_eq!(requests[0].0.uri.path(), "/api/v1/validate");
+    let logs: Vec<serde_json::Value> = serde_json::from_slice(&requests[1].1).unwrap();
+    assert_eq!(logs.len(), 3);
+    assert!(contains_name("DatadogLogsEndpointFailover"));
+
+    let (config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            endpoint = []
+        "#})
+    .unwrap();
+    assert!(config.build(cx).await.is_err());
+}
+
+#[tokio::test]
+/// Assert that adaptive concurrency backs off from an overloaded intake
+///
+/// The mock intake answers `429 Too Many Requests` while more than two
+/// requests are in flight, and records how many requests were in flight as
+/// each one arrived. Adaptive concurrency must grow until the intake is
+/// overloaded, and then drop back under its capacity once the 429s start,
+/// rather than keep overloading it, as it would with a fixed concurrency.
+async fn adaptive_concurrency() {
+    const CAPACITY: usize = 2;
+
+    #[derive(Default)]
+    struct Intake {
+        in_flight: AtomicUsize,
+        arrivals: Mutex<Vec<usize>>,
+        accepted: AtomicUsize,
+    }
+
+    let intake = Arc::new(Intake::default());
+    let addr = next_addr();
+    let service = {
+        let intake = Arc::clone(&intake);
+        make_service_fn(move |_| {
+            let intake = Arc::clone(&intake);
+            async move {
+                Ok::<_, crate::Error>(service_fn(move |_: Request<Body>| {
+                    let intake = Arc::clone(&intake);
+                    async move {
+                        let in_flight = intake.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                        intake.arrivals.lock().unwrap().push(in_flight);
+                        let status = if in_flight > CAPACITY {
+                            StatusCode::TOO_MANY_REQUESTS
+                        } else {
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                            intake.accepted.fetch_add(1, Ordering::SeqCst);
+                            StatusCode::ACCEPTED
+                        };
+                        intake.in_flight.fetch_sub(1, Ordering::SeqCst);
+                        Ok::<_, crate::Error>(response_with_status(status))
+                    }
+                }))
+            }
+        })
+    };
+    tokio::spawn(Server::bind(&addr).serve(service));
+
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            batch.max_events = 1
+            request.concurrency = "adaptive"
+            request.retry_initial_backoff_secs = 1
+        "#})
+    .unwrap();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let events = (0..60)
+        .map(|i| Event::from(format!("event {}", i)))
+        .collect::<Vec<_>>();
+    let () = sink.run(stream::iter(events)).await.unwrap();
+
+    assert_eq!(intake.accepted.load(Ordering::SeqCst), 60);
+
+    let arrivals = intake.arrivals.lock().unwrap();
+    let first_rejection = arrivals
+        .iter()
+        .position(|in_flight| *in_flight > CAPACITY)
+        .expect("concurrency never grew enough to overload the intake");
+    // The last requests arrive as the sink drains, when fewer are in flight
+    // whatever the concurrency, so they don't count towards the drop.
+    let draining = arrivals.len().saturating_sub(CAPACITY + 1);
+    let after_rejection = arrivals.get(first_rejection + 1..draining).unwrap_or(&[]);
+    assert!(
+        after_rejection
+            .iter()
+            .any(|in_flight| *in_flight <= CAPACITY),
+        "in-flight requests didn't drop after the first 429: {:?}",
+        arrivals
+    );
+}
+
+#[tokio::test]
+/// Assert that a partition failing while others are delivered is reported
+///
+/// Events of one API key are rejected while those of another are accepted,
+/// and once the rejected key has gone longer than the threshold without a
+/// successful delivery, it must be reported as stalled.
+async fn partition_stall_threshold() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            partition_stall_threshold_secs = 1
+            batch.max_events = 1
+            request.concurrency = 1
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (_rx, _trigger, server) = test_server_with(addr, |parts, _| {
+        if parts.headers.get("DD-API-KEY").unwrap() == "failing" {
+            response_with_status(StatusCode::FORBIDDEN)
+        } else {
+            response_with_status(StatusCode::ACCEPTED)
+        }
+    });
+    tokio::spawn(server);
+
+    let round = || {
+        stream::iter(vec![
+            event_with_api_key("ok", "delivering"),
+            event_with_api_key("stuck", "failing"),
+        ])
+    };
+    let events = round().chain(
+        stream::once(tokio::time::sleep(Duration::from_millis(1500))).flat_map(move |()| round()),
+    );
+
+    clear_recorded_events();
+    let () = sink.run(events).await.unwrap();
+    assert!(contains_name("DatadogLogPartitionStalled"));
+}
+
+#[tokio::test]
+/// Assert that the tags of a metric are converted into `ddtags`
+///
+/// The tags must be joined in a deterministic order, regardless of the order
+/// they were set in.
+async fn metric_tags_to_ddtags() {
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            metrics.host_tag = "host"
+        "#};
+
+    let tags = vec![("env", "prod"), ("service", "api"), ("az", "us-east-1a")]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    let metric = Metric::new(
+        "requests",
+        MetricKind::Incremental,
+        MetricValue::Counter { value: 1.0 },
+    )
+    .with_tags(Some(tags));
+
+    let payloads = run_with_config(config, vec![Event::Metric(metric)], 1).await;
+    assert_eq!(payloads[0].len(), 1);
+    assert_eq!(
+        payloads[0][0]["ddtags"],
+        "az:us-east-1a,env:prod,service:api"
+    );
+}
+
+#[tokio::test]
+/// Assert that metrics which aren't converted into logs are rejected
+///
+/// Without `metrics` configured, metrics are dropped, but their reads must
+/// still be acknowledged and the drops counted.
+async fn unconverted_metrics_are_rejected() {
+    let (mut config, mut cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+        "#})
+    .unwrap();
+    let (acker, ack_counter) = Acker::basic();
+    cx.acker = acker;
+    let addr = next_addr();
+    config.endpoint = Some(format!("http://{}", addr).into());
+    let (sink, _) = config.build(cx).await.unwrap();
+    let (rx, _trigger, server) = test_server(addr, ApiStatus::OKv2);
+    tokio::spawn(server);
+
+    let (metric_batch, metric_receiver) = BatchNotifier::new_with_receiver();
+    let (log_batch, log_receiver) = BatchNotifier::new_with_receiver();
+    let metric = Metric::new(
+        "requests",
+        MetricKind::Incremental,
+        MetricValue::Counter { value: 1.0 },
+    );
+    let events = vec![
+        Event::Metric(metric).with_batch_notifier(&metric_batch),
+        Event::from("log").with_batch_notifier(&log_batch),
+    ];
+    drop((metric_batch, log_batch));
+
+    clear_recorded_events();
+    let () = sink.run(stream::iter(events)).await.unwrap();
+    let (_, body) = rx.take(1).collect::<Vec<_>>().await.remove(0);
+    let logs = serde_json::from_slice::<Vec<serde_json::Value>>(&body[..]).unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0]["message"], "log");
+    assert_eq!(metric_receiver.await, BatchStatus::Rejected);
+    assert_eq!(log_receiver.await, BatchStatus::Delivered);
+    assert_eq!(ack_counter.load(Ordering::Relaxed), 2);
+    assert!(contains_name("DatadogLogEventDropped"));
+}
+
+#[tokio::test]
+/// Assert that the healthcheck is retried at boot
+///
+/// The validate endpoint fails twice before succeeding, which must fail the
+/// healthcheck with a single retry, and pass it with two.
+async fn healthcheck_boot_retries() {
+    async fn healthcheck(retries: usize) -> crate::Result<()> {
+        let (mut config, cx) = load_sink::<DatadogLogsConfig>(&format!(
+            indoc! {r#"
+                default_api_key = "atoken"
+                healthcheck_boot_retries = {}
+                healthcheck_boot_backoff_secs = 1
+            "#},
+            retries
+        ))
+        .unwrap();
+
+        let addr = next_addr();
+        // Swap out the endpoint so we can force send it to our local server
+        config.endpoint = Some(format!("http://{}", addr).into());
+
+        let (_, healthcheck) = config.build(cx).await.unwrap();
+
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let (_rx, _trigger, server) = test_server_with(addr, move |_, _| {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                response_with_status(StatusCode::SERVICE_UNAVAILABLE)
+            } else {
+                response_with_status(StatusCode::OK)
+            }
+        });
+        tokio::spawn(server);
+
+        healthcheck.await
+    }
+
+    assert!(healthcheck(1).await.is_err());
+    assert!(healthcheck(2).await.is_ok());
+}
+
+#[tokio::test]
+/// Assert that attribute keys with dots are handled per `dotted_key_policy`
+///
+/// By default they are sent as is, while `sanitize` replaces the dots with
+/// underscores and `reject` drops the attribute.
+async fn dotted_key_policy() {
+    async fn encode(policy: &str) -> serde_json::Value {
+        let config = format!(
+            indoc! {r#"
+                default_api_key = "atoken"
+                compression = "none"
+                dotted_key_policy = "{}"
+            "#},
+            policy
+        );
+        let mut event = Event::from("dotted");
+        event.as_mut_log().insert_flat("a.b.c", "value");
+        let payloads = run_with_config(&config, vec![event], 1).await;
+        payloads[0][0].clone()
+    }
+
+    let log = encode("nest").await;
+    assert_eq!(log["a.b.c"], "value");
+
+    let log = encode("sanitize").await;
+    assert!(log.get("a.b.c").is_none());
+    assert_eq!(log["a_b_c"], "value");
+
+    clear_recorded_events();
+    let log = encode("reject").await;
+    assert!(log.get("a.b.c").is_none());
+    assert!(log.get("a_b_c").is_none());
+    assert_eq!(log["message"], "dotted");
+    assert!(contains_name("DatadogLogDottedKeyRejected"));
+}
+
+#[tokio::test]
+/// Assert that a canary log is sent when the sink starts
+///
+/// The canary must be the first request, and the sink must fail if it is
+/// rejected.
+async fn startup_canary() {
+    async fn run(status: StatusCode) -> (Result<(), ()>, serde_json::Value) {
+        let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+                default_api_key = "atoken"
+                compression = "none"
+                startup_canary = true
+            "#})
+        .unwrap();
+
+        let addr = next_addr();
+        // Swap out the endpoint so we can force send it to our local server
+        config.endpoint = Some(format!("http://{}", addr).into());
+
+        let (sink, _) = config.build(cx).await.unwrap();
+
+        let (mut rx, _trigger, server) =
+            test_server_with(addr, move |_, _| response_with_status(status));
+        tokio::spawn(server);
+
+        let result = sink.run(stream::iter(vec![Event::from("event")])).await;
+        let (_, body) = rx.next().await.unwrap();
+        let logs = serde_json::from_slice::<Vec<serde_json::Value>>(&body[..]).unwrap();
+        assert_eq!(logs.len(), 1);
+        (result, logs[0].clone())
+    }
+
+    let (result, canary) = run(StatusCode::ACCEPTED).await;
+    assert!(result.is_ok());
+    assert_eq!(canary["canary"], true);
+    assert_eq!(canary["service"], "vector-canary");
+
+    let (result, canary) = run(StatusCode::FORBIDDEN).await;
+    assert!(result.is_err());
+    assert_eq!(canary["canary"], true);
+}
+
+#[tokio::test]
+/// Assert that partitions are held to their own request rate limits
+///
+/// The requests of a partition with a low limit must be spread out, while
+/// those of a partition with a high limit must not be held back by it.
+async fn per_partition_rate_limit() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            batch.max_events = 1
+            request.concurrency = 20
+            per_partition_rate_limit.slow = 2
+            per_partition_rate_limit.fast = 100
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) = test_server(addr, ApiStatus::OKv2);
+    tokio::spawn(server);
+
+    let events = (0..6)
+        .flat_map(|_| {
+            vec![
+                event_with_api_key("slow", "slow"),
+                event_with_api_key("fast", "fast"),
+            ]
+        })
+        .collect::<Vec<_>>();
+    tokio::spawn(sink.run(stream::iter(events)));
+
+    let start = tokio::time::Instant::now();
+    let arrivals = rx
+        .take(12)
+        .map(|(parts, _)| {
+            let api_key = parts.headers.get("DD-API-KEY").unwrap().clone();
+            (api_key, start.elapsed())
+        })
+        .collect::<Vec<_>>()
+        .await;
+    let last_arrival = |api_key: &str| {
+        arrivals
+            .iter()
+            .filter(|(key, _)| key == api_key)
+            .map(|(_, elapsed)| *elapsed)
+            .max()
+            .unwrap()
+    };
+
+    // Two requests of the slow partition fit in its initial burst, and the
+    // remaining four are spaced half a second apart.
+    assert!(last_arrival("slow") >= Duration::from_millis(1900));
+    assert!(last_arrival("fast") < Duration::from_secs(1));
+}
+
+#[tokio::test]
+/// Assert that batches end when the value of `flush_on_change_field` changes
+///
+/// Sessions alternate, and each run of a session must be sent in its own
+/// request, even when the same session comes back later.
+async fn flush_on_change_field() {
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            flush_on_change_field = "session_id"
+        "#};
+
+    let sessions = ["a", "a", "b", "b", "b", "a"];
+    let events = sessions
+        .iter()
+        .map(|session| {
+            let mut event = Event::from("event");
+            event.as_mut_log().insert("session_id", *session);
+            event
+        })
+        .collect();
+
+    let payloads = run_with_config(config, events, 3).await;
+    let mut runs = payloads
+        .iter()
+        .map(|logs| {
+            let session = logs[0]["session_id"].as_str().unwrap();
+            assert!(logs.iter().all(|log| log["session_id"] == session));
+            (session.to_string(), logs.len())
+        })
+        .collect::<Vec<_>>();
+    runs.sort();
+    assert_eq!(
+        runs,
+        vec![
+            ("a".to_string(), 1),
+            ("a".to_string(), 2),
+            ("b".to_string(), 3)
+        ]
+    );
+}
+
+#[tokio::test]
+/// Assert that batches end at a gap in the `flush_on_sequence_gap` field
+///
+/// The sequence skips from 3 to 7, so the events must be sent as two requests
+/// of contiguous sequence numbers.
+async fn flush_on_sequence_gap() {
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            flush_on_sequence_gap = "seq"
+        "#};
+
+    let events = [1, 2, 3, 7, 8]
+        .iter()
+        .map(|seq| {
+            let mut event = Event::from("event");
+            event.as_mut_log().insert("seq", *seq);
+            event
+        })
+        .collect();
+
+    let payloads = run_with_config(config, events, 2).await;
+    let mut runs = payloads
+        .iter()
+        .map(|logs| {
+            logs.iter()
+                .map(|log| log["seq"].as_i64().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    runs.sort();
+    assert_eq!(runs, vec![vec![1, 2, 3], vec![7, 8]]);
+}
+
+#[tokio::test]
+/// Assert that the batch of a run is sent as soon as the next run starts
+///
+/// The batch timeout is far longer than the test, and the stream stays open, so
+/// the first run can only be sent because the second one ended it.
+async fn ended_run_flushes_immediately() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            flush_on_change_field = "session_id"
+            batch.timeout_secs = 600
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (mut rx, _trigger, server) = test_server(addr, ApiStatus::OKv2);
+    tokio::spawn(server);
+
+    let events = ["a", "a", "b"].iter().map(|session| {
+        let mut event = Event::from("event");
+        event.as_mut_log().insert("session_id", *session);
+        event
+    });
+    tokio::spawn(sink.run(stream::iter(events).chain(stream::pending())));
+
+    let (_, body) = tokio::time::timeout(Duration::from_secs(5), rx.next())
+        .await
+        .expect("the ended run wasn't flushed")
+        .unwrap();
+    let logs = serde_json::from_slice::<Vec<serde_json::Value>>(&body[..]).unwrap();
+    assert_eq!(logs.len(), 2);
+    assert!(logs.iter().all(|log| log["session_id"] == "a"));
+}
+
+#[tokio::test]
+/// Assert that compressing events as they are encoded yields the same body as
+/// compressing their whole encoding at once
+///
+/// The same events are sent uncompressed and with gzip, and the gzip body must
+/// match the uncompressed body compressed in a single write.
+async fn streamed_compression() {
+    use std::io::Write;
+
+    async fn body(compression: &str, events: Vec<Event>) -> Bytes {
+        let (mut config, cx) = load_sink::<DatadogLogsConfig>(&formatdoc! {r#"
+                default_api_key = "atoken"
+                compression = "{}"
+            "#, compression})
+        .unwrap();
+
+        let addr = next_addr();
+        // Swap out the endpoint so we can force send it to our local server
+        config.endpoint = Some(format!("http://{}", addr).into());
+
+        let (sink, _) = config.build(cx).await.unwrap();
+
+        let (rx, _trigger, server) = test_server(addr, ApiStatus::OKv2);
+        tokio::spawn(server);
+
+        let () = sink.run(stream::iter(events)).await.unwrap();
+        rx.take(1).collect::<Vec<_>>().await.remove(0).1
+    }
+
+    let events = (0..500)
+        .map(|i| Event::from(format!("event {} {}", i, "x".repeat(i))))
+        .collect::<Vec<_>>();
+    let uncompressed = body("none", events.clone()).await;
+    let gzip = body("gzip", events).await;
+
+    let mut compressor = Compressor::from(Compression::gzip_default());
+    compressor.write_all(&uncompressed).unwrap();
+    assert_eq!(gzip, compressor.into_inner());
+}
+
+#[tokio::test]
+/// Assert that `brotli` compression is applied and advertised
+async fn smoke_json_brotli() {
+    use std::io::Read;
+
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "brotli"
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    let endpoint = format!("http://{}", addr);
+    config.endpoint = Some(endpoint.into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) =
+        test_server_with(addr, |_, _| response_with_status(StatusCode::ACCEPTED));
+    tokio::spawn(server);
+
+    let events = vec![Event::from("a"), Event::from("b")];
+    let () = sink.run(stream::iter(events)).await.unwrap();
+
+    let (parts, body) = rx.take(1).collect::<Vec<_>>().await.remove(0);
+    assert_eq!(parts.headers.get("Content-Encoding").unwrap(), "br");
+
+    let mut decompressed = Vec::new();
+    brotli::Decompressor::new(&body[..], 4096)
+        .read_to_end(&mut decompressed)
+        .unwrap();
+    let logs: Vec<serde_json::Value> = serde_json::from_slice(&decompressed).unwrap();
+    let messages = logs
+        .iter()
+        .map(|log| log["message"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(messages, vec!["a", "b"]);
+}
+
+#[tokio::test]
+/// Assert that the buffer depth gauges reflect the events held by the sink
+///
+/// The server never answers, so the events accumulate as a backlog, first in
+/// the batcher and then in the pending request, and must be counted
+/// throughout.
+async fn buffer_depth_metrics() {
+    components::init_test();
+
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            buffer_metrics_interval_secs = 1
+            batch.timeout_secs = 1
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    // Accept connections, but never respond.
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    tokio::spawn(async move {
+        let mut connections = Vec::new();
+        while let Ok((connection, _)) = listener.accept().await {
+            connections.push(connection);
+        }
+    });
+
+    let events = (0..5).map(|i| Event::from(format!("event {}", i)));
+    tokio::spawn(sink.run(stream::iter(events).chain(stream::pending())));
+
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+
+    let gauges = Controller::get()
+        .unwrap()
+        .capture_metrics()
+        .filter_map(|metric| match metric.value() {
+            MetricValue::Gauge { value } => Some((metric.name().to_string(), *value)),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+    assert_eq!(gauges["sink_buffered_events"], 5.0);
+    assert!(gauges["sink_buffered_bytes"] > 0.0);
+}
+
+#[tokio::test]
+/// Assert that `host_template` derives the host from the event's fields
+///
+/// The template takes precedence over the host field, which is kept when the
+/// template references a missing field.
+async fn host_template() {
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            host_template = "{{ region }}-{{ node }}"
+        "#};
+
+    let mut complete = Event::from("complete");
+    complete.as_mut_log().insert("host", "plain-host");
+    complete.as_mut_log().insert("region", "eu");
+    complete.as_mut_log().insert("node", "n1");
+    let mut partial = Event::from("partial");
+    partial.as_mut_log().insert("host", "plain-host");
+    partial.as_mut_log().insert("region", "eu");
+
+    let payloads = run_with_config(config, vec![complete, partial], 1).await;
+    let logs = &payloads[0];
+    assert_eq!(logs[0]["host"], "eu-n1");
+    assert_eq!(logs[1]["host"], "plain-host");
+}
+
+#[tokio::test]
+/// Assert that `omit_content_length` sends bodies chunked, without a
+/// `Content-Length` header
+async fn omit_content_length() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            omit_content_length = true
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) =
+        test_server_with(addr, |_, _| response_with_status(StatusCode::ACCEPTED));
+    tokio::spawn(server);
+
+    let events = vec![Event::from("a"), Event::from("b")];
+    let () = sink.run(stream::iter(events)).await.unwrap();
+
+    let (parts, body) = rx.take(1).collect::<Vec<_>>().await.remove(0);
+    assert!(!parts.headers.contains_key("Content-Length"));
+    assert_eq!(parts.headers.get("Transfer-Encoding").unwrap(), "chunked");
+    let logs: Vec<serde_json::Value> = serde_json::from_slice(&body[..]).unwrap();
+    assert_eq!(logs.len(), 2);
+}
+
+#[tokio::test]
+/// Assert that `add_codec_tag` stamps the compression applied to each request
+///
+/// Batches skipping compression because of `precompressed_field` must be
+/// tagged as uncompressed, even though gzip is configured.
+async fn add_codec_tag() {
+    use std::io::Read;
+
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "gzip"
+            add_codec_tag = true
+            precompressed_field = "payload"
+            batch.max_events = 1
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) =
+        test_server_with(addr, |_, _| response_with_status(StatusCode::ACCEPTED));
+    tokio::spawn(server);
+
+    let mut precompressed = Event::from("precompressed");
+    precompressed.as_mut_log().insert("payload", "H4sI");
+    let events = vec![Event::from("plain"), precompressed];
+    let () = sink.run(stream::iter(events)).await.unwrap();
+
+    let mut codecs = rx
+        .take(2)
+        .map(|(parts, body)| {
+            let body = match parts.headers.get("Content-Encoding") {
+                Some(encoding) => {
+                    assert_eq!(encoding, "gzip");
+                    let mut decompressed = Vec::new();
+                    flate2::read::GzDecoder::new(&body[..])
+                        .read_to_end(&mut decompressed)
+                        .unwrap();
+                    decompressed
+                }
+                None => body.to_vec(),
+            };
+            let logs: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+            (
+                logs[0]["message"].as_str().unwrap().to_string(),
+                logs[0]["vector.codec"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect::<Vec<_>>()
+        .await;
+    codecs.sort();
+    assert_eq!(
+        codecs,
+        vec![
+            ("plain".to_string(), "gzip".to_string()),
+            ("precompressed".to_string(), "none".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+/// Assert that logs above the per-log size limit are dropped or truncated
+///
+/// The neighboring logs of the batch must be sent unchanged either way.
+async fn on_oversized() {
+    let events = || {
+        vec![
+            Event::from("before"),
+            Event::from("x".repeat(300_000)),
+            Event::from("after"),
+        ]
+    };
+
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+        "#};
+    let payloads = run_with_config(config, events(), 1).await;
+    let messages = payloads[0]
+        .iter()
+        .map(|log| log["message"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(messages, vec!["before", "after"]);
+
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            on_oversized = "truncate"
+        "#};
+    let payloads = run_with_config(config, events(), 1).await;
+    let logs = &payloads[0];
+    assert_eq!(logs.len(), 3);
+    assert_eq!(logs[0]["message"], "before");
+    assert_eq!(logs[2]["message"], "after");
+    let truncated = logs[1]["message"].as_str().unwrap();
+    assert!(truncated.len() < 300_000);
+    assert!(truncated.chars().all(|c| c == 'x'));
+    assert!(serde_json::to_vec(&logs[1]).unwrap().len() <= 256_000);
+}
+
+#[tokio::test]
+/// Assert that the events of a batch which fails to build are reported
+///
+/// Control characters take six bytes each once escaped, so the batch of the
+/// first events exceeds the payload limit although each event is within the
+/// size limit of a log. The failure must be reported once, counting each of
+/// its events as discarded, while the next batch is still sent.
+async fn encoding_error() {
+    components::init_test();
+    clear_recorded_events();
+
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            batch.max_events = 25
+        "#};
+
+    let mut events = (0..25)
+        .map(|_| Event::from("\u{1}".repeat(40_000)))
+        .collect::<Vec<_>>();
+    events.push(Event::from("after"));
+
+    let payloads = run_with_config(config, events, 1).await;
+    assert_eq!(payloads[0][0]["message"], "after");
+    assert!(contains_name("DatadogLogEncodingError"));
+
+    let discarded = Controller::get()
+        .unwrap()
+        .capture_metrics()
+        .filter(|metric| {
+            metric.name() == "events_discarded_total"
+                && metric.tag_value("reason").as_deref() == Some("payload_too_large")
+        })
+        .map(|metric| match metric.value() {
+            MetricValue::Counter { value } => *value,
+            _ => 0.0,
+        })
+        .sum::<f64>();
+    assert_eq!(discarded, 25.0);
+}
+
+#[tokio::test]
+/// Assert that `zstd` compression is applied and advertised
+async fn smoke_json_zstd() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "zstd"
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) =
+        test_server_with(addr, |_, _| response_with_status(StatusCode::ACCEPTED));
+    tokio::spawn(server);
+
+    let events = vec![Event::from("a"), Event::from("b")];
+    let () = sink.run(stream::iter(events)).await.unwrap();
+
+    let (parts, body) = rx.take(1).collect::<Vec<_>>().await.remove(0);
+    assert_eq!(parts.headers.get("Content-Encoding").unwrap(), "zstd");
+
+    let decompressed = zstd::stream::decode_all(&body[..]).unwrap();
+    let logs: Vec<serde_json::Value> = serde_json::from_slice(&decompressed).unwrap();
+    let messages = logs
+        .iter()
+        .map(|log| log["message"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(messages, vec!["a", "b"]);
+}
+
+#[tokio::test]
+/// Assert that long attribute values are truncated, but the message is left alone
+async fn max_attribute_value_bytes() {
+    let mut event = Event::from("a message well over the limit");
+    event
+        .as_mut_log()
+        .insert("detail", "a detail well over the limit");
+
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            max_attribute_value_bytes = 10
+        "#};
+    let payloads = run_with_config(config, vec![event], 1).await;
+    let log = &payloads[0][0];
+    assert_eq!(log["detail"], "a detai...");
+    assert_eq!(log["message"], "a message well over the limit");
+}
+
+#[tokio::test]
+/// Assert that the `binary` codec sends length-prefixed frames which decode back into the events
+async fn binary_codec() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            encoding.codec = "binary"
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) =
+        test_server_with(addr, |_, _| response_with_status(StatusCode::ACCEPTED));
+    tokio::spawn(server);
+
+    let mut event = Event::from("a");
+    event.as_mut_log().insert("detail", "b");
+    let events = vec![event, Event::from("c")];
+    let () = sink.run(stream::iter(events)).await.unwrap();
+
+    let (parts, body) = rx.take(1).collect::<Vec<_>>().await.remove(0);
+    assert_eq!(
+        parts.headers.get("Content-Type").unwrap(),
+        "application/octet-stream"
+    );
+
+    let mut body = &body[..];
+    let mut events = Vec::new();
+    while !body.is_empty() {
+        let (prefix, rest) = body.split_at(4);
+        let len = u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize;
+        let (frame, rest) = rest.split_at(len);
+        events.push(Event::from(EventWrapper::decode(frame).unwrap()));
+        body = rest;
+    }
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].as_log()["message"], "a".into());
+    assert_eq!(events[0].as_log()["detail"], "b".into());
+    assert_eq!(events[1].as_log()["message"], "c".into());
+}
+
+#[tokio::test]
+/// Assert that `invalid_utf8` handles messages which aren't valid UTF-8
+///
+/// The `binary` codec sends messages as raw bytes, so by default the invalid
+/// sequence must reach the server unchanged, while it must be replaced or the
+/// event dropped with the other policies.
+async fn invalid_utf8() {
+    async fn messages(policy: &str) -> Vec<Bytes> {
+        let (mut config, cx) = load_sink::<DatadogLogsConfig>(&formatdoc! {r#"
+                default_api_key = "atoken"
+                compression = "none"
+                encoding.codec = "binary"
+                invalid_utf8 = "{}"
+            "#, policy})
+        .unwrap();
+
+        let addr = next_addr();
+        // Swap out the endpoint so we can force send it to our local server
+        config.endpoint = Some(format!("http://{}", addr).into());
+
+        let (sink, _) = config.build(cx).await.unwrap();
+
+        let (rx, _trigger, server) =
+            test_server_with(addr, |_, _| response_with_status(StatusCode::ACCEPTED));
+        tokio::spawn(server);
+
+        let invalid = Event::from(Bytes::from_static(b"bad \xff byte"));
+        let events = vec![invalid, Event::from("good")];
+        let () = sink.run(stream::iter(events)).await.unwrap();
+
+        let (_, body) = rx.take(1).collect::<Vec<_>>().await.remove(0);
+        let mut body = &body[..];
+        let mut messages = Vec::new();
+        while !body.is_empty() {
+            let (prefix, rest) = body.split_at(4);
+            let len = u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize;
+            let (frame, rest) = rest.split_at(len);
+            let event = Event::from(EventWrapper::decode(frame).unwrap());
+            messages.push(event.as_log()["message"].as_bytes());
+            body = rest;
+        }
+        messages
+    }
+
+    assert_eq!(
+        messages("pass").await,
+        [&b"bad \xff byte"[..], &b"good"[..]]
+    );
+    assert_eq!(
+        messages("replace").await,
+        ["bad \u{fffd} byte".as_bytes(), &b"good"[..]]
+    );
+    assert_eq!(messages("drop").await, [&b"good"[..]]);
+}
+
+#[tokio::test]
+/// Assert that the `cef` codec sends one valid CEF line per event
+///
+/// The message is the name of the event and its status gives the severity,
+/// with the header and extension delimiters escaped.
+async fn cef_codec() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            encoding.codec = "cef"
+            cef.device_vendor = "Acme"
+            cef.device_product = "Relay"
+            cef.device_version = "1.2"
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) =
+        test_server_with(addr, |_, _| response_with_status(StatusCode::ACCEPTED));
+    tokio::spawn(server);
+
+    let mut event = Event::from("Login failed | bad password");
+    let log = event.as_mut_log();
+    log.remove(log_schema().timestamp_key());
+    log.insert("status", "error");
+    log.insert("user", "a=b\\c");
+    let mut other = Event::from("Login succeeded");
+    other.as_mut_log().remove(log_schema().timestamp_key());
+    let () = sink.run(stream::iter(vec![event, other])).await.unwrap();
+
+    let (parts, body) = rx.take(1).collect::<Vec<_>>().await.remove(0);
+    assert_eq!(parts.headers.get("Content-Type").unwrap(), "text/plain");
+    assert_eq!(
+        String::from_utf8(body.to_vec()).unwrap(),
+        concat!(
+            "CEF:0|Acme|Relay|1.2|log|Login failed \\| bad password|8|user=a\\=b\\\\c\n",
+            "CEF:0|Acme|Relay|1.2|log|Login succeeded|5|\n",
+        )
+    );
+}
+
+#[tokio::test]
+/// Assert that a rate limited request is retried no sooner than the
+/// `Retry-After` delay, rather than after the shorter default backoff
+async fn retry_after_rate_limited() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let requests = Arc::new(AtomicUsize::new(0));
+    let (rx, _trigger, server) = test_server_with(addr, move |_, _| {
+        if requests.fetch_add(1, Ordering::SeqCst) == 0 {
+            Response::builder()
+                .status(StatusCode::TOO_MANY_REQUESTS)
+                .header("Retry-After", "2")
+                .body(Body::empty())
+                .unwrap()
+        } else {
+            response_with_status(StatusCode::ACCEPTED)
+        }
+    });
+    tokio::spawn(server);
+
+    tokio::spawn(sink.run(stream::iter(vec![Event::from("a")])));
+
+    let arrivals = rx
+        .take(2)
+        .map(|_| tokio::time::Instant::now())
+        .collect::<Vec<_>>()
+        .await;
+    assert!(arrivals[1] - arrivals[0] >= Duration::from_secs(2));
+}
+
+#[tokio::test]
+/// Assert that a retried request carries the idempotency key of the original
+///
+/// The first attempt fails with a server error, so the batch is retried, and
+/// the relay must be able to recognize the retry from its key. Another batch
+/// must get a different key.
+async fn idempotency() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            idempotency = true
+            idempotency_header = "X-Idempotency-Key"
+            batch.max_events = 1
+            request.concurrency = 1
+            request.retry_initial_backoff_secs = 1
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let requests = Arc::new(AtomicUsize::new(0));
+    let (rx, _trigger, server) = test_server_with(addr, move |_, _| {
+        if requests.fetch_add(1, Ordering::SeqCst) == 0 {
+            response_with_status(StatusCode::INTERNAL_SERVER_ERROR)
+        } else {
+            response_with_status(StatusCode::ACCEPTED)
+        }
+    });
+    tokio::spawn(server);
+
+    let events = vec![Event::from("a"), Event::from("b")];
+    tokio::spawn(sink.run(stream::iter(events)));
+
+    let keys = rx
+        .take(3)
+        .map(|(parts, _)| parts.headers["X-Idempotency-Key"].clone())
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(keys[0], keys[1]);
+    assert_ne!(keys[1], keys[2]);
+}
+
+#[tokio::test]
+/// Assert that `fill_missing_message` sends the whole event as its message
+///
+/// Only events without a message are affected, and without the option an event
+/// without a message is sent without one.
+async fn fill_missing_message() {
+    let mut without_message = Event::new_empty_log();
+    without_message.as_mut_log().insert("user", "alice");
+    let events = vec![without_message, Event::from("hello")];
+
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            fill_missing_message = true
+        "#};
+    let payloads = run_with_config(config, events.clone(), 1).await;
+    assert_eq!(payloads[0][0]["message"], r#"{"user":"alice"}"#);
+    assert_eq!(payloads[0][0]["user"], "alice");
+    assert_eq!(payloads[0][1]["message"], "hello");
+
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+        "#};
+    let payloads = run_with_config(config, events, 1).await;
+    assert!(payloads[0][0].get("message").is_none());
+}
+
+#[tokio::test]
+/// Assert that `fill_missing_timestamp` stamps events with the current time
+///
+/// Events which already have a timestamp keep it.
+async fn fill_missing_timestamp() {
+    let mut without_timestamp = Event::from("a");
+    without_timestamp
+        .as_mut_log()
+        .remove(log_schema().timestamp_key());
+    let mut with_timestamp = Event::from("b");
+    with_timestamp
+        .as_mut_log()
+        .insert(log_schema().timestamp_key(), Utc.timestamp(1_000, 0));
+
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            fill_missing_timestamp = true
+        "#};
+    let before = Utc::now().timestamp_millis();
+    let payloads = run_with_config(config, vec![without_timestamp, with_timestamp], 1).await;
+    let after = Utc::now().timestamp_millis();
+
+    let filled = payloads[0][0]["timestamp"].as_i64().unwrap();
+    assert!((before..=after).contains(&filled));
+    assert_eq!(payloads[0][1]["timestamp"], 1_000_000);
+}
+
+#[tokio::test]
+/// Assert that `self_metrics` serves counts of the sink's requests
+///
+/// The first request fails and is retried, after which all the events are
+/// delivered, which the scraped counters must reflect.
+async fn self_metrics() {
+    let metrics_addr = next_addr();
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(&formatdoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            request.retry_initial_backoff_secs = 1
+            self_metrics.address = "{}"
+        "#, metrics_addr})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let requests = Arc::new(AtomicUsize::new(0));
+    let (rx, _trigger, server) = test_server_with(addr, move |_, _| {
+        if requests.fetch_add(1, Ordering::SeqCst) == 0 {
+            response_with_status(StatusCode::INTERNAL_SERVER_ERROR)
+        } else {
+            response_with_status(StatusCode::ACCEPTED)
+        }
+    });
+    tokio::spawn(server);
+
+    // Keep the sink running, as its metrics are only served for as long as it is.
+    let events = (0..3).map(|i| Event::from(format!("event {}", i)));
+    tokio::spawn(sink.run(stream::iter(events).chain(stream::pending())));
+    let (_, body) = rx.take(2).collect::<Vec<_>>().await.remove(1);
+
+    let uri = format!("http://{}/metrics", metrics_addr).parse().unwrap();
+    let mut counters = HashMap::new();
+    for _ in 0..50 {
+        let response = hyper::Client::new().get(uri.clone()).await.unwrap();
+        let scraped = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        counters = std::str::from_utf8(&scraped[..])
+            .unwrap()
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| {
+                let (name, value) = line.split_once(' ').unwrap();
+                (name.to_string(), value.parse::<u64>().unwrap())
+            })
+            .collect::<HashMap<_, _>>();
+        if counters["vector_datadog_logs_events_sent_total"] > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(counters["vector_datadog_logs_events_sent_total"], 3);
+    assert_eq!(
+        counters["vector_datadog_logs_bytes_sent_total"],
+        body.len() as u64
+    );
+    assert_eq!(counters["vector_datadog_logs_retries_total"], 1);
+    assert_eq!(counters["vector_datadog_logs_events_dropped_total"], 0);
+    assert_eq!(counters["vector_datadog_logs_requests_in_flight"], 0);
+}
+
+#[tokio::test]
+/// Assert that `request.deadline_secs` gives up on a batch which keeps failing
+///
+/// The server always answers with a retryable error, so without a deadline the
+/// batch would be retried indefinitely. With one, its events must be finalized
+/// as errored once the deadline passes.
+async fn request_deadline() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            request.deadline_secs = 2
+            request.retry_initial_backoff_secs = 1
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (mut rx, _trigger, server) = test_server_with(addr, |_, _| {
+        response_with_status(StatusCode::SERVICE_UNAVAILABLE)
+    });
+    tokio::spawn(server);
+
+    let (batch, receiver) = BatchNotifier::new_with_receiver();
+    let event = Event::from("a").with_batch_notifier(&batch);
+    drop(batch);
+    let started = tokio::time::Instant::now();
+    tokio::spawn(sink.run(stream::iter(vec![event]).chain(stream::pending())));
+
+    let status = tokio::time::timeout(Duration::from_secs(10), receiver)
+        .await
+        .expect("batch must be given up on");
+    assert_eq!(status, BatchStatus::Errored);
+    assert!(started.elapsed() >= Duration::from_secs(2));
+
+    // The batch was retried until the deadline.
+    let mut attempts = 0;
+    while let Ok(Some(_)) = rx.try_next() {
+        attempts += 1;
+    }
+    assert!(attempts > 1, "{}", attempts);
+}
+
+#[tokio::test]
+/// Assert that the timestamp is sent as the configured attribute
+async fn reserved_attributes_timestamp() {
+    let event = Event::from("a");
+    let timestamp = event.as_log()[log_schema().timestamp_key()]
+        .as_timestamp()
+        .unwrap()
+        .timestamp_millis();
+
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            reserved_attributes.timestamp = "date"
+        "#};
+    let payloads = run_with_config(config, vec![event], 1).await;
+    let log = payloads[0][0].as_object().unwrap();
+    assert_eq!(log["date"], timestamp);
+    assert!(!log.contains_key("timestamp"));
+    assert_eq!(log["message"], "a");
+}
+
+#[tokio::test]
+/// Assert that `add_send_timestamp` stamps each event with the time its request
+/// was built
+async fn add_send_timestamp() {
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            add_send_timestamp = true
+        "#};
+    let before = Utc::now().timestamp_millis();
+    let payloads = run_with_config(config, vec![Event::from("a"), Event::from("b")], 1).await;
+    let after = Utc::now().timestamp_millis();
+
+    let logs = &payloads[0];
+    let sent_at = logs[0]["vector.sent_at"].as_i64().unwrap();
+    assert!(before <= sent_at && sent_at <= after);
+    assert_eq!(logs[1]["vector.sent_at"], sent_at);
+}
+
+#[tokio::test]
+/// Assert that `mirror_to_file` writes the uncompressed body of every request
+async fn mirror_to_file() {
+    use std::io::Read;
+
+    let path = temp_file();
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(&formatdoc! {r#"
+            default_api_key = "atoken"
+            compression = "gzip"
+            batch.max_events = 1
+            mirror_to_file.path = "{}"
+        "#, path.display()})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) =
+        test_server_with(addr, |_, _| response_with_status(StatusCode::ACCEPTED));
+    tokio::spawn(server);
+
+    let events = vec![Event::from("a"), Event::from("b")];
+    let () = sink.run(stream::iter(events)).await.unwrap();
+
+    let mut sent = rx
+        .take(2)
+        .map(|(_, body)| {
+            let mut decompressed = String::new();
+            flate2::read::GzDecoder::new(&body[..])
+                .read_to_string(&mut decompressed)
+                .unwrap();
+            decompressed
+        })
+        .collect::<Vec<_>>()
+        .await;
+    sent.sort();
+
+    // The mirror is written in the background.
+    let mut mirrored = Vec::new();
+    for _ in 0..50 {
+        let contents = std::fs::read_to_string(&path).unwrap_or_default();
+        mirrored = contents.lines().map(str::to_string).collect::<Vec<_>>();
+        if mirrored.len() == sent.len() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    mirrored.sort();
+    assert_eq!(mirrored, sent);
+}
+
+#[tokio::test]
+/// Assert that the configured default attributes are only set on events which
+/// don't already have them
+async fn default_attributes() {
+    let mut tagged = Event::from("tagged");
+    let log = tagged.as_mut_log();
+    log.insert("ddsource", "event-source");
+    log.insert("ddtags", "env:event");
+    log.insert("service", "event-service");
+    log.insert("host", "event-host");
+    let events = vec![tagged, Event::from("untagged")];
+
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            default_ddsource = "config-source"
+            default_ddtags = ["env:config", "team:logs"]
+            default_hostname = "config-host"
+            default_service = "config-service"
+        "#};
+    let payloads = run_with_config(config, events, 1).await;
+    let logs = &payloads[0];
+
+    assert_eq!(logs[0]["message"], "tagged");
+    assert_eq!(logs[0]["ddsource"], "event-source");
+    assert_eq!(logs[0]["ddtags"], "env:event");
+    assert_eq!(logs[0]["service"], "event-service");
+    assert_eq!(logs[0]["host"], "event-host");
+
+    assert_eq!(logs[1]["message"], "untagged");
+    assert_eq!(logs[1]["ddsource"], "config-source");
+    assert_eq!(logs[1]["ddtags"], "env:config,team:logs");
+    assert_eq!(logs[1]["service"], "config-service");
+    assert_eq!(logs[1]["host"], "config-host");
+}
+
+#[tokio::test]
+/// Assert that `ddtags_field` adds the contents of the field to the tags
+///
+/// The field may hold a map or an array of tags, which are added to the tags
+/// of the event, or else to the default tags. Events without the field keep
+/// their tags as they are.
+async fn ddtags_field() {
+    use std::collections::BTreeMap;
+
+    use vector_core::event::Value;
+
+    let mut map = Event::from("map");
+    let labels = ["app", "tier"]
+        .iter()
+        .zip(["web", "frontend"])
+        .map(|(key, value)| (key.to_string(), Value::from(value)))
+        .collect::<BTreeMap<_, _>>();
+    map.as_mut_log().insert("k8s.labels", labels);
+
+    let mut array = Event::from("array");
+    array
+        .as_mut_log()
+        .insert("k8s.labels", vec!["app:worker", "tier:backend"]);
+
+    let mut tagged = Event::from("tagged");
+    tagged.as_mut_log().insert("ddtags", "env:event");
+    tagged.as_mut_log().insert("k8s.labels", vec!["app:batch"]);
+
+    let events = vec![map, array, tagged, Event::from("missing")];
+
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            default_ddtags = ["env:config"]
+            ddtags_field = "k8s.labels"
+        "#};
+    let payloads = run_with_config(config, events, 1).await;
+    let logs = &payloads[0];
+
+    assert_eq!(logs[0]["ddtags"], "env:config,app:web,tier:frontend");
+    assert_eq!(logs[1]["ddtags"], "env:config,app:worker,tier:backend");
+    assert_eq!(logs[2]["ddtags"], "env:event,app:batch");
+    assert_eq!(logs[3]["ddtags"], "env:config");
+    for log in logs {
+        assert!(log["k8s"].get("labels").is_none(), "{}", log);
+    }
+}
+
+#[tokio::test]
+/// Assert that both the requests and the healthcheck go through the configured
+/// proxy
+async fn proxy() {
+    let (mut config, mut cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+        "#})
+    .unwrap();
+
+    // The endpoint is never resolved, as requests are sent to the proxy.
+    config.endpoint = Some("http://datadog.invalid".to_string().into());
+    let addr = next_addr();
+    cx.proxy = ProxyConfig {
+        http: Some(format!("http://{}", addr)),
+        ..Default::default()
+    };
+
+    let (sink, healthcheck) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) =
+        test_server_with(addr, |_, _| response_with_status(StatusCode::OK));
+    tokio::spawn(server);
+
+    healthcheck.await.unwrap();
+    let () = sink
+        .run(stream::iter(vec![Event::from("a")]))
+        .await
+        .unwrap();
+
+    let requests = rx.take(2).collect::<Vec<_>>().await;
+    for (parts, _) in &requests {
+        assert_eq!(parts.uri.host(), Some("datadog.invalid"));
+        assert_eq!(parts.headers.get("DD-API-KEY").unwrap(), "atoken");
+    }
+    assert_eq!(requests[0].0.uri.path(), "/api/v1/validate");
+    assert_eq!(requests[1].0.uri.path(), "/");
+}
+
+#[tokio::test]
+/// Assert that the `invalid_timestamp` policy is applied to events whose
+/// timestamp isn't a time value
+async fn invalid_timestamp() {
+    let events = || {
+        let mut invalid = Event::from("invalid");
+        invalid
+            .as_mut_log()
+            .insert(log_schema().timestamp_key(), true);
+        vec![invalid, Event::from("valid")]
+    };
+
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            invalid_timestamp = "now"
+        "#};
+    let before = Utc::now().timestamp_millis();
+    let payloads = run_with_config(config, events(), 1).await;
+    let after = Utc::now().timestamp_millis();
+    let logs = &payloads[0];
+    assert_eq!(logs[0]["message"], "invalid");
+    let timestamp = logs[0]["timestamp"].as_i64().unwrap();
+    assert!(before <= timestamp && timestamp <= after);
+
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            invalid_timestamp = "drop"
+        "#};
+    let payloads = run_with_config(config, events(), 1).await;
+    let messages = payloads[0]
+        .iter()
+        .map(|log| log["message"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(messages, vec!["valid"]);
+}
+
+#[tokio::test]
+/// Assert that a batch fed by steady trickle traffic is flushed once its
+/// oldest event is `batch.timeout_secs` old
+///
+/// The batch timeout starts when an event opens the batch and isn't reset by
+/// the events that follow, so it already bounds how long an event is held.
+async fn batch_timeout_bounds_trickle() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            batch.timeout_secs = 1
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) = test_server(addr, ApiStatus::OKv2);
+    tokio::spawn(server);
+
+    // An event every 200ms, well within the timeout, without end.
+    let events = stream::iter(0..).then(|i| async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        Event::from(format!("event {}", i))
+    });
+    let start = tokio::time::Instant::now();
+    tokio::spawn(sink.run(events));
+
+    let arrivals = rx
+        .take(2)
+        .map(|(_, body)| {
+            let logs: Vec<serde_json::Value> = serde_json::from_slice(&body[..]).unwrap();
+            (tokio::time::Instant::now() - start, logs.len())
+        })
+        .collect::<Vec<_>>()
+        .await;
+    for (elapsed, count) in &arrivals {
+        assert!(*count > 0 && *count <= 6, "batch of {} events", count);
+        assert!(*elapsed < Duration::from_millis(3000));
+    }
+    assert!(arrivals[1].0 - arrivals[0].0 < Duration::from_millis(1600));
+}
+
+#[tokio::test]
+/// Assert that a rejected API key fails the healthcheck as `Unauthorized`
+///
+/// Retrying can't fix the API key, so the healthcheck must fail on the first
+/// attempt even though boot retries are configured.
+async fn healthcheck_unauthorized() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            healthcheck_boot_retries = 3
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (_, healthcheck) = config.build(cx).await.unwrap();
+
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let counted = Arc::clone(&attempts);
+    let (_rx, _trigger, server) = test_server_with(addr, move |_, _| {
+        counted.fetch_add(1, Ordering::SeqCst);
+        response_with_status(StatusCode::UNAUTHORIZED)
+    });
+    tokio::spawn(server);
+
+    let error = healthcheck.await.unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<DatadogLogsError>(),
+        Some(DatadogLogsError::Unauthorized { status }) if *status == StatusCode::UNAUTHORIZED
+    ));
+    assert_eq!(
+        error.to_string(),
+        "Server rejected the API key: 401 Unauthorized."
+    );
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+/// Assert that events are batched by their values of `partition_key_fields`
+///
+/// Each service must be sent in its own request, and events without a
+/// service must share a request rather than be dropped.
+async fn partition_key_fields() {
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            partition_key_fields = ["service"]
+        "#};
+
+    let services = [Some("web"), Some("db"), None, Some("web"), Some("db")];
+    let events = services
+        .iter()
+        .map(|service| {
+            let mut event = Event::from("event");
+            if let Some(service) = service {
+                event.as_mut_log().insert("service", *service);
+            }
+            event
+        })
+        .collect();
+
+    let payloads = run_with_config(config, events, 3).await;
+    let mut partitions = payloads
+        .iter()
+        .map(|logs| {
+            let service = logs[0]["service"].as_str();
+            assert!(logs.iter().all(|log| log["service"].as_str() == service));
+            (service.map(String::from), logs.len())
+        })
+        .collect::<Vec<_>>();
+    partitions.sort();
+    assert_eq!(
+        partitions,
+        vec![
+            (None, 1),
+            (Some("db".to_string()), 2),
+            (Some("web".to_string()), 2)
+        ]
+    );
+}
+
+#[tokio::test]
+/// Assert that dropped events are summarized by reason
+///
+/// The drops all happen within one interval, so they must be reported in a
+/// single summary, emitted as the sink shuts down.
+async fn drop_summary() {
+    components::init_test();
+
+    let mut events = vec![Event::from("valid")];
+    for _ in 0..3 {
+        events.push(Event::from("x".repeat(300_000)));
+    }
+    for _ in 0..2 {
+        let mut event = Event::from("invalid");
+        event
+            .as_mut_log()
+            .insert(log_schema().timestamp_key(), true);
+        events.push(event);
+    }
+
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            invalid_timestamp = "drop"
+            drop_summary_interval_secs = 60
+        "#};
+    let payloads = run_with_config(config, events, 1).await;
+    assert_eq!(payloads[0].len(), 1);
+
+    let counters = Controller::get()
+        .unwrap()
+        .capture_metrics()
+        .filter_map(|metric| match metric.value() {
+            MetricValue::Counter { value } => {
+                let reason = metric.tag_value("reason").unwrap_or_default();
+                Some(((metric.name().to_string(), reason), *value))
+            }
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+    let counter = |name: &str, reason: &str| counters[&(name.to_string(), reason.to_string())];
+    assert_eq!(counter("drop_summaries_total", ""), 1.0);
+    assert_eq!(counter("summarized_dropped_events_total", "oversized"), 3.0);
+    assert_eq!(
+        counter("summarized_dropped_events_total", "invalid_timestamp"),
+        2.0
+    );
+}
+
+#[tokio::test]
+/// Assert that events of failed batches are never acknowledged as delivered
+/// with `strict_ack_verification`
+///
+/// The batches of one API key are rejected while those of another are
+/// accepted, and the verifier panics if any event is acknowledged as delivered
+/// without a `2xx` response.
+async fn strict_ack_verification() {
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            strict_ack_verification = true
+            batch.max_events = 1
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (_rx, _trigger, server) = test_server_with(addr, |parts, _| {
+        if parts.headers.get("DD-API-KEY").unwrap() == "failing" {
+            response_with_status(StatusCode::BAD_REQUEST)
+        } else {
+            response_with_status(StatusCode::ACCEPTED)
+        }
+    });
+    tokio::spawn(server);
+
+    let mut receivers = Vec::new();
+    let events = (0..20)
+        .map(|i| {
+            let key = if i % 2 == 0 { "delivering" } else { "failing" };
+            let (batch, receiver) = BatchNotifier::new_with_receiver();
+            receivers.push((key, receiver));
+            event_with_api_key(&format!("event {}", i), key).with_batch_notifier(&batch)
+        })
+        .collect::<Vec<_>>();
+
+    let () = sink.run(stream::iter(events)).await.unwrap();
+
+    for (key, receiver) in receivers {
+        let expected = if key == "failing" {
+            BatchStatus::Rejected
+        } else {
+            BatchStatus::Delivered
+        };
+        assert_eq!(receiver.await, expected);
+    }
+}
+
+#[tokio::test]
+/// Assert that the lines of a stack trace are coalesced into a single log
+/// with `multiline`
+///
+/// Each line starting without whitespace starts a new message, which the
+/// indented lines that follow are appended to.
+async fn multiline_stack_trace() {
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            multiline.start_pattern = '^\S'
+        "#};
+
+    let lines = [
+        "Exception in thread \"main\" java.lang.IllegalStateException: boom",
+        "    at com.example.Service.handle(Service.java:42)",
+        "    at com.example.Main.main(Main.java:7)",
+        "Request handled",
+    ];
+    let (batch, receiver) = BatchNotifier::new_with_receiver();
+    let events = lines
+        .iter()
+        .map(|line| Event::from(*line).with_batch_notifier(&batch))
+        .collect();
+    drop(batch);
+
+    let payloads = run_with_config(config, events, 1).await;
+    let messages = payloads[0]
+        .iter()
+        .map(|log| log["message"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(messages, vec![lines[..3].join("\n").as_str(), lines[3]]);
+    assert_eq!(receiver.await, BatchStatus::Delivered);
+}
+
+#[tokio::test]
+/// Assert that `multiline` acknowledges every line it coalesced, and only
+/// coalesces the lines of one source
+///
+/// The lines of two files are interleaved, so a continuation line must be
+/// appended to the message of its own file. Each of the five lines read is
+/// acknowledged, though only three messages are sent.
+async fn multiline_acks_lines_per_source() {
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            multiline.start_pattern = '^\S'
+        "#};
+    let (mut config, mut cx) = load_sink::<DatadogLogsConfig>(config).unwrap();
+    let (acker, ack_counter) = Acker::basic();
+    cx.acker = acker;
+
+    let addr = next_addr();
+    config.endpoint = Some(format!("http://{}", addr).into());
+    let (sink, _) = config.build(cx).await.unwrap();
+    let (rx, _trigger, server) = test_server(addr, ApiStatus::OKv2);
+    tokio::spawn(server);
+
+    let lines = [
+        ("a.log", "first"),
+        ("b.log", "second"),
+        ("a.log", "  continued"),
+        ("b.log", "  continued"),
+        ("a.log", "third"),
+    ];
+    let events = lines
+        .iter()
+        .map(|(file, line)| {
+            let mut event = Event::from(*line);
+            event.as_mut_log().insert("file", *file);
+            event
+        })
+        .collect::<Vec<_>>();
+    let () = sink.run(stream::iter(events)).await.unwrap();
+
+    let mut messages = rx
+        .take(1)
+        .map(|(_, body)| serde_json::from_slice::<Vec<serde_json::Value>>(&body[..]).unwrap())
+        .concat()
+        .await
+        .iter()
+        .map(|log| log["message"].as_str().unwrap().to_owned())
+        .collect::<Vec<_>>();
+    messages.sort();
+    assert_eq!(
+        messages,
+        vec!["first\n  continued", "second\n  continued", "third"]
+    );
+    assert_eq!(ack_counter.load(Ordering::Relaxed), lines.len());
+}
+
+#[tokio::test]
+/// Assert that flushed batches report how full they were
+///
+/// With room for two events, five events are flushed as two full batches and a
+/// final half-full one once the input ends.
+async fn batch_utilization() {
+    components::init_test();
+
+    let events = (0..5)
+        .map(|i| Event::from(format!("event {}", i)))
+        .collect();
+    let batches = run_with_config(
+        indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            batch.max_events = 2
+        "#},
+        events,
+        3,
+    )
+    .await;
+    assert_eq!(batches.len(), 3);
+
+    let ratios = Controller::get()
+        .unwrap()
+        .capture_metrics()
+        .filter(|metric| {
+            metric.name() == "batch_fill_ratio"
+                && metric.tag_value("limit").as_deref() == Some("events")
+        })
+        .filter_map(|metric| match metric.value() {
+            MetricValue::AggregatedHistogram { count, sum, .. } => {
+                Some((metric.tag_value("trigger").unwrap(), (*count, *sum)))
+            }
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+    assert_eq!(ratios["size"], (2, 2.0));
+    assert_eq!(ratios["timeout"], (1, 0.5));
+}
+
+#[tokio::test]
+/// Assert that built requests report their size before and after compression
+///
+/// For a gzip request, the sizes must match the decompressed and the sent
+/// body, and the ratio must be the former over the latter.
+async fn compression_ratio() {
+    use std::io::Read;
+
+    components::init_test();
+
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "gzip"
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) =
+        test_server_with(addr, |_, _| response_with_status(StatusCode::ACCEPTED));
+    tokio::spawn(server);
+
+    let events = (0..10)
+        .map(|_| Event::from("a highly compressible message"))
+        .collect::<Vec<_>>();
+    let () = sink.run(stream::iter(events)).await.unwrap();
+
+    let (_, body) = rx.take(1).collect::<Vec<_>>().await.remove(0);
+    let mut decompressed = Vec::new();
+    flate2::read::GzDecoder::new(&body[..])
+        .read_to_end(&mut decompressed)
+        .unwrap();
+
+    let sizes = Controller::get()
+        .unwrap()
+        .capture_metrics()
+        .filter(|metric| metric.tag_value("compression").as_deref() == Some("gzip"))
+        .filter_map(|metric| match metric.value() {
+            MetricValue::AggregatedHistogram { count, sum, .. } => {
+                Some((metric.name().to_string(), (*count, *sum)))
+            }
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+    let uncompressed = decompressed.len() as f64;
+    let compressed = body.len() as f64;
+    assert_eq!(sizes["request_uncompressed_bytes"], (1, uncompressed));
+    assert_eq!(sizes["request_compressed_bytes"], (1, compressed));
+    assert_eq!(
+        sizes["request_compression_ratio"],
+        (1, uncompressed / compressed)
+    );
+    assert!(uncompressed > compressed);
+}
+
+#[tokio::test]
+/// Assert that events are sent to the intake matching their routed field
+///
+/// Each event must only reach the endpoint its region maps to, while events
+/// with an unmapped region go to the default intake.
+async fn route_by_field() {
+    let eu_addr = next_addr();
+    let us_addr = next_addr();
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(&format!(
+        indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            route_by_field.field = "region"
+            route_by_field.routes.eu.endpoint = "http://{}"
+            route_by_field.default.endpoint = "http://{}"
+        "#},
+        eu_addr, us_addr
+    ))
+    .unwrap();
+
+    // The sink's own endpoint must not receive any routed event
+    config.endpoint = Some(format!("http://{}", next_addr()).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (eu_rx, _eu_trigger, eu_server) = test_server(eu_addr, ApiStatus::OKv2);
+    tokio::spawn(eu_server);
+    let (us_rx, _us_trigger, us_server) = test_server(us_addr, ApiStatus::OKv2);
+    tokio::spawn(us_server);
+
+    let mut eu = Event::from("eu");
+    eu.as_mut_log().insert("region", "eu");
+    let mut us = Event::from("us");
+    us.as_mut_log().insert("region", "us");
+    let () = sink.run(stream::iter(vec![eu, us])).await.unwrap();
+
+    let messages = |body: Bytes| {
+        serde_json::from_slice::<Vec<serde_json::Value>>(&body[..])
+            .unwrap()
+            .iter()
+            .map(|log| log["message"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    let (_, eu_body) = eu_rx.take(1).collect::<Vec<_>>().await.remove(0);
+    let (_, us_body) = us_rx.take(1).collect::<Vec<_>>().await.remove(0);
+    assert_eq!(messages(eu_body), vec!["eu"]);
+    assert_eq!(messages(us_body), vec!["us"]);
+}
+
+#[tokio::test]
+/// Assert that `flatten_attributes` flattens nested attributes into dotted keys
+///
+/// Nested maps must be keyed by their path, and arrays by the index of their
+/// elements, while the reserved attributes are left as they are.
+async fn flatten_attributes() {
+    let mut nested = Event::from("nested");
+    let log = nested.as_mut_log();
+    log.insert("http.request.method", "GET");
+    log.insert("http.status", 200);
+    log.insert("host.name", "web-1");
+
+    let mut array = Event::from("array");
+    let log = array.as_mut_log();
+    log.insert("tags", vec!["a", "b"]);
+    log.insert("empty", Vec::<String>::new());
+
+    let config = indoc! {r#"
+            default_api_key = "atoken"
+            compression = "none"
+            flatten_attributes = true
+        "#};
+    let payloads = run_with_config(config, vec![nested, array], 1).await;
+    let logs = &payloads[0];
+
+    assert_eq!(logs[0]["http.request.method"], "GET");
+    assert_eq!(logs[0]["http.status"], 200);
+    assert!(logs[0].get("http").is_none(), "{}", logs[0]);
+    assert_eq!(logs[0]["host"]["name"], "web-1");
+    assert_eq!(logs[0]["message"], "nested");
+
+    assert_eq!(logs[1]["tags.0"], "a");
+    assert_eq!(logs[1]["tags.1"], "b");
+    assert!(logs[1].get("tags").is_none(), "{}", logs[1]);
+    assert_eq!(logs[1]["empty"], serde_json::json!([]));
+}
+
+#[tokio::test]
+/// Assert that consecutive requests each hold only their own events
+///
+/// The buffers request bodies are compressed into are reused, so a long body
+/// followed by shorter ones must not leave any of its bytes behind.
+async fn reused_request_buffers() {
+    use std::io::Read;
+
+    let (mut config, cx) = load_sink::<DatadogLogsConfig>(indoc! {r#"
+            default_api_key = "atoken"
+            compression = "gzip"
+            batch.max_events = 2
+        "#})
+    .unwrap();
+
+    let addr = next_addr();
+    // Swap out the endpoint so we can force send it to our local server
+    config.endpoint = Some(format!("http://{}", addr).into());
+
+    let (sink, _) = config.build(cx).await.unwrap();
+
+    let (rx, _trigger, server) = test_server(addr, ApiStatus::OKv2);
+    tokio::spawn(server);
+
+    let expected = (0..6)
+        .rev()
+        .map(|i| format!("event {}", "x".repeat(i * 1000)))
+        .collect::<Vec<_>>();
+    let events = expected
+        .iter()
+        .map(|message| Event::from(message.as_str()))
+        .collect::<Vec<_>>();
+    let () = sink.run(stream::iter(events)).await.unwrap();
+
+    let mut messages = Vec::new();
+    for (_, body) in rx.take(3).collect::<Vec<_>>().await {
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(&body[..])
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        let logs = serde_json::from_slice::<Vec<serde_json::Value>>(&decompressed).unwrap();
+        assert_eq!(logs.len(), 2);
+        messages.extend(
+            logs.iter()
+                .map(|log| log["message"].as_str().unwrap().to_string()),
+        );
+    }
+    messages.sort();
+    let mut expected = expected;
+    expected.sort();
+    assert_eq!(messages, expected);
+}
@@ -0,0 +1,87 @@
+use std::task::{Context, Poll};
+
+use chrono::{Duration, Utc};
+use futures::{future::BoxFuture, FutureExt, TryFutureExt};
+use tower::{buffer::Buffer, Service};
+use vector_core::{
+    config::log_schema,
+    event::{Event, Value},
+};
+
+use super::service::{LogApiRequest, LogApiResponse};
+
+/// The number of requests which may be waiting for each bucket's service. Requests of the other
+/// bucket are only held back once this many requests of one bucket are waiting.
+const BUCKET_QUEUE_SIZE: usize = 32;
+
+/// Whether an event is recent, or is backfilling older data.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TimeBucket {
+    Hot,
+    Backfill,
+}
+
+impl TimeBucket {
+    /// Gets the bucket of `event`, which is hot if its timestamp is within `hot_window` of the
+    /// current time, or if it has no timestamp.
+    pub fn of(event: &Event, hot_window: Duration) -> Self {
+        match event.as_log().get(log_schema().timestamp_key()) {
+            Some(Value::Timestamp(ts)) if Utc::now() - *ts > hot_window => TimeBucket::Backfill,
+            _ => TimeBucket::Hot,
+        }
+    }
+}
+
+/// Routes requests to a separate service for each time bucket, so that backfill traffic gets its
+/// own concurrency and rate limits, and doesn't hold back recent events.
+pub struct TimeBucketRouter<S>
+where
+    S: Service<LogApiRequest>,
+{
+    hot: Buffer<S, LogApiRequest>,
+    backfill: Buffer<S, LogApiRequest>,
+}
+
+impl<S> TimeBucketRouter<S>
+where
+    S: Service<LogApiRequest, Response = LogApiResponse> + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Into<crate::Error> + Send + Sync,
+{
+    pub fn new(hot: S, backfill: S) -> Self {
+        Self {
+            hot: Buffer::new(hot, BUCKET_QUEUE_SIZE),
+            backfill: Buffer::new(backfill, BUCKET_QUEUE_SIZE),
+        }
+    }
+}
+
+impl<S> Service<LogApiRequest> for TimeBucketRouter<S>
+where
+    S: Service<LogApiRequest, Response = LogApiResponse> + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Into<crate::Error> + Send + Sync,
+{
+    type Response = LogApiResponse;
+    type Error = crate::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    // As we can't know which bucket the next request belongs to, both must be ready. Once ready,
+    // a buffer keeps its reserved slot until it is called, so this doesn't lose capacity.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let hot = self.hot.poll_ready(cx)?;
+        let backfill = self.backfill.poll_ready(cx)?;
+        if hot.is_ready() && backfill.is_ready() {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn call(&mut self, request: LogApiRequest) -> Self::Future {
+        match request.time_bucket {
+            Some(TimeBucket::Backfill) => self.backfill.call(request).err_into().boxed(),
+            Some(TimeBucket::Hot) | None => self.hot.call(request).err_into().boxed(),
+        }
+    }
+}
@@ -92,10 +92,22 @@ impl<'de> de::Deserialize<'de> for Compression {
                 match s {
                     "none" => Ok(Compression::None),
                     "gzip" => Ok(Compression::gzip_default()),
-                    _ => Err(de::Error::invalid_value(
-                        de::Unexpected::Str(s),
-                        &r#""none" or "gzip""#,
-                    )),
+                    // The level of gzip may also be given inline, as in `gzip(9)`.
+                    _ => match s
+                        .strip_prefix("gzip(")
+                        .and_then(|level| level.strip_suffix(')'))
+                    {
+                        Some(level) => gzip_level(
+                            level
+                                .parse::<i64>()
+                                .map_or_else(|_| Value::String(level.to_owned()), Value::from),
+                        )
+                        .map(Compression::Gzip),
+                        None => Err(de::Error::invalid_value(
+                            de::Unexpected::Str(s),
+                            &r#""none", "gzip" or "gzip(<level>)""#,
+                        )),
+                    },
                 }
             }
 
@@ -118,37 +130,7 @@ impl<'de> de::Deserialize<'de> for Compression {
                             if level.is_some() {
                                 return Err(de::Error::duplicate_field("level"));
                             }
-                            level = Some(match map.next_value::<Value>()? {
-                                Value::Number(level) => match level.as_u64() {
-                                    Some(value) if value <= 9 => {
-                                        flate2::Compression::new(value as u32)
-                                    }
-                                    Some(_) | None => {
-                                        return Err(de::Error::invalid_value(
-                                            de::Unexpected::Other(&level.to_string()),
-                                            &"0, 1, 2, 3, 4, 5, 6, 7, 8 or 9",
-                                        ))
-                                    }
-                                },
-                                Value::String(level) => match level.as_str() {
-                                    "none" => flate2::Compression::none(),
-                                    "fast" => flate2::Compression::fast(),
-                                    "default" => flate2::Compression::default(),
-                                    "best" => flate2::Compression::best(),
-                                    level => {
-                                        return Err(de::Error::invalid_value(
-                                            de::Unexpected::Str(level),
-                                            &r#""none", "fast", "best" or "default""#,
-                                        ))
-                                    }
-                                },
-                                value => {
-                                    return Err(de::Error::invalid_type(
-                                        de::Unexpected::Other(&value.to_string()),
-                                        &"integer or string",
-                                    ));
-                                }
-                            });
+                            level = Some(gzip_level(map.next_value::<Value>()?)?);
                         }
                         _ => return Err(de::Error::unknown_field(key, &["algorithm", "level"])),
                     };
@@ -169,6 +151,32 @@ impl<'de> de::Deserialize<'de> for Compression {
     }
 }
 
+fn gzip_level<E: de::Error>(level: Value) -> Result<flate2::Compression, E> {
+    match level {
+        Value::Number(level) => match level.as_u64() {
+            Some(value) if value <= 9 => Ok(flate2::Compression::new(value as u32)),
+            Some(_) | None => Err(de::Error::invalid_value(
+                de::Unexpected::Other(&level.to_string()),
+                &"0, 1, 2, 3, 4, 5, 6, 7, 8 or 9",
+            )),
+        },
+        Value::String(level) => match level.as_str() {
+            "none" => Ok(flate2::Compression::none()),
+            "fast" => Ok(flate2::Compression::fast()),
+            "default" => Ok(flate2::Compression::default()),
+            "best" => Ok(flate2::Compression::best()),
+            level => Err(de::Error::invalid_value(
+                de::Unexpected::Str(level),
+                &r#""none", "fast", "best" or "default""#,
+            )),
+        },
+        value => Err(de::Error::invalid_type(
+            de::Unexpected::Other(&value.to_string()),
+            &"integer or string",
+        )),
+    }
+}
+
 impl ser::Serialize for Compression {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -217,6 +225,14 @@ mod test {
                 r#"{"algorithm": "gzip", "level": 8}"#,
                 Compression::Gzip(flate2::Compression::new(8)),
             ),
+            (
+                r#""gzip(8)""#,
+                Compression::Gzip(flate2::Compression::new(8)),
+            ),
+            (
+                r#""gzip(fast)""#,
+                Compression::Gzip(flate2::Compression::fast()),
+            ),
         ];
         for (sources, result) in fixtures_valid.iter() {
             let deserialized: Result<Compression, _> = serde_json::from_str(sources);
@@ -230,7 +246,7 @@ mod test {
             ),
             (
                 r#""b42""#,
-                r#"invalid value: string "b42", expected "none" or "gzip" at line 1 column 5"#,
+                r#"invalid value: string "b42", expected "none", "gzip" or "gzip(<level>)" at line 1 column 5"#,
             ),
             (
                 r#"{"algorithm": "b42"}"#,
@@ -252,6 +268,18 @@ mod test {
                 r#"{"algorithm": "gzip", "level": {}}"#,
                 r#"invalid type: {}, expected integer or string at line 1 column 34"#,
             ),
+            (
+                r#""zstd""#,
+                r#"invalid value: string "zstd", expected "none", "gzip" or "gzip(<level>)" at line 1 column 6"#,
+            ),
+            (
+                r#"{"algorithm": "brotli"}"#,
+                r#"unknown variant `brotli`, expected `none` or `gzip` at line 1 column 23"#,
+            ),
+            (
+                r#""gzip(12)""#,
+                r#"invalid value: 12, expected 0, 1, 2, 3, 4, 5, 6, 7, 8 or 9 at line 1 column 10"#,
+            ),
             (
                 r#"{"algorithm": "gzip", "level": "default", "key": 42}"#,
                 r#"unknown field `key`, expected `algorithm` or `level` at line 1 column 47"#,
@@ -4,25 +4,22 @@ use flate2::write::{GzEncoder, ZlibEncoder};
 
 use super::Compression;
 
-enum Writer {
-    Plain(Vec<u8>),
-    Gzip(GzEncoder<Vec<u8>>),
-    Zlib(ZlibEncoder<Vec<u8>>),
+enum Writer<W> {
+    Plain(W),
+    Gzip(GzEncoder<W>),
+    Zlib(ZlibEncoder<W>),
 }
 
-impl Writer {
-    pub fn get_ref(&self) -> &Vec<u8> {
+impl<W: io::Write> Writer<W> {
+    pub fn get_ref(&self) -> &W {
         match self {
             Writer::Plain(inner) => inner,
             Writer::Gzip(inner) => inner.get_ref(),
             Writer::Zlib(inner) => inner.get_ref(),
         }
     }
-}
 
-impl From<Compression> for Writer {
-    fn from(compression: Compression) -> Self {
-        let buffer = Vec::with_capacity(1_024);
+    fn with_buffer(compression: Compression, buffer: W) -> Self {
         match compression {
             Compression::None => Writer::Plain(buffer),
             Compression::Gzip(level) => Writer::Gzip(GzEncoder::new(buffer, level)),
@@ -30,7 +27,13 @@ impl From<Compression> for Writer {
     }
 }
 
-impl io::Write for Writer {
+impl From<Compression> for Writer<Vec<u8>> {
+    fn from(compression: Compression) -> Self {
+        Self::with_buffer(compression, Vec::with_capacity(1_024))
+    }
+}
+
+impl<W: io::Write> io::Write for Writer<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         match self {
             Writer::Plain(inner_buf) => inner_buf.write(buf),
@@ -51,8 +54,10 @@ impl io::Write for Writer {
 /// Simple compressor implementation based on [`Compression`].
 ///
 /// Users can acquire a `Compressor` via [`Compressor::from`] based on the desired compression scheme.
-pub struct Compressor {
-    inner: Writer,
+/// The payload is written to a `Vec<u8>` unless another writer is given with
+/// [`Compressor::with_buffer`].
+pub struct Compressor<W = Vec<u8>> {
+    inner: Writer<W>,
 }
 
 impl Compressor {
@@ -63,8 +68,18 @@ impl Compressor {
             inner: Writer::Zlib(ZlibEncoder::new(buf, flate2::Compression::default())),
         }
     }
+}
+
+impl<W: io::Write> Compressor<W> {
+    /// Creates a compressor appending the payload to `buffer`, such as a buffer kept from an earlier
+    /// payload so that its capacity is reused.
+    pub fn with_buffer(compression: Compression, buffer: W) -> Self {
+        Self {
+            inner: Writer::with_buffer(compression, buffer),
+        }
+    }
 
-    pub fn get_ref(&self) -> &Vec<u8> {
+    pub fn get_ref(&self) -> &W {
         self.inner.get_ref()
     }
 
@@ -74,7 +89,7 @@ impl Compressor {
     ///
     /// If the compressor encounters an I/O error while finalizing the payload, an error
     /// variant will be returned.
-    pub fn finish(self) -> io::Result<Vec<u8>> {
+    pub fn finish(self) -> io::Result<W> {
         let buf = match self.inner {
             Writer::Plain(buf) => buf,
             Writer::Gzip(writer) => writer.finish()?,
@@ -93,7 +108,7 @@ impl Compressor {
     /// footer/checksum data.
     ///
     /// Consider using `finish` if catching these scenarios is important.
-    pub fn into_inner(self) -> Vec<u8> {
+    pub fn into_inner(self) -> W {
         match self.inner {
             Writer::Plain(buf) => buf,
             Writer::Gzip(writer) => writer
@@ -106,7 +121,7 @@ impl Compressor {
     }
 }
 
-impl io::Write for Compressor {
+impl<W: io::Write> io::Write for Compressor<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         self.inner.write(buf)
     }
@@ -28,6 +28,11 @@ pub trait RetryLogic: Clone + Send + Sync + 'static {
 
     fn is_retriable_error(&self, error: &Self::Error) -> bool;
 
+    /// The minimum delay before retrying after `error`, such as when the server asked for one.
+    fn retry_after(&self, _error: &Self::Error) -> Option<Duration> {
+        None
+    }
+
     fn should_retry_response(&self, _response: &Self::Response) -> RetryAction {
         // Treat the default as the request is successful
         RetryAction::Successful
@@ -80,12 +85,17 @@ impl<L: RetryLogic> FixedRetryPolicy<L> {
         self.current_duration
     }
 
-    fn build_retry(&self) -> RetryPolicyFuture<L> {
+    fn build_retry(&self, retry_after: Option<Duration>) -> RetryPolicyFuture<L> {
         let policy = self.advance();
-        let delay = Box::pin(sleep(self.backoff()));
-
-        debug!(message = "Retrying request.", delay_ms = %self.backoff().as_millis());
-        RetryPolicyFuture { delay, policy }
+        let delay = retry_after.map_or(self.backoff(), |retry_after| {
+            cmp::max(retry_after, self.backoff())
+        });
+
+        debug!(message = "Retrying request.", delay_ms = %delay.as_millis());
+        RetryPolicyFuture {
+            delay: Box::pin(sleep(delay)),
+            policy,
+        }
     }
 }
 
@@ -109,7 +119,7 @@ where
                     }
 
                     warn!(message = "Retrying after response.", reason = %reason);
-                    Some(self.build_retry())
+                    Some(self.build_retry(None))
                 }
 
                 RetryAction::DontRetry(reason) => {
@@ -128,7 +138,7 @@ where
                 if let Some(expected) = error.downcast_ref::<L::Error>() {
                     if self.logic.is_retriable_error(expected) {
                         warn!(message = "Retrying after error.", error = %expected);
-                        Some(self.build_retry())
+                        Some(self.build_retry(self.logic.retry_after(expected)))
                     } else {
                         error!(
                             message = "Non-retriable error; dropping the request.",
@@ -138,7 +148,7 @@ where
                     }
                 } else if error.downcast_ref::<Elapsed>().is_some() {
                     warn!("Request timed out. If this happens often while the events are actually reaching their destination, try decreasing `batch.max_bytes` and/or using `compression` if applicable. Alternatively `request.timeout_secs` can be increased.");
-                    Some(self.build_retry())
+                    Some(self.build_retry(None))
                 } else {
                     error!(
                         message = "Unexpected error type; dropping the request.",
@@ -18,7 +18,8 @@ _values: {
 //
 // * `none` - compression is not applied
 // * `gzip` - gzip compression applied
-#CompressionAlgorithm: "none" | "gzip" | "lz4" | "snappy" | "zstd"
+// * `smart` - gzip compression applied, unless the data barely compresses
+#CompressionAlgorithm: "none" | "brotli" | "gzip" | "lz4" | "smart" | "snappy" | "zstd"
 
 #CompressionLevel: "none" | "fast" | "default" | "best" | >=0 & <=9
 
@@ -52,7 +53,7 @@ _values: {
 // * `removed` - The component has been removed.
 #DevelopmentStatus: "beta" | "stable" | "deprecated" | "removed"
 
-#EncodingCodec: "json" | "logfmt" | "ndjson" | "text"
+#EncodingCodec: "binary" | "json" | "logfmt" | "ndjson" | "text"
 
 #Endpoint: {
 	description: string
@@ -137,6 +137,12 @@ components: sinks: [Name=string]: {
 								if algo == "gzip" {
 									gzip: "[Gzip](\(urls.gzip)) standard DEFLATE compression."
 								}
+								if algo == "brotli" {
+									brotli: "[Brotli](\(urls.brotli)) compression, at the highest quality unless a `level` from 0 to 11 is set."
+								}
+								if algo == "smart" {
+									smart: "[Gzip](\(urls.gzip)) standard DEFLATE compression, which is skipped for the API keys whose recent payloads barely compressed, as the bandwidth saved wouldn't be worth the CPU time. While skipped, payloads are still compressed periodically, so that compression resumes once the data becomes more compressible."
+								}
 								if algo == "snappy" {
 									snappy: "[Snappy](\(urls.snappy)) compression."
 								}
@@ -169,6 +175,9 @@ components: sinks: [Name=string]: {
 									let batched = features.send.encoding.codec.batched
 									enum: {
 										for codec in features.send.encoding.codec.enum {
+											if codec == "binary" {
+												binary: "Concatenation of events in Vector's protobuf encoding, each prefixed by its length as a big-endian 32-bit integer."
+											}
 											if codec == "text" {
 												if batched {
 													text: "Newline delimited list of messages generated from the message key from each event."
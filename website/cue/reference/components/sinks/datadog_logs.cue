@@ -19,17 +19,21 @@ components: sinks: datadog_logs: {
 			compression: {
 				enabled: true
 				default: "gzip"
-				algorithms: ["none", "gzip"]
+				algorithms: ["none", "gzip", "brotli", "zstd", "smart"]
 				levels: ["none", "fast", "default", "best", 0, 1, 2, 3, 4, 5, 6, 7, 8, 9]
 			}
 			encoding: {
 				enabled: true
-				codec: enabled: false
+				codec: {
+					enabled: true
+					batched: true
+					enum: ["json", "ndjson", "binary", "cef"]
+				}
 			}
 			proxy: enabled: true
 			request: {
 				enabled: true
-				headers: false
+				headers: true
 			}
 			tls: {
 				enabled:                true
@@ -59,21 +63,861 @@ components: sinks: datadog_logs: {
 	support: sinks._datadog.support
 
 	configuration: {
+		add_codec_tag: {
+			common:      false
+			description: "Stamps each event with a `vector.codec` attribute holding the compression algorithm applied to its request: `none`, `gzip`, `brotli` or `zstd`. Batches that skip compression, such as with `smart` compression or `precompressed_field`, are tagged `none`. Requests sent uncompressed because of `on_compression_rejected` keep their original tag."
+			required:    false
+			type: bool: default: false
+		}
+		add_send_timestamp: {
+			common:      false
+			description: "Stamps each event with a `vector.sent_at` attribute holding the time its request was built, in milliseconds since the epoch, so that it can be compared with the time Datadog received it to detect clock skew. All events of a request share the same time, which is kept when the request is retried."
+			required:    false
+			type: bool: default: false
+		}
+		buffer_metrics_interval_secs: {
+			common:      false
+			description: "Emits the `sink_buffered_events` and `sink_buffered_bytes` gauges at this interval, reporting the events held by the sink across all API keys, from when they are received until their request completes. This includes events waiting to be batched and events of requests in flight or being retried. Disabled when unset."
+			required:    false
+			type: uint: {
+				default: null
+				examples: [10]
+				unit: "seconds"
+			}
+		}
+		canary_on_sigusr1: {
+			common:      false
+			description: "Sends a canary log along with the events each time Vector receives a `SIGUSR1`, to verify on demand that the delivery path is live without restarting. The outcome of each canary is logged and counted in `canaries_total`, but a failed canary doesn't fail the sink. The canary has the `vector-canary` service and a `canary` attribute set to `true`. Only available on Unix."
+			required:    false
+			type: bool: default: false
+		}
+		cef: {
+			common:        false
+			description:   "The header fields identifying the sender of each line encoded in the Common Event Format (CEF), for SIEM relays which expect it rather than JSON. The message of each event is its name, its `status` attribute gives its severity, and its other attributes are written as extensions."
+			relevant_when: "encoding.codec is `cef`"
+			required:      false
+			type: object: {
+				examples: []
+				options: {
+					device_product: {
+						common:      false
+						description: "The product sending the logs."
+						required:    false
+						type: string: {
+							default: "Vector"
+						}
+					}
+					device_vendor: {
+						common:      false
+						description: "The vendor of the product sending the logs."
+						required:    false
+						type: string: {
+							default: "Vector"
+						}
+					}
+					device_version: {
+						common:      false
+						description: "The version of the product sending the logs. Defaults to the version of Vector."
+						required:    false
+						type: string: {
+							default: null
+						}
+					}
+				}
+			}
+		}
+		correlation_id_from_metadata: {
+			common:      false
+			description: "Stamps each event with the correlation ID from its metadata as the `correlation_id` attribute, for correlation with other systems. The ID is set in the metadata by tracing, or with the `set_metadata_field` function. Events without one are given a generated ID, and the `correlation_id_synthetic` attribute set to `true`."
+			required:    false
+			type: bool: default: false
+		}
+		ddtags_field: {
+			common:      false
+			description: "Moves the contents of this field into the `ddtags` attribute of each event, in addition to the tags the event already has, or else to `default_ddtags`. The field may hold a map, whose entries become `key:value` tags, or an array of tags. Events without the field keep their tags as they are."
+			required:    false
+			type: string: {
+				default: null
+				examples: ["k8s.labels"]
+				syntax: "field_path"
+			}
+		}
 		default_api_key: {
-			description: "Default Datadog [API key](https://docs.datadoghq.com/api/?lang=bash#authentication), if an event has a key set in its metadata it will prevail over the one set here."
+			description: "Default Datadog [API key](https://docs.datadoghq.com/api/?lang=bash#authentication), if an event has a key set in its metadata it will prevail over the one set here. This can also be a template of event fields, which then prevails over the key in the metadata of each event it renders for. Events it fails to render for keep the key in their metadata, or otherwise use `fallback_api_key`, which is then required."
 			required:    true
 			warnings: []
 			type: string: {
+				examples: ["${DATADOG_API_KEY_ENV_VAR}", "ef8d5de700e7989468166c40fc8a0ccd", "{{ tenant_key }}"]
+				syntax: "template"
+			}
+		}
+		default_ddsource: {
+			common:      false
+			description: "The `ddsource` attribute of events which don't already have one."
+			required:    false
+			type: string: {
+				default: null
+				examples: ["nginx"]
+			}
+		}
+		default_ddtags: {
+			common:      false
+			description: "The `ddtags` attribute of events which don't already have one, joined into the comma-separated form Datadog expects."
+			required:    false
+			type: array: {
+				default: null
+				items: type: string: {
+					examples: ["env:prod", "team:logs"]
+				}
+			}
+		}
+		default_hostname: {
+			common:      false
+			description: "The host attribute of events which don't already have one, after `host_template` and `reserved_attributes.host` are applied."
+			required:    false
+			type: string: {
+				default: null
+				examples: ["web-01"]
+			}
+		}
+		default_service: {
+			common:      false
+			description: "The `service` attribute of events which don't already have one."
+			required:    false
+			type: string: {
+				default: null
+				examples: ["checkout"]
+			}
+		}
+		deterministic_id: {
+			common:      false
+			description: "Stamps each event with an identifier derived from the values of the given fields, allowing Datadog to deduplicate events that are reprocessed."
+			required:    false
+			type: object: {
+				examples: []
+				options: {
+					fields: {
+						description: "The fields whose values are hashed to produce the identifier."
+						required:    true
+						type: array: items: type: string: {
+							examples: ["message", "service"]
+							syntax: "field_path"
+						}
+					}
+					target: {
+						common:      false
+						description: "The attribute the identifier is written to."
+						required:    false
+						type: string: {
+							default: "event_id"
+						}
+					}
+				}
+			}
+		}
+		dotted_key_policy: {
+			common:      false
+			description: "How attribute keys containing dots are handled. Datadog reads such keys as paths, so an attribute literally named `a.b.c` shows up as nested attributes. Applies to the keys of nested attributes too."
+			required:    false
+			type: string: {
+				default: "nest"
+				enum: {
+					nest:     "Sends the key as is, leaving Datadog to nest it."
+					sanitize: "Replaces the dots with underscores. A sanitized key replaces any attribute that already has its name."
+					reject:   "Drops the attribute, and reports it."
+				}
+			}
+		}
+		drain_order: {
+			common:      false
+			description: "The order in which batches that are ready at the same time are sent. This is the case while draining a backlog, for example after an outage, and prioritizing newer events lets dashboards recover first while the backfill trickles behind. Up to 64 ready batches are reordered at a time; the order of the buffer itself is unaffected."
+			required:    false
+			type: string: {
+				default: "fifo"
+				enum: {
+					fifo:         "Sends the oldest batch first."
+					lifo:         "Sends the most recently formed batch first."
+					newest_first: "Sends the batch with the newest event timestamps first."
+				}
+			}
+		}
+		drop_summary_interval_secs: {
+			common:      false
+			description: "Reports the events dropped by the sink once per interval, as a single summary with a count per reason, such as `oversized` or `invalid_timestamp`. The drops since the last summary are also reported when the sink shuts down. This is in addition to the reports of each dropped event. Disabled when unset."
+			required:    false
+			type: uint: {
+				default: null
+				examples: [60]
+				unit: "seconds"
+			}
+		}
+		dry_run: {
+			common:      false
+			description: "Only validates the configuration of the sink, including its endpoints, TLS settings and request options, and then discards events instead of sending them to Datadog. The healthcheck doesn't contact Datadog either. Useful to check configurations in CI without shipping any data."
+			required:    false
+			type: bool: default: false
+		}
+		encode_concurrency: {
+			common:      false
+			description: "The maximum number of batches encoded at the same time. Batches are encoded in parallel, on separate tasks, while their requests are still sent in the order of their batches, so the events of a partition stay in order. Raise this when encoding and compression, rather than the Datadog API, limit the throughput of the sink."
+			required:    false
+			type: uint: {
+				default: 64
+				unit:    "concurrency"
+			}
+		}
+		dual_ship: {
+			common:      false
+			description: "Ships a copy of every event to a second Datadog site, for example while migrating between organizations. Requests to the second site are retried independently, so an outage of either site doesn't hold back the other. Delivery to the second site is best effort: events are acknowledged based on the primary site alone, and copies are dropped if the second site falls too far behind."
+			required:    false
+			type: object: {
+				examples: []
+				options: {
+					api_key: {
+						description: "The API key used for all events sent to the second site."
+						required:    true
+						type: string: {
+							examples: ["${DATADOG_SECONDARY_API_KEY_ENV_VAR}"]
+						}
+					}
+					endpoint: {
+						common:        false
+						description:   "The endpoint to send the copies to."
+						relevant_when: "site is not set"
+						required:      false
+						type: string: {
+							default: null
+							examples: ["127.0.0.1:8080", "example.com:12345"]
+						}
+					}
+					site: {
+						common:        false
+						description:   "The [Datadog site](https://docs.datadoghq.com/getting_started/site) to send the copies to."
+						relevant_when: "endpoint is not set"
+						required:      false
+						type: string: {
+							default: "datadoghq.com"
+							examples: ["us3.datadoghq.com", "datadoghq.eu"]
+						}
+					}
+				}
+			}
+		}
+		endpoint: {
+			common:        false
+			description:   "The endpoint to send data to. A list of endpoints may be given instead, in order of preference: requests are sent to the first one, and fail over to the next one whenever the current one can't be connected to, such as during a regional outage. The healthcheck passes if any of them is healthy."
+			relevant_when: "site is not set"
+			required:      false
+			type: string: {
+				default: null
+				examples: ["127.0.0.1:8080", "example.com:12345"]
+			}
+		}
+		fallback_api_key: {
+			common:      false
+			description: "The API key of events without one when `default_api_key` is a template, which is required in that case. It is also the key the healthcheck uses."
+			required:    false
+			type: string: {
+				default: null
 				examples: ["${DATADOG_API_KEY_ENV_VAR}", "ef8d5de700e7989468166c40fc8a0ccd"]
 			}
 		}
-		endpoint: sinks._datadog.configuration.endpoint
-		region:   sinks._datadog.configuration.region
+		fill_missing_message: {
+			common:      false
+			description: "Sends the whole event, serialized as JSON, as the message of each event without one, as Datadog displays logs without a message as empty."
+			required:    false
+			type: bool: default: false
+		}
+		fill_missing_timestamp: {
+			common:      false
+			description: "Stamps each event without a timestamp with the time it is encoded by the sink."
+			required:    false
+			type: bool: default: false
+		}
+		flatten_attributes: {
+			common:      false
+			description: "Flattens the maps and arrays nested in each event into top-level attributes keyed by their dotted path, such as `http.request.method`, with array elements keyed by their index, such as `tags.0`, for Datadog pipelines which index nested attributes poorly. The message, host and timestamp attributes are left as they are, as are empty maps and arrays."
+			required:    false
+			type: bool: default: false
+		}
+		flush_on_change_field: {
+			common:      false
+			description: "Ends the current batch of an API key whenever the value of this field changes between consecutive events, so that each request holds a single contiguous run of events with the same value, such as one session's logs. An ended batch is sent right away, without waiting for the batch timeout."
+			required:    false
+			type: string: {
+				default: null
+				examples: ["session_id"]
+			}
+		}
+		flush_on_sequence_gap: {
+			common:      false
+			description: "Ends the current batch of an API key whenever the integer value of this field isn't one more than that of the previous event, so that each request holds a contiguous run of the sequence. Events without an integer value of the field stay in the current batch. An ended batch is sent right away, without waiting for the batch timeout."
+			required:    false
+			type: string: {
+				default: null
+				examples: ["sequence"]
+			}
+		}
+		healthcheck_boot_backoff_secs: {
+			common:      false
+			description: "The delay before the first retry of a failed healthcheck when `healthcheck_boot_retries` is set. The delay doubles after each further failure."
+			required:    false
+			type: uint: {
+				default: 1
+				unit:    "seconds"
+			}
+		}
+		healthcheck_boot_retries: {
+			common:      false
+			description: "The number of times a failed healthcheck is retried with exponential backoff before it is reported as failed, so that Datadog being briefly unavailable when Vector starts doesn't fail the healthcheck (and, with `healthcheck.enabled` and `--require-healthy`, the startup)."
+			required:    false
+			type: uint: {
+				default: 0
+				unit:    null
+			}
+		}
+		healthcheck_healthy_statuses: {
+			common:      false
+			description: "HTTP statuses which pass the healthcheck in addition to `2xx`, such as `403` when the API key is write-only and can't be validated. The healthcheck can also be disabled entirely with `healthcheck.enabled = false`, in which case Datadog isn't contacted at startup."
+			required:    false
+			type: array: {
+				default: []
+				items: type: uint: {
+					examples: [403]
+					unit: null
+				}
+			}
+		}
+		healthcheck_use_real_body: {
+			common:      false
+			description: "Makes the healthcheck send a single synthetic log to the logs intake, instead of validating the API key with a request that has an empty body, which some firewalls reject. The synthetic log has the `vector-healthcheck` service and a `synthetic` attribute set to `true`, and is indexed like any other log."
+			required:    false
+			type: bool: default: false
+		}
+		host_template: {
+			common:      false
+			description: "Derives the `host` of each event from a template of its fields, taking precedence over the host field. If the template references a field the event doesn't have, the event keeps its host field, and a rendering error is reported."
+			required:    false
+			type: string: {
+				default: null
+				examples: ["{{ region }}-{{ node }}"]
+				syntax: "template"
+			}
+		}
+		idempotency: {
+			common:      false
+			description: "Sends a key derived from the contents of each request in the `idempotency_header` header. Retries of a request carry the same key, so that a relay which supports idempotency keys can deduplicate the retries of requests it already received, such as after a timeout."
+			required:    false
+			type: bool: default: false
+		}
+		idempotency_header: {
+			common:        false
+			description:   "The header carrying the idempotency key of each request."
+			relevant_when: "idempotency = true"
+			required:      false
+			type: string: {
+				default: "Idempotency-Key"
+			}
+		}
+		implausible_timestamp_threshold_secs: {
+			common:      false
+			description: "Warns about events whose timestamp is further than this from the current time, which usually means the timestamp was produced with a precision other than milliseconds (for example, nanoseconds read as a date tens of thousands of years away). Datadog silently misplaces such events on the timeline. Integer timestamps are read as milliseconds. Disabled when unset."
+			required:    false
+			type: uint: {
+				default: null
+				examples: [86400]
+				unit: "seconds"
+			}
+		}
+		invalid_timestamp: {
+			common:      false
+			description: "How events whose timestamp isn't a time value, such as a string or a boolean, are handled, as Datadog can't parse them. A warning is logged for each such event. Doesn't apply when `reserved_attributes.timestamp` is empty, as the timestamp is then sent as is."
+			required:    false
+			type: string: {
+				default: "remove"
+				enum: {
+					remove: "Removes the timestamp, leaving Datadog to use the time it receives the log."
+					now:    "Replaces the timestamp with the current time."
+					drop:   "Drops the log."
+					leave:  "Sends the timestamp as is."
+				}
+			}
+		}
+		invalid_utf8: {
+			common:      false
+			description: "How events whose message isn't valid UTF-8 are handled, as Datadog may reject them or store them garbled. The `json`, `ndjson` and `cef` codecs already replace invalid sequences when encoding, while the `binary` codec sends the message bytes as is."
+			required:    false
+			type: string: {
+				default: "pass"
+				enum: {
+					pass:    "Sends the message as is, leaving invalid sequences to the codec."
+					replace: "Replaces invalid sequences with the Unicode replacement character."
+					drop:    "Drops the log."
+				}
+			}
+		}
+		max_bandwidth_bytes_per_sec: {
+			common:      false
+			description: "The maximum number of (compressed) request body bytes sent to Datadog per second. When exceeded, the sink applies backpressure until the budget recovers. Up to one second's worth of bytes may be sent in a burst."
+			required:    false
+			type: uint: {
+				default: null
+				examples: [1_000_000]
+				unit: "bytes"
+			}
+		}
+		max_attributes_per_event: {
+			common:      false
+			description: "The maximum number of top-level attributes of each event. Datadog stops indexing attributes beyond its own limit, which silently breaks facets, so excess attributes are dropped and reported instead. The reserved attributes (`ddsource`, `ddtags`, `host`, `message`, `service`, `status`, `timestamp` and `trace_id`) count toward the limit but are always retained, and the remaining slots go to the other attributes in alphabetical order."
+			required:    false
+			type: uint: {
+				default: null
+				examples: [256]
+				unit: null
+			}
+		}
+		max_attribute_value_bytes: {
+			common:      false
+			description: "The maximum size of each string value of the attributes of an event, including those nested within objects and arrays. Longer values are truncated, with a trailing `...` marker counted toward the limit, so that a single large attribute doesn't get the whole log rejected. The `message` is left alone, as it is covered by `on_oversized`. Unlimited when unset."
+			required:    false
+			type: uint: {
+				default: null
+				examples: [16_384]
+				unit: "bytes"
+			}
+		}
+		max_concurrent_handshakes: {
+			common:      false
+			description: "The maximum number of connections to Datadog which are established at the same time. When many partitions start sending at once, such as at startup or when concurrency scales up, the burst of TLS handshakes causes CPU spikes and handshake timeouts, which this spreads out. Unlimited when unset."
+			required:    false
+			type: uint: {
+				default: null
+				examples: [4]
+				unit: null
+			}
+		}
+		metrics: {
+			common:      false
+			description: "Accepts metrics, converting each into a log. The tags of a metric are also set as the log's `ddtags`, sorted by key, so that they remain searchable in Datadog. Without this option, and for metrics which can't be converted, metrics are rejected and counted as dropped."
+			required:    false
+			type: object: {
+				examples: []
+				options: {
+					host_tag: {
+						common:      false
+						description: "Tag key that identifies the source host."
+						required:    false
+						type: string: {
+							default: "hostname"
+							examples: ["host", "hostname"]
+						}
+					}
+					timezone: configuration._timezone
+				}
+			}
+		}
+		mirror_to_file: {
+			common:      false
+			description: "Appends the uncompressed body of every request to a local file, one body per line, to retain a copy of everything sent to Datadog. The file is written in the background, so that a slow or failing disk doesn't hold back delivery: failed writes are reported, and bodies are dropped if the writes fall too far behind."
+			required:    false
+			type: object: {
+				examples: []
+				options: {
+					max_bytes: {
+						common:      false
+						description: "The size above which the file is rotated. Rotated files are suffixed with the time of their rotation."
+						required:    false
+						type: uint: {
+							default: 104_857_600
+							unit:    "bytes"
+						}
+					}
+					path: {
+						description: "The file the request bodies are appended to."
+						required:    true
+						type: string: {
+							examples: ["/var/lib/vector/datadog_logs_mirror.log"]
+						}
+					}
+					rotate_secs: {
+						common:      false
+						description: "The age after which the file is rotated. The file is only rotated by size when unset."
+						required:    false
+						type: uint: {
+							default: null
+							examples: [3600]
+							unit: "seconds"
+						}
+					}
+				}
+			}
+		}
+		multiline: {
+			common:      false
+			description: "Coalesces the consecutive lines of one message, such as a stack trace, into a single log before batching. A log whose message matches `start_pattern` starts a new message, and the following logs of the same API key and source which don't match it are appended to that message, separated by newlines. Logs are of the same source if they have the same `host`, `file` and `stream` fields. Each line is acknowledged once the message it is part of is delivered."
+			required:    false
+			type: object: {
+				examples: []
+				options: {
+					start_pattern: {
+						description: "The regular expression matching the first line of a message."
+						required:    true
+						type: string: {
+							examples: ["^[^\\s]"]
+						}
+					}
+					timeout_ms: {
+						common:      false
+						description: "The time after the last line of a message at which it is sent, even if the next message hasn't started yet."
+						required:    false
+						type: uint: {
+							default: 1000
+							unit:    "milliseconds"
+						}
+					}
+				}
+			}
+		}
+		omit_content_length: {
+			common:      false
+			description: "Sends request bodies with chunked transfer encoding instead of a `Content-Length` header, for relays which reject requests that have the header. Datadog, and any relay in between, must accept chunked requests."
+			required:    false
+			type: bool: default: false
+		}
+		on_compression_rejected: {
+			common:      false
+			description: "Falls back to sending uncompressed requests once compressed requests are repeatedly rejected with `400 Bad Request` as their body couldn't be decoded, as some relays are misconfigured to reject compressed bodies. A rejection counts as such if the response lists the encodings it accepts without that of the request, or if its body mentions decoding or the encoding of the request. Each such rejected request is retried uncompressed right away, and once compression is disabled it stays disabled."
+			required:    false
+			type: object: {
+				examples: []
+				options: {
+					consecutive_rejections: {
+						common:      false
+						description: "The number of consecutive compressed requests rejected as their body couldn't be decoded after which compression is disabled."
+						required:    false
+						type: uint: {
+							default: 3
+							unit:    "requests"
+						}
+					}
+				}
+			}
+		}
+		on_oversized: {
+			common:      false
+			description: "The handling of logs above 256KB once encoded, which Datadog rejects along with the rest of their batch."
+			required:    false
+			type: string: {
+				default: "drop"
+				enum: {
+					drop:     "Drops the log."
+					truncate: "Shortens the `message` of the log to fit. The log is dropped if it has no message, or if shortening the message isn't enough."
+				}
+			}
+		}
+		partition_key_fields: {
+			common:      false
+			description: "Fields whose values, along with the API key, partition events into batches, so that each request only holds events with the same values, such as a single `service`. Events missing a field are batched together."
+			required:    false
+			type: array: {
+				default: []
+				items: type: string: {
+					examples: ["service", "source"]
+					syntax: "field_path"
+				}
+			}
+		}
+		partition_stall_threshold_secs: {
+			common:      false
+			description: "Warns when a partition (API key) has not been delivered successfully for longer than this, while other partitions were delivered within that time. This surfaces a single misconfigured or revoked API key whose events are stuck, which is otherwise hidden by the overall success of the sink. The API key is identified by its last four characters. Disabled when unset."
+			required:    false
+			type: uint: {
+				default: null
+				examples: [300]
+				unit: "seconds"
+			}
+		}
+		per_partition_rate_limit: {
+			common:      false
+			description: "The maximum number of requests per second sent for individual API keys, for tenants whose Datadog rate limits are lower than the sink's. Each API key is limited independently of the others and of `request.rate_limit_num`, and up to one second's worth of requests may be sent in a burst. API keys without a limit are not limited."
+			required:    false
+			type: object: {
+				examples: [{"<api-key>": 10}]
+				options: {
+					"*": {
+						common:      false
+						description: "The maximum number of requests per second sent for this API key."
+						required:    false
+						type: uint: {
+							default: null
+							examples: [10]
+							unit: "requests"
+						}
+					}
+				}
+			}
+		}
+		precompressed_field: {
+			common:      false
+			description: "A field which indicates that an event carries already compressed data, such as a base64 encoded gzip blob. Compression is skipped for batches in which most events have this field, as recompressing them gains little and wastes CPU time."
+			required:    false
+			type: string: {
+				default: null
+				examples: ["compressed_payload"]
+				syntax: "field_path"
+			}
+		}
+		region: sinks._datadog.configuration.region
+		reserved_attributes: {
+			common:      false
+			description: "The attributes that the message, host and timestamp of events are sent as, which Datadog reads specially. An empty name leaves the field under its name in the log schema, for pipelines which already produce the attributes Datadog expects."
+			required:    false
+			type: object: {
+				examples: []
+				options: {
+					host: {
+						common:      false
+						description: "The attribute the host is sent as. `host_template` also renders into this attribute."
+						required:    false
+						type: string: {
+							default: "host"
+						}
+					}
+					message: {
+						common:      false
+						description: "The attribute the message is sent as."
+						required:    false
+						type: string: {
+							default: "message"
+						}
+					}
+					timestamp: {
+						common:      false
+						description: "The attribute the timestamp is sent as, in milliseconds since the epoch. When empty, the timestamp is also left in its original format."
+						required:    false
+						type: string: {
+							default: "timestamp"
+						}
+					}
+				}
+			}
+		}
+		request: type: object: options: {
+			deadline_secs: {
+				common:      false
+				description: "The total time spent on a request, across all of its retries, after which it's given up on and its events are finalized as errored, so that a batch which keeps failing doesn't hold back the newer batches behind it. Unlike `timeout_secs`, which bounds each attempt, this bounds the request as a whole. Disabled when unset."
+				required:    false
+				type: uint: {
+					default: null
+					examples: [300]
+					unit: "seconds"
+				}
+			}
+			max_in_flight_bytes: {
+				common:      false
+				description: "The total size of the request bodies in flight, retries included, past which new requests are held back until earlier ones complete. This bounds the memory held by requests when batch sizes vary widely, and applies in addition to `concurrency`, which bounds their number. A body larger than the limit is sent on its own. Disabled when unset."
+				required:    false
+				type: uint: {
+					default: null
+					examples: [10000000]
+					unit: "bytes"
+				}
+			}
+		}
+		route_by_field: {
+			common:      false
+			description: "Sends events to a different Datadog intake depending on the value of one of their fields, for example to keep EU-resident logs on the EU site while sending the others to the US site. Events are batched by their intake in addition to their API key, and requests to a routed intake don't fail over to the other `endpoint`s. The healthcheck only checks the sink's own `endpoint` or `site`."
+			required:    false
+			type: object: {
+				examples: []
+				options: {
+					default: {
+						common:      false
+						description: "The intake of events whose value matches no route, or which don't have the field. Defaults to the sink's own `endpoint` or `site`."
+						required:    false
+						type: object: {
+							examples: []
+							options: {
+								endpoint: {
+									common:        false
+									description:   "The endpoint to send events whose value matches no route to."
+									relevant_when: "site is not set"
+									required:      false
+									type: string: {
+										default: null
+										examples: ["127.0.0.1:8080", "example.com:12345"]
+									}
+								}
+								site: {
+									common:        false
+									description:   "The [Datadog site](https://docs.datadoghq.com/getting_started/site) to send events whose value matches no route to."
+									relevant_when: "endpoint is not set"
+									required:      false
+									type: string: {
+										default: "datadoghq.com"
+										examples: ["us3.datadoghq.com", "datadoghq.eu"]
+									}
+								}
+							}
+						}
+					}
+					field: {
+						description: "The field whose value selects the intake of each event."
+						required:    true
+						type: string: {
+							examples: ["region"]
+						}
+					}
+					routes: {
+						description: "The intake of each value of the field."
+						required:    true
+						type: object: {
+							examples: [{"eu": {"site": "datadoghq.eu"}}]
+							options: {
+								"*": {
+									common:      false
+									description: "The intake of the events with this value."
+									required:    false
+									type: object: {
+										examples: []
+										options: {
+											endpoint: {
+												common:        false
+												description:   "The endpoint to send the events with this value to."
+												relevant_when: "site is not set"
+												required:      false
+												type: string: {
+													default: null
+													examples: ["127.0.0.1:8080", "example.com:12345"]
+												}
+											}
+											site: {
+												common:        false
+												description:   "The [Datadog site](https://docs.datadoghq.com/getting_started/site) to send the events with this value to."
+												relevant_when: "endpoint is not set"
+												required:      false
+												type: string: {
+													default: "datadoghq.com"
+													examples: ["us3.datadoghq.com", "datadoghq.eu"]
+												}
+											}
+										}
+									}
+								}
+							}
+						}
+					}
+				}
+			}
+		}
+		self_metrics: {
+			common:      false
+			description: "Serves counts of the sink's requests in the Prometheus text format, for environments which don't consume Vector's internal metrics. The counters `vector_datadog_logs_events_sent_total`, `vector_datadog_logs_events_dropped_total`, `vector_datadog_logs_bytes_sent_total` and `vector_datadog_logs_retries_total`, and the gauge `vector_datadog_logs_requests_in_flight`, are served at any path."
+			required:    false
+			type: object: options: {
+				address: {
+					description: "The address to serve the counts on."
+					required:    true
+					type: string: {
+						examples: ["0.0.0.0:9599"]
+					}
+				}
+			}
+		}
 		site:     sinks._datadog.configuration.site
+		size_class_partitioning: {
+			common:      false
+			description: "Byte size thresholds which split events into size classes, so that small events are batched with small events and large events with large ones, as mixing them hurts compression and batch sizing. An event's size class is the number of thresholds its size reaches, as estimated from its in-memory size."
+			required:    false
+			type: array: {
+				default: null
+				items: type: uint: {
+					examples: [1024, 65536]
+					unit: "bytes"
+				}
+			}
+		}
+		startup_canary: {
+			common:      false
+			description: "Sends a single canary log when the sink starts, before any events, through the same encoding, retries and request settings as events. The sink fails if the canary isn't accepted, surfacing a broken delivery path at boot rather than when the first events are dropped. The canary has the `vector-canary` service and a `canary` attribute set to `true`, and is indexed like any other log."
+			required:    false
+			type: bool: default: false
+		}
+		strict_ack_verification: {
+			common:      false
+			description: "Tracks every request from when it is built until its events are acknowledged, and panics if any event is acknowledged as delivered without a `2xx` response confirming the delivery of its request. This is meant for durability tests, and is only available in debug builds."
+			required:    false
+			type: bool: default: false
+		}
+		strip_control_chars: {
+			common:      false
+			description: "Removes non-printable control characters from all string fields of each event before it is encoded, which protects against binary-tainted logs that Datadog fails to parse or display."
+			required:    false
+			type: bool: default: false
+		}
+		strip_control_chars_keep_whitespace: {
+			common:      false
+			description: "Keeps tabs and newlines when stripping control characters. Only applies when `strip_control_chars` is enabled."
+			required:    false
+			type: bool: default: true
+		}
+		target_retention_days: {
+			common:      false
+			description: "The retention of the target index, in days. Events whose timestamp is older than that are dropped before sending, since Datadog drops them at ingest anyway. Each dropped event is counted in `events_discarded_total` with the `beyond_retention` reason. Events without a timestamp are always sent."
+			required:    false
+			type: uint: {
+				default: null
+				unit:    null
+			}
+		}
+		time_bucketing: {
+			common:      false
+			description: "Batches recent events separately from backfilled ones, so that backfill traffic goes through its own lower-priority path with its own request settings, and doesn't hold back recent events. Requests of one bucket are only held back once 32 requests of the other bucket are waiting."
+			required:    false
+			type: object: {
+				examples: []
+				options: {
+					backfill_request: {
+						common:      false
+						description: "The request settings of backfill events, which support the same options as `request`. Hot events use the `request` settings."
+						required:    false
+						type: object: {
+							examples: [{concurrency: 1, rate_limit_num: 10}]
+							options: {}
+						}
+					}
+					hot_window_secs: {
+						common:      false
+						description: "Events whose timestamp is within this duration of the current time are hot, and all older events are backfill. Events without a timestamp are hot."
+						required:    false
+						type: uint: {
+							default: 60
+							unit:    "seconds"
+						}
+					}
+				}
+			}
+		}
 	}
 
 	input: {
 		logs:    true
 		metrics: null
 	}
+
+	how_it_works: {
+		compression_by_codec: {
+			title: "Compression by codec"
+			body:  """
+				Payloads of each codec compress differently, so `compression` can be given
+				for each codec in place of a single value, with the codecs as keys. Codecs
+				without a compression aren't compressed.
+
+				```toml
+				compression.json = { algorithm = "gzip", level = 9 }
+				compression.binary = "zstd"
+				```
+				"""
+		}
+	}
 }
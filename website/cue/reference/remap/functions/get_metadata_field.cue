@@ -19,6 +19,11 @@ remap: functions: get_metadata_field: {
 
 					This exists if the `store_api_key` setting is true in the `datadog_agent` source.
 					"""
+				correlation_id: """
+					The correlation ID of the event, as set by tracing.
+
+					This exists if it was set, for example by `set_metadata_field`.
+					"""
 				splunk_hec_token: """
 					The Splunk HEC token.
 
@@ -17,6 +17,11 @@ remap: functions: set_metadata_field: {
 
 					This field will be used by the  `datadog_*` sinks as the API key to send the events with.
 					"""
+				correlation_id: """
+					The correlation ID of the event, as set by tracing.
+
+					This field will be used by the `datadog_logs` sink as the `correlation_id` attribute of the event, when `correlation_id_from_metadata` is enabled.
+					"""
 				splunk_hec_token: """
 					The Splunk HEC token.
 
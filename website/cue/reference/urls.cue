@@ -92,6 +92,7 @@ urls: {
 	basic_auth:                                               "\(wikipedia)/wiki/Basic_access_authentication"
 	big_query_streaming:                                      "https://cloud.google.com/bigquery/streaming-data-into-bigquery"
 	bind_dnstap:                                              "https://kb.isc.org/docs/aa-01342"
+	brotli:                                                   "https://github.com/google/brotli"
 	b_tree_map:                                               "https://doc.rust-lang.org/std/collections/struct.BTreeMap.html"
 	cargo_audit:                                              "\(github)/RustSec/cargo-audit"
 	centos:                                                   "https://www.centos.org/"